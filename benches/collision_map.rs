@@ -0,0 +1,71 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use minigene::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the bytes currently allocated, to compare the memory used by maps.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// A 4000x4000 world, the biggest square under `MAX_FLAT_TILES`, where only a few
+/// buildings collide.
+fn sparse_world(map: &mut CollisionMap) {
+    for i in 0..20 {
+        map.set_rect(i * 200 + 40, i * 200 + 40, 20, 10);
+    }
+}
+
+/// Gives the map and the bytes it allocated.
+fn measure(build: fn() -> CollisionMap) -> (CollisionMap, usize) {
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    let map = build();
+    (map, ALLOCATED.load(Ordering::SeqCst) - before)
+}
+
+fn sparse_4k(c: &mut Criterion) {
+    let (flat, flat_bytes) = measure(|| {
+        let mut map = CollisionMap::new(4_000, 4_000);
+        sparse_world(&mut map);
+        map
+    });
+    let (chunked, chunked_bytes) = measure(|| {
+        let mut map = CollisionMap::new_chunked(4_000, 4_000);
+        sparse_world(&mut map);
+        map
+    });
+    assert!(!flat.is_chunked());
+    let (start, goal) = (Point::new(30, 30), Point::new(90, 70));
+    // The memory used by each map is part of the benchmark id shown in the report.
+    let mut group = c.benchmark_group("sparse_4000x4000");
+    group.sample_size(10);
+    group.bench_with_input(
+        BenchmarkId::new("flat_a_star", format!("{}_bytes", flat_bytes)),
+        &flat,
+        |b, map| b.iter(|| find_path(black_box(map), start, goal)),
+    );
+    group.bench_with_input(
+        BenchmarkId::new("chunked_a_star", format!("{}_bytes", chunked_bytes)),
+        &chunked,
+        |b, map| b.iter(|| find_path(black_box(map), start, goal)),
+    );
+    group.finish();
+}
+
+criterion_group!(benches, sparse_4k);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use minigene::*;
+
+/// A 512x512 map with long walls alternating their gap between the bottom and the top,
+/// so that the path has to snake through the whole map.
+fn snake_map() -> CollisionMap {
+    let mut map = CollisionMap::new(512, 512);
+    for x in (8..512).step_by(16) {
+        for y in 0..512 {
+            if ((x / 16) % 2 == 0 && y < 504) || ((x / 16) % 2 == 1 && y >= 8) {
+                map.set(x, y);
+            }
+        }
+    }
+    map
+}
+
+fn full_map_a_star(c: &mut Criterion) {
+    let mut map = snake_map();
+    let (start, goal) = (Point::new(0, 0), Point::new(511, 511));
+    let mut group = c.benchmark_group("a_star_512x512");
+    group.sample_size(10);
+    group.bench_function("orthogonal", |b| {
+        b.iter(|| find_path(black_box(&map), start, goal))
+    });
+    map.set_diagonal_movement(DiagonalMovement::IfBothOpen);
+    map.set_heuristic(Heuristic::Chebyshev);
+    group.bench_function("diagonal", |b| {
+        b.iter(|| find_path(black_box(&map), start, goal))
+    });
+    group.finish();
+}
+
+fn empty_map_jps(c: &mut Criterion) {
+    let mut map = CollisionMap::new(256, 256);
+    let (start, goal) = (Point::new(0, 0), Point::new(255, 200));
+    let mut group = c.benchmark_group("empty_256x256");
+    group.sample_size(10);
+    for diagonal in &[DiagonalMovement::Never, DiagonalMovement::IfBothOpen] {
+        map.set_diagonal_movement(*diagonal);
+        map.set_heuristic(Heuristic::Chebyshev);
+        let name = format!("{:?}", diagonal);
+        group.bench_function(format!("a_star_{}", name), |b| {
+            b.iter(|| find_path(black_box(&map), start, goal))
+        });
+        group.bench_function(format!("jps_{}", name), |b| {
+            b.iter(|| find_path_jps(black_box(&map), start, goal))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, full_map_a_star, empty_map_jps);
+criterion_main!(benches);
@@ -1,23 +1,376 @@
 use crate::*;
+use hibitset::BitSetLike;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// A single colored letter sprite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Sprite {
     /// The char symbol displayed.
     pub glyph: u16,
     /// The foreground color.
     pub fg: RGBA,
-    /// The background color.
-    pub bg: RGBA,
+    /// The background color. Without one, the background below the sprite is kept.
+    pub bg: Option<RGBA>,
+}
+
+impl Sprite {
+    /// Creates a sprite using the colors of the `Palette` keys.
+    /// The colors are copied: use a `ThemedSprite` to follow changes of the palette.
+    pub fn themed(glyph: u16, fg_key: &str, bg_key: Option<&str>, palette: &Palette) -> Self {
+        Sprite {
+            glyph,
+            fg: palette.color(fg_key),
+            bg: bg_key.map(|key| palette.color(key)),
+        }
+    }
+}
+
+/// What an `AnimatedSprite` does after showing its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Starts again from the first frame.
+    Loop,
+    /// Stays on the last frame.
+    Once,
+}
+
+/// Changes the glyph of the entity's `Sprite` over time.
+#[derive(new)]
+pub struct AnimatedSprite {
+    /// The glyphs shown one after the other.
+    pub frames: Vec<u16>,
+    /// How long each frame is shown, in seconds.
+    pub frame_duration: f32,
+    /// What happens after the last frame.
+    pub mode: AnimationMode,
+    /// The time since the animation started, in seconds.
+    #[new(default)]
+    pub elapsed: f32,
+}
+
+impl AnimatedSprite {
+    /// Gives the index in `frames` of the frame currently shown.
+    pub fn current_frame(&self) -> usize {
+        if self.frames.is_empty() || self.frame_duration <= 0.0 {
+            return 0;
+        }
+        let frame = (self.elapsed / self.frame_duration) as usize;
+        match self.mode {
+            AnimationMode::Loop => frame % self.frames.len(),
+            AnimationMode::Once => frame.min(self.frames.len() - 1),
+        }
+    }
+}
+
+/// Makes the foreground color of the entity's `Sprite` go back and forth between
+/// two colors, for example to pulse a highlight.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct ColorPulse {
+    /// The color at the start of each period.
+    pub from: RGBA,
+    /// The color in the middle of each period.
+    pub to: RGBA,
+    /// How long going to `to` and back takes, in seconds.
+    pub period: f32,
+    /// The time since the pulse started, in seconds.
+    #[new(default)]
+    pub elapsed: f32,
+}
+
+impl ColorPulse {
+    /// Gives the color at the current time, easing in and out of both colors.
+    pub fn color(&self) -> RGBA {
+        if self.period <= 0.0 {
+            return self.from;
+        }
+        let phase = (self.elapsed / self.period).fract();
+        let t = (1.0 - (phase * 2.0 * std::f32::consts::PI).cos()) / 2.0;
+        lerp_rgba(self.from, self.to, t)
+    }
+}
+
+/// The order in which sprites are rendered. Higher layers are drawn on top of lower
+/// ones. Entities without a `Layer` are on layer 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Layer(pub i32);
+
+/// Mirrors and rotates the glyph of the entity's `Sprite` when rendering, so that
+/// a single glyph can face every direction. The glyph is mirrored first, then
+/// rotated clockwise.
+///
+/// Consoles can't rotate glyphs, so glyphs are swapped with their rotated or
+/// mirrored counterpart (see `rotate_glyph_cw`). Glyphs without one, like
+/// letters, are drawn unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlyphTransform {
+    /// Mirrors the glyph horizontally.
+    pub flip_x: bool,
+    /// Mirrors the glyph vertically.
+    pub flip_y: bool,
+    /// The number of clockwise quarter turns, from 0 to 3.
+    pub quarter_turns: u8,
+}
+
+impl GlyphTransform {
+    /// Rotates a glyph drawn facing `North` so that it faces this direction.
+    /// `Up` and `Down` keep the glyph unchanged.
+    pub fn facing(dir: Direction) -> Self {
+        let quarter_turns = match dir {
+            Direction::East => 1,
+            Direction::South => 2,
+            Direction::West => 3,
+            _ => 0,
+        };
+        GlyphTransform {
+            quarter_turns,
+            ..Default::default()
+        }
+    }
+
+    /// Gives the transformed glyph.
+    pub fn apply(&self, glyph: u16) -> u16 {
+        let mut glyph = glyph;
+        if self.flip_x {
+            glyph = mirror_glyph_x(glyph);
+        }
+        if self.flip_y {
+            glyph = mirror_glyph_y(glyph);
+        }
+        for _ in 0..(self.quarter_turns % 4) {
+            glyph = rotate_glyph_cw(glyph);
+        }
+        glyph
+    }
+}
+
+/// Multiplies the colors of the entity's sprite when rendering, without changing the
+/// `Sprite` itself. Applies to both the foreground and the background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tint(pub RGBA);
+
+/// Makes the entity's sprite flash: it is drawn during `on_ticks` frames, then
+/// skipped during `off_ticks` frames, and so on.
+/// The phase comes from the frame number, so entities having the same `offset`
+/// blink together.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blink {
+    /// The number of frames during which the sprite is drawn.
+    pub on_ticks: u32,
+    /// The number of frames during which the sprite is hidden.
+    pub off_ticks: u32,
+    /// Shifts the phase by this many frames.
+    #[new(default)]
+    pub offset: u32,
+}
+
+impl Blink {
+    /// Checks if the sprite is drawn during the given frame, see `Time::frame_number`.
+    pub fn is_visible(&self, frame: u64) -> bool {
+        let period = self.on_ticks as u64 + self.off_ticks as u64;
+        period == 0 || (frame + self.offset as u64) % period < self.on_ticks as u64
+    }
+}
+
+/// Draws the entity's sprite this many tiles away from its position, while the
+/// position itself stays on the tile used for collisions.
+/// Used to show smooth movement between tiles, see `interpolate_positions_system`.
+#[derive(new, Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderOffset {
+    /// The horizontal offset in tiles.
+    pub x: f32,
+    /// The vertical offset in tiles.
+    pub y: f32,
+}
+
+/// The logical positions of the entity at the last two fixed updates, recorded by
+/// `snapshot_positions_system`, so that `interpolate_positions_system` can draw it
+/// between them.
+#[derive(new, Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterpolatedPosition {
+    /// The position at the fixed update before the last one.
+    #[new(default)]
+    pub previous: Option<Point>,
+    /// The position at the last fixed update.
+    #[new(default)]
+    pub current: Option<Point>,
 }
 
 /// The index of a 2d sprite. Created from `SpriteSheet`'s index.
+/// `resolve_sprite_index_system` also uses it to look up a `Sprite` in the
+/// `SpriteDefinitions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SpriteIndex(pub usize);
 
+/// A `Sprite` whose colors are keys of the `Palette`.
+/// `resolve_themed_sprite_system` inserts the matching `Sprite`, so changing the
+/// palette recolors every entity using its keys.
+#[derive(new, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ThemedSprite {
+    /// The char symbol displayed.
+    pub glyph: u16,
+    /// The key of the foreground color.
+    pub fg: String,
+    /// The key of the background color. Without one, the background below the
+    /// sprite is kept.
+    pub bg: Option<String>,
+}
+
+/// The default `MultiSprite::transparent_glyph`: the cp437 null character, which
+/// draws nothing anyway.
+pub const TRANSPARENT_GLYPH: u16 = 0;
+
 /// A text-based sprite that is multiple tiles wide/high.
-#[derive(new)]
+/// Tiles using the `transparent_glyph` are holes: they are neither drawn nor part
+/// of the footprint, so non-rectangular shapes fit in the rectangle of tiles.
+/// REX Paint images are loaded with `from_xp`.
+#[derive(new, Debug, Clone, PartialEq)]
 pub struct MultiSprite {
-    /// The tile.
-    pub tile: MultiTileSprite,
+    /// The width in tiles.
+    pub width: u32,
+    /// The height in tiles.
+    pub height: u32,
+    /// The tiles, row by row. Should contain `width * height` sprites.
+    pub tiles: Vec<Sprite>,
+    /// The glyph of the transparent tiles. Defaults to `TRANSPARENT_GLYPH`.
+    #[new(value = "TRANSPARENT_GLYPH")]
+    pub transparent_glyph: u16,
+}
+
+impl MultiSprite {
+    /// Creates a `MultiSprite` from the characters of a string, read row by row.
+    /// Missing characters are filled with spaces.
+    pub fn from_string(content: &str, width: u32, height: u32, fg: RGBA, bg: Option<RGBA>) -> Self {
+        let mut glyphs = content.chars().map(to_cp437);
+        let tiles = (0..width * height)
+            .map(|_| Sprite {
+                glyph: glyphs.next().unwrap_or(to_cp437(' ')),
+                fg,
+                bg,
+            })
+            .collect();
+        MultiSprite::new(width, height, tiles)
+    }
+
+    /// Creates a `MultiSprite` from a REX Paint image, like `MultiTileSprite::from_xp`.
+    /// The layers are stacked in order, and cells with the transparent REX background
+    /// let the layers below show. Tiles left uncovered by every layer are holes.
+    pub fn from_xp(xp: &XpFile) -> Self {
+        let (width, height) = xp
+            .layers
+            .first()
+            .map(|l| (l.width as u32, l.height as u32))
+            .unwrap_or((0, 0));
+        let mut tiles = vec![Self::hole(TRANSPARENT_GLYPH); (width * height) as usize];
+        for layer in xp.layers.iter() {
+            for y in 0..height.min(layer.height as u32) {
+                for x in 0..width.min(layer.width as u32) {
+                    let cell = match layer.get(x as usize, y as usize) {
+                        Some(cell) if !cell.bg.is_transparent() => cell,
+                        _ => continue,
+                    };
+                    let color = |c: XpColor| RGBA::from_u8(c.r, c.g, c.b, 255);
+                    tiles[(y * width + x) as usize] = Sprite {
+                        glyph: cell.ch as u16,
+                        fg: color(cell.fg),
+                        bg: Some(color(cell.bg)),
+                    };
+                }
+            }
+        }
+        MultiSprite::new(width, height, tiles)
+    }
+
+    // An invisible tile with the given transparent glyph.
+    fn hole(glyph: u16) -> Sprite {
+        Sprite {
+            glyph,
+            fg: RGBA::from_f32(0.0, 0.0, 0.0, 0.0),
+            bg: None,
+        }
+    }
+
+    /// Sets the glyph of the transparent tiles.
+    pub fn with_transparent_glyph(mut self, glyph: u16) -> Self {
+        self.transparent_glyph = glyph;
+        self
+    }
+
+    /// Checks if the given tile is a hole. Positions outside are transparent too.
+    pub fn is_transparent(&self, x: u32, y: u32) -> bool {
+        self.get(x, y)
+            .map(|s| s.glyph == self.transparent_glyph)
+            .unwrap_or(true)
+    }
+
+    /// Gets the sprite at the given position inside of this `MultiSprite`.
+    pub fn get(&self, x: u32, y: u32) -> Option<&Sprite> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles.get((y * self.width + x) as usize)
+    }
+
+    /// Every tile covered when placed at `anchor`, row by row.
+    /// The anchor is the top-left tile, like when rendering.
+    /// Transparent tiles are left out.
+    pub fn footprint(&self, anchor: Point) -> Vec<Point> {
+        Footprint::new(self.width, self.height)
+            .tiles(anchor)
+            .into_iter()
+            .filter(|p| self.occupies(anchor, *p))
+            .collect()
+    }
+
+    /// Checks if `p` is covered by a tile that isn't transparent when placed at
+    /// `anchor`, the top-left tile.
+    pub fn occupies(&self, anchor: Point, p: Point) -> bool {
+        p.x >= anchor.x
+            && p.y >= anchor.y
+            && !self.is_transparent((p.x - anchor.x) as u32, (p.y - anchor.y) as u32)
+    }
+
+    /// Rotates clockwise by 90 degrees `quarter_turns` times.
+    pub fn rotated(&self, quarter_turns: u8) -> MultiSprite {
+        self.rotated_with(quarter_turns, &HashMap::new())
+    }
+
+    /// Rotates clockwise by 90 degrees `quarter_turns` times.
+    /// On each quarter turn, glyphs found in `remap` are replaced by their value,
+    /// which should be the same glyph rotated clockwise (ie. '─' to '│').
+    /// Missing tiles, when `tiles` is shorter than `width * height`, become holes.
+    pub fn rotated_with(&self, quarter_turns: u8, remap: &HashMap<u16, u16>) -> MultiSprite {
+        let mut sprite = self.clone();
+        for _ in 0..(quarter_turns % 4) {
+            let (w, h) = (sprite.height, sprite.width);
+            let tiles = (0..w * h)
+                .map(|i| {
+                    let (x, y) = (i % w, i / w);
+                    let mut tile = sprite
+                        .get(y, sprite.height - 1 - x)
+                        .copied()
+                        .unwrap_or_else(|| Self::hole(sprite.transparent_glyph));
+                    if let Some(glyph) = remap.get(&tile.glyph) {
+                        tile.glyph = *glyph;
+                    }
+                    tile
+                })
+                .collect();
+            sprite = MultiSprite {
+                width: w,
+                height: h,
+                tiles,
+                ..sprite
+            };
+        }
+        sprite
+    }
 }
 
 /// The path calculated by the Ai that it will follow.
@@ -27,22 +380,206 @@ pub struct AiPath {
     pub path: NavigationPath,
 }
 
+impl AiPath {
+    /// The number of steps left before reaching the end of the path.
+    pub fn remaining(&self) -> usize {
+        if self.path.success {
+            self.path.steps.len().saturating_sub(1)
+        } else {
+            0
+        }
+    }
+}
+
 /// Indicates that the ai should calculate an AiPath from the current position
 /// towards this destination.
-#[derive(new)]
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AiDestination {
     /// The destination position.
     pub target: Point,
+    /// Keeps this component when the `AiPath` is completed instead of removing it.
+    #[new(default)]
+    pub keep_on_complete: bool,
+    /// Goes to the nearest open tile when the target collides, instead of not
+    /// moving at all.
+    #[new(default)]
+    pub snap_to_open: bool,
+}
+
+/// Asks `path_request_system` to calculate a `PathResult` from the entity's current
+/// position towards the goal, once. The request is then removed.
+/// Unlike `AiDestination`, the entity doesn't follow the path, which lets it be
+/// previewed or planned ahead.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PathRequest {
+    /// The goal position.
+    pub goal: Point,
+    /// Goes to the nearest open tile when the goal collides, instead of failing.
+    #[new(default)]
+    pub snap_to_open: bool,
+}
+
+impl PathRequest {
+    /// Calculates the path from the position towards the goal using A*.
+    /// A goal outside of the map is clamped to its edge. Returns `None` if the
+    /// position is outside of the map or if the goal collides and can't, or isn't
+    /// allowed to, snap to the nearest open tile.
+    pub fn path_from(
+        &self,
+        global_map: &CollisionResource,
+        from: &Point,
+    ) -> Option<NavigationPath> {
+        if !global_map.is_inside(from) {
+            return None;
+        }
+        let (w, h) = global_map.map.size();
+        let (mut tx, mut ty) = (
+            (self.goal.x - global_map.position.x).clamp(0, w as i32 - 1) as u32,
+            (self.goal.y - global_map.position.y).clamp(0, h as i32 - 1) as u32,
+        );
+        if global_map.map.is_set(tx, ty) {
+            match global_map.map.nearest_open(tx, ty) {
+                Some(open) if self.snap_to_open => {
+                    tx = open.0;
+                    ty = open.1;
+                }
+                _ => return None,
+            }
+        }
+        let (px, py) = global_map.relative_point(from);
+        let (start, goal) = (
+            global_map.map.index_of(px, py),
+            global_map.map.index_of(tx, ty),
+        );
+        if start == goal {
+            let mut path = NavigationPath::new();
+            path.destination = goal as usize;
+            path.success = true;
+            path.steps.push(start as usize);
+            return Some(path);
+        }
+        Some(a_star_search(start, goal, &global_map.map))
+    }
+}
+
+/// The path calculated for a `PathRequest`.
+/// Movement systems ignore it: insert `AiPath::new(result.path)` to follow it.
+#[derive(new)]
+pub struct PathResult {
+    /// The path.
+    pub path: NavigationPath,
+}
+
+/// Present on entities waiting for `ai_pathing_system` to calculate their `AiPath`
+/// because the `PathingBudget` of the frame is spent. Their current `AiPath`, if
+/// any, is kept until then.
+pub struct PathPending;
+
+/// Makes `ai_movement_system` move the entity at a fixed pace instead of one tile
+/// each time it runs, so that its speed doesn't depend on the frame rate.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct MoveCooldown {
+    /// The time it takes to move one tile, in seconds.
+    pub seconds_per_tile: f32,
+    /// The time since the last step, in seconds, carried over between frames.
+    #[new(default)]
+    pub elapsed: f32,
+}
+
+impl MoveCooldown {
+    /// Adds the duration of a frame, in seconds, and gives how many tiles can be
+    /// moved during it. The time of those tiles is taken out of `elapsed`.
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        if self.seconds_per_tile <= 0.0 {
+            self.elapsed = 0.0;
+            return 1;
+        }
+        self.elapsed += delta;
+        let steps = (self.elapsed / self.seconds_per_tile) as u32;
+        self.elapsed -= steps as f32 * self.seconds_per_tile;
+        steps
+    }
 }
 
+/// Present for one frame on entities that just finished following their `AiPath`.
+pub struct PathComplete;
+
 /// Indicates that the ai should calculate an AiPath from the current position
 /// towards this destination.
 #[derive(new)]
 pub struct GotoStraight {
     /// The destination position.
     pub target: Point,
-    /// The speed at which the entity moves in tiles/second.
+    /// The speed at which the entity moves in tiles per second of
+    /// `Time::delta_time`, independently of the frame rate.
     pub speed: f32,
+    /// When set, a blocked step inserts an `AiPath` towards the target instead
+    /// of walking through the obstacle.
+    #[new(default)]
+    pub pathfind_fallback: bool,
+    /// The progress towards the next tile, from 0 to 1.
+    #[new(default)]
+    pub progress: f32,
+    /// How close to the target, in tiles, the entity snaps to it and arrives.
+    /// Clamped below 1.
+    #[new(default)]
+    pub arrival_epsilon: f32,
+}
+
+/// How a `Patrol` goes through its waypoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Goes back to the first waypoint after the last one.
+    Loop,
+    /// Goes back and forth, reversing at the first and last waypoints.
+    PingPong,
+}
+
+/// Makes the ai walk through a list of waypoints using `AiDestination`s.
+/// The component is removed by `patrol_system` when it has no waypoints.
+#[derive(new, Debug, Clone, PartialEq)]
+pub struct Patrol {
+    /// The positions to visit, in order.
+    pub waypoints: Vec<Point>,
+    /// What happens after the last waypoint.
+    pub loop_mode: LoopMode,
+    /// The index of the waypoint the entity is going towards.
+    #[new(default)]
+    pub current: usize,
+    /// Set while going back through the waypoints in `LoopMode::PingPong`.
+    #[new(default)]
+    pub reversed: bool,
+}
+
+impl Patrol {
+    /// The waypoint the entity is going towards.
+    pub fn target(&self) -> Option<Point> {
+        self.waypoints.get(self.current).copied()
+    }
+
+    /// Moves on to the next waypoint.
+    pub fn advance(&mut self) {
+        let len = self.waypoints.len();
+        if len < 2 {
+            self.current = 0;
+            return;
+        }
+        match self.loop_mode {
+            LoopMode::Loop => self.current = (self.current + 1) % len,
+            LoopMode::PingPong => {
+                if self.reversed && self.current == 0 || !self.reversed && self.current + 1 >= len {
+                    self.reversed = !self.reversed;
+                }
+                if self.reversed {
+                    self.current -= 1;
+                } else {
+                    self.current += 1;
+                }
+            }
+        }
+    }
 }
 
 /// Indicates that the ai should calculate an AiPath from the current position
@@ -53,101 +590,1704 @@ pub struct GotoEntity {
     pub entity: Entity,
     /// The speed at which the entity moves in tiles/second.
     pub speed: f32,
+    /// How far the target has to move before the `AiPath` is recalculated.
+    #[new(default)]
+    pub repath_threshold: f32,
+    /// The target position the current `AiPath` was calculated for.
+    #[new(default)]
+    pub last_target: Option<Point>,
+    /// The tiles earned by `goto_entity_simple_system` but not moved yet.
+    #[new(default)]
+    pub progress: f32,
+}
+
+/// Present for one frame on entities whose `GotoEntity` target died or lost its
+/// position. The `GotoEntity` and the `AiPath` made for it are removed at the same
+/// time.
+pub struct TargetLost;
+
+/// Forcibly moves an entity in a direction, for example when hit by an explosion.
+///
+/// When an entity has several movement components, only the first one of this
+/// order moves it, the others wait:
+/// 1. `Knockback`, with `knockback_system`.
+/// 2. `GotoStraight`, with `goto_straight_system`.
+/// 3. `GotoEntity` with `goto_entity_simple_system`, or `AiPath` with
+///    `ai_movement_system`.
+///
+/// The `AiPath` inserted by `GotoStraight::pathfind_fallback` is followed though,
+/// as the `GotoStraight` waits for it.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct Knockback {
+    /// The direction in which the entity is pushed.
+    pub direction: Direction,
+    /// The number of tiles left to move.
+    pub distance: u32,
+    /// The speed at which the entity is pushed in tiles/second.
+    pub speed: f32,
+    /// The progress towards the next tile, in tiles.
+    #[new(default)]
+    pub progress: f32,
+}
+
+/// Makes the ai run away from an entity. The opposite of `GotoEntity`.
+/// While the threat is closer than `min_distance`, `flee_entity_system` sets an
+/// `AiDestination` away from it.
+#[derive(new)]
+pub struct FleeEntity {
+    /// The entity we are running away from.
+    pub entity: Entity,
+    /// The straight line distance, in tiles, at which the entity feels safe.
+    pub min_distance: f32,
+}
+
+/// Makes the ai step around tiles crowded by other entities instead of piling
+/// onto the same tiles as everyone following a similar path, see `separation_system`.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separation {
+    /// How many entities can stand on the same tile before it counts as crowded.
+    pub max_per_tile: usize,
+}
+
+/// Makes `adjacency_system` send an `Adjacent` event when another entity comes
+/// orthogonally next to this one, for example to attack on bump or start a dialogue.
+#[derive(new, Debug, Clone, Default, PartialEq)]
+pub struct AdjacencyTrigger {
+    /// The entities that were adjacent during the last frame, which don't send
+    /// events again until they leave.
+    #[new(default)]
+    pub adjacent: Vec<Entity>,
+}
+
+/// Emits light around the entity's position, see `LightMap`.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    /// How far the light reaches, in tiles.
+    pub radius: u32,
+    /// The light level at the light's position. The full level is 1.
+    pub intensity: f32,
+}
+
+/// Sees the tiles around the entity's position, see `VisibleSet`.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewer {
+    /// How far the entity sees, in tiles.
+    pub radius: u32,
+}
+
+/// Draws a dimmed marker where the entity was last seen while it is out of the
+/// `VisibleSet`, instead of hiding it. The last seen position is recorded by
+/// `visible_set_system`.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct Remembered {
+    /// How much of the colors are kept, from 0 (black) to 1.
+    pub dim: f32,
+    /// The sprite drawn instead of the entity's own sprites, if any.
+    #[new(default)]
+    pub marker: Option<Sprite>,
+    /// The position the entity was at when it was last seen.
+    #[new(default)]
+    pub last_seen: Option<Point>,
+}
+
+impl Remembered {
+    /// Draws the sprite as the marker instead of the entity's sprites.
+    pub fn with_marker(self, marker: Sprite) -> Self {
+        Self {
+            marker: Some(marker),
+            ..self
+        }
+    }
+}
+
+/// Gates the movement of turn-based entities.
+/// The movement systems only move an entity having this component when it has
+/// points left, and spend one point per step. Entities without it move freely,
+/// which lets real-time and turn-based entities share the same systems.
+///
+/// To begin a new turn, refill the points by running `refill_action_points_system`
+/// (or by calling `ActionPoints::refill` on the entities whose turn starts).
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionPoints {
+    /// The points left in this turn.
+    pub current: u32,
+    /// The points given at the start of each turn.
+    pub max: u32,
+}
+
+impl ActionPoints {
+    /// Restores the points to `max`.
+    pub fn refill(&mut self) {
+        self.current = self.max;
+    }
+
+    /// Spends one point. Returns false if there were no points left.
+    pub fn spend(&mut self) -> bool {
+        if self.current == 0 {
+            false
+        } else {
+            self.current -= 1;
+            true
+        }
+    }
+}
+
+/// The movement type of an entity, such as `"ground"`, `"water"` or `"air"`.
+/// Pathing and movement systems use the layer of this class in the
+/// `CollisionMapStack` instead of the `CollisionResource`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MovementClass(pub String);
+
+/// Makes `face_movement_system` turn the entity's `Direction` towards the tile it
+/// moved to.
+#[derive(new, Debug, Clone, Copy, Default, PartialEq)]
+pub struct FaceMovement {
+    /// The position seen during the last frame.
+    #[new(default)]
+    pub last: Option<Point>,
+}
+
+/// Deletes the entity when its position leaves the `CollisionResource`, see
+/// `despawn_out_of_bounds_system`. Useful for projectiles.
+pub struct DespawnOutOfBounds;
+
+/// Collision of an entity. It blocks the tiles of its `Footprint`, or of its
+/// `MultiSprite` if it has no `Footprint`, or else the single tile it stands on.
+/// The transparent tiles of the `MultiSprite` don't block.
+pub struct Collision;
+
+/// The rectangle of tiles covered by an entity, starting at its position in the
+/// top-left corner like a `MultiSprite`.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footprint {
+    /// The width in tiles.
+    pub width: u32,
+    /// The height in tiles.
+    pub height: u32,
+}
+
+impl Footprint {
+    /// Gives the footprint of an entity: its `Footprint` if it has one, otherwise
+    /// the size of its `MultiSprite`, otherwise a single tile.
+    pub fn of(footprint: Option<&Footprint>, multi_sprite: Option<&MultiSprite>) -> Footprint {
+        footprint.copied().unwrap_or_else(|| {
+            multi_sprite
+                .map(|m| Footprint::new(m.width, m.height))
+                .unwrap_or_else(|| Footprint::new(1, 1))
+        })
+    }
+
+    /// Gives the tiles covered by an entity placed at `anchor`, like `Footprint::of`
+    /// but leaving out the transparent tiles of the `MultiSprite`.
+    pub fn tiles_of(
+        footprint: Option<&Footprint>,
+        multi_sprite: Option<&MultiSprite>,
+        anchor: Point,
+    ) -> Vec<Point> {
+        match (footprint, multi_sprite) {
+            (None, Some(multi_sprite)) => multi_sprite.footprint(anchor),
+            _ => Footprint::of(footprint, multi_sprite).tiles(anchor),
+        }
+    }
+
+    /// Every tile covered when placed at `anchor`, row by row.
+    pub fn tiles(&self, anchor: Point) -> Vec<Point> {
+        (0..self.height as i32)
+            .flat_map(|y| {
+                (0..self.width as i32).map(move |x| Point::new(anchor.x + x, anchor.y + y))
+            })
+            .collect()
+    }
+}
+
+/// Error returned when accessing a position outside of a `CollisionMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+    /// The requested position.
+    pub position: (u32, u32),
+    /// The size of the map.
+    pub size: (u32, u32),
+}
+
+impl std::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Position ({}, {}) is outside of the {}x{} collision map.",
+            self.position.0, self.position.1, self.size.0, self.size.1
+        )
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+/// Error returned when combining `CollisionMap`s of different sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeMismatch {
+    /// The size of the map being modified.
+    pub expected: (u32, u32),
+    /// The size of the other map.
+    pub found: (u32, u32),
+}
+
+impl std::fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Expected a {}x{} collision map, got {}x{}.",
+            self.expected.0, self.expected.1, self.found.0, self.found.1
+        )
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+/// Error returned when a `CollisionMap` can't be built from a text layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The layout doesn't contain any tile.
+    Empty,
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LayoutError::Empty => write!(f, "The collision map layout is empty."),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// The distance estimate used by A* on a `CollisionMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Heuristic {
+    /// `dx + dy`. Exact when only moving orthogonally, but overestimates when
+    /// diagonal movement is enabled.
+    #[default]
+    Manhattan,
+    /// The straight line distance. Never overestimates.
+    Euclidean,
+    /// `max(dx, dy)`. Never overestimates, even with diagonal movement.
+    Chebyshev,
+    /// `D * max(dx, dy) + (D2 - D) * min(dx, dy)`, with `D` the cost of an
+    /// orthogonal move and `D2` the cost of a diagonal one. Exact on an empty map
+    /// with diagonal movement.
+    Octile,
+}
+
+/// Controls if and when a `CollisionMap` allows moving diagonally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DiagonalMovement {
+    /// Only the four orthogonal neighbors are reachable.
+    #[default]
+    Never,
+    /// Diagonal moves are allowed when at least one of the two orthogonally
+    /// adjacent tiles is free.
+    IfEitherOpen,
+    /// Diagonal moves are allowed only when both orthogonally adjacent tiles
+    /// are free. This prevents cutting through wall corners.
+    IfBothOpen,
+}
+
+/// The result of `CollisionMap::raycast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaycastHit {
+    /// No tile blocked the ray.
+    Clear,
+    /// A tile blocked the ray.
+    Blocked {
+        /// The last tile reached before the blocking one.
+        last_clear: Point,
+        /// The first colliding tile met by the ray.
+        blocking: Point,
+    },
+}
+
+/// The result of `CollisionMap::ray_march_entities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayMarchHit {
+    /// Neither a tile nor an entity stopped the ray.
+    Clear,
+    /// A colliding tile stopped the ray.
+    Wall {
+        /// The last tile reached before the blocking one.
+        last_clear: Point,
+        /// The first colliding tile met by the ray.
+        blocking: Point,
+    },
+    /// An entity stopped the ray.
+    Entity {
+        /// The entity that was hit.
+        entity: Entity,
+        /// The tile it stands on.
+        tile: Point,
+    },
+}
+
+/// The number of tiles in each chunk of a chunked `CollisionMap`.
+const CHUNK_TILES: u32 = 1024;
+
+/// The colliding tile indices of a `CollisionMap`.
+#[derive(Clone)]
+enum TileBits {
+    /// One bit per tile of the map.
+    Flat(HBitSet),
+    /// Chunks of `CHUNK_TILES` consecutive indices, only allocated while one of
+    /// their tiles collides.
+    Chunked(BTreeMap<u32, Box<[u64; (CHUNK_TILES / 64) as usize]>>),
+}
+
+impl TileBits {
+    fn add(&mut self, idx: u32) {
+        match self {
+            TileBits::Flat(bits) => {
+                bits.add(idx);
+            }
+            TileBits::Chunked(chunks) => {
+                let chunk = chunks
+                    .entry(idx / CHUNK_TILES)
+                    .or_insert_with(|| Box::new([0; (CHUNK_TILES / 64) as usize]));
+                let bit = idx % CHUNK_TILES;
+                chunk[(bit / 64) as usize] |= 1 << (bit % 64);
+            }
+        }
+    }
+
+    fn remove(&mut self, idx: u32) {
+        match self {
+            TileBits::Flat(bits) => {
+                bits.remove(idx);
+            }
+            TileBits::Chunked(chunks) => {
+                if let Some(chunk) = chunks.get_mut(&(idx / CHUNK_TILES)) {
+                    let bit = idx % CHUNK_TILES;
+                    chunk[(bit / 64) as usize] &= !(1 << (bit % 64));
+                    if chunk.iter().all(|word| *word == 0) {
+                        chunks.remove(&(idx / CHUNK_TILES));
+                    }
+                }
+            }
+        }
+    }
+
+    fn contains(&self, idx: u32) -> bool {
+        match self {
+            TileBits::Flat(bits) => bits.contains(idx),
+            TileBits::Chunked(chunks) => chunks
+                .get(&(idx / CHUNK_TILES))
+                .map(|chunk| {
+                    let bit = idx % CHUNK_TILES;
+                    chunk[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+                })
+                .unwrap_or(false),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            TileBits::Flat(bits) => bits.clear(),
+            TileBits::Chunked(chunks) => chunks.clear(),
+        }
+    }
+
+    /// Iterates over the set indices in increasing order.
+    fn iter(&self) -> Box<dyn Iterator<Item = u32> + '_> {
+        match self {
+            TileBits::Flat(bits) => Box::new(bits.iter()),
+            TileBits::Chunked(chunks) => Box::new(chunks.iter().flat_map(|(id, chunk)| {
+                chunk.iter().enumerate().flat_map(move |(w, word)| {
+                    (0..64)
+                        .filter(move |bit| word & (1 << bit) != 0)
+                        .map(move |bit| id * CHUNK_TILES + w as u32 * 64 + bit)
+                })
+            })),
+        }
+    }
+
+    /// An empty storage of the same kind, for a map of `len` tiles.
+    /// Flat storages too big for `MAX_FLAT_TILES` become chunked.
+    fn empty_like(&self, len: u32) -> Self {
+        match self {
+            TileBits::Flat(_) if len as u64 > MAX_FLAT_TILES => TileBits::Chunked(BTreeMap::new()),
+            TileBits::Flat(_) => TileBits::Flat(HBitSet::with_capacity(len)),
+            TileBits::Chunked(_) => TileBits::Chunked(BTreeMap::new()),
+        }
+    }
+}
+
+/// Collision of a multi tile entity. Not necessarily colliding everywhere.
+/// Can be both used as a global resource and as a component for individual entities.
+///
+/// Collisions are stored in two layers: the static layer for the map itself, such
+/// as walls, and the dynamic layer for the entities, which the collision systems
+/// rebuild without touching the static one. A tile collides when it is set in
+/// either layer, which is what `is_set` and pathfinding use.
+/// `set`, `unset` and the boolean combinators edit the static layer.
+///
+/// Positions outside of the map are handled the same way by every method taking
+/// one: the plain accessors like `set`, `is_set` or `set_cost` panic with an
+/// `OutOfBounds` message, their `try_` variants return it instead, and the
+/// rectangle and brush methods ignore the parts outside of the map.
+/// An empty map, of width or height `0`, doesn't contain any position.
+///
+/// With the `serde` feature, the static collisions are serialized as a compact array
+/// of 64 bits words. The dynamic layer isn't saved.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "CollisionMapData", into = "CollisionMapData")
+)]
+pub struct CollisionMap {
+    bitset: TileBits,
+    dynamic: TileBits,
+    width: u32,
+    height: u32,
+    diagonal: DiagonalMovement,
+    // Only set by `set_heuristic`, otherwise the heuristic follows `diagonal`.
+    heuristic: Option<Heuristic>,
+    wrap: bool,
+    // Empty until a cost is first set, in which case it holds one cost per tile.
+    costs: Vec<f32>,
+}
+
+/// The most tiles that a `CollisionMap` stores in a flat bitset, which is the
+/// capacity of `hibitset` (64⁴ bits). `CollisionMap::new` creates a chunked map
+/// for bigger sizes.
+pub const MAX_FLAT_TILES: u64 = 64 * 64 * 64 * 64;
+
+/// The most tiles that a `CollisionMap` can hold, flat or chunked, since tiles are
+/// indexed with a `u32`. Creating or resizing a map past it panics.
+pub const MAX_TILES: u64 = u32::MAX as u64;
+
+impl CollisionMap {
+    /// Create a new collision map.
+    /// Maps of more than `MAX_FLAT_TILES` tiles are created with `new_chunked`.
+    /// Panics for maps of more than `MAX_TILES` tiles.
+    pub fn new(width: u32, height: u32) -> Self {
+        if width as u64 * height as u64 > MAX_FLAT_TILES {
+            return Self::new_chunked(width, height);
+        }
+        Self::with_tiles(
+            width,
+            height,
+            TileBits::Flat(HBitSet::with_capacity(width * height)),
+        )
+    }
+
+    /// Creates a collision map storing its collisions in chunks, which are only
+    /// allocated while one of their tiles collides. Meant for huge worlds that are
+    /// mostly empty, where `new` would allocate one bit per tile.
+    /// It is used like any other map, but is slower when most tiles collide.
+    /// Setting tile costs still allocates a cost for every tile.
+    /// Panics for maps of more than `MAX_TILES` tiles.
+    pub fn new_chunked(width: u32, height: u32) -> Self {
+        Self::with_tiles(width, height, TileBits::Chunked(BTreeMap::new()))
+    }
+
+    fn with_tiles(width: u32, height: u32, bitset: TileBits) -> Self {
+        let len = Self::checked_len(width, height);
+        Self {
+            dynamic: bitset.empty_like(len),
+            bitset,
+            width,
+            height,
+            diagonal: DiagonalMovement::default(),
+            heuristic: None,
+            wrap: false,
+            costs: vec![],
+        }
+    }
+
+    /// The number of tiles of a `width` by `height` map, which must fit `MAX_TILES`.
+    fn checked_len(width: u32, height: u32) -> u32 {
+        width.checked_mul(height).unwrap_or_else(|| {
+            panic!(
+                "Map size {}x{} is over the {} tiles limit.",
+                width, height, MAX_TILES
+            )
+        })
+    }
+
+    /// Creates a collision map from a multi-line text layout, where every `wall`
+    /// character collides and every other character is open.
+    /// The width is the length of the longest line and the height is the number of lines.
+    /// Shorter lines are padded with open tiles.
+    pub fn from_str_layout(layout: &str, wall: char) -> Result<Self, LayoutError> {
+        let width = layout.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let height = layout.lines().count();
+        if width == 0 {
+            return Err(LayoutError::Empty);
+        }
+        let mut map = CollisionMap::new(width as u32, height as u32);
+        for (y, line) in layout.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == wall {
+                    map.set(x as u32, y as u32);
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    /// Enable collision at the given position, on the static layer.
+    pub fn set(&mut self, x: u32, y: u32) {
+        self.bitset.add(self.checked_index(x, y));
+    }
+
+    /// Disable collision at the given position, on the static layer.
+    pub fn unset(&mut self, x: u32, y: u32) {
+        self.bitset.remove(self.checked_index(x, y));
+    }
+
+    /// Checks if collision is enabled at the given position, on either layer.
+    pub fn is_set(&self, x: u32, y: u32) -> bool {
+        self.blocks(self.checked_index(x, y))
+    }
+
+    /// Checks if collision is enabled at the given position on the static layer.
+    pub fn is_set_static(&self, x: u32, y: u32) -> bool {
+        self.bitset.contains(self.checked_index(x, y))
+    }
+
+    /// Enable collision at the given position, on the dynamic layer.
+    pub fn set_dynamic(&mut self, x: u32, y: u32) {
+        self.dynamic.add(self.checked_index(x, y));
+    }
+
+    /// Disable collision at the given position, on the dynamic layer.
+    pub fn unset_dynamic(&mut self, x: u32, y: u32) {
+        self.dynamic.remove(self.checked_index(x, y));
+    }
+
+    /// Checks if collision is enabled at the given position on the dynamic layer.
+    pub fn is_set_dynamic(&self, x: u32, y: u32) -> bool {
+        self.dynamic.contains(self.checked_index(x, y))
+    }
+
+    /// Erases the dynamic layer, keeping the static one.
+    pub fn clear_dynamic(&mut self) {
+        self.dynamic.clear();
+    }
+
+    fn blocks(&self, idx: u32) -> bool {
+        self.bitset.contains(idx) || self.dynamic.contains(idx)
+    }
+
+    /// Iterates over the tiles set in either layer, in increasing order.
+    fn iter_blocked(&self) -> impl Iterator<Item = u32> + '_ {
+        let (mut a, mut b) = (
+            self.bitset.iter().peekable(),
+            self.dynamic.iter().peekable(),
+        );
+        std::iter::from_fn(move || match (a.peek().copied(), b.peek().copied()) {
+            (Some(x), Some(y)) if x == y => {
+                b.next();
+                a.next()
+            }
+            (Some(x), Some(y)) if y < x => b.next(),
+            (Some(_), _) => a.next(),
+            (None, _) => b.next(),
+        })
+    }
+
+    /// Enable collision at the given position, or returns an error if it is outside of the map.
+    pub fn try_set(&mut self, x: u32, y: u32) -> Result<(), OutOfBounds> {
+        self.check_bounds(x, y)?;
+        self.set(x, y);
+        Ok(())
+    }
+
+    /// Disable collision at the given position, or returns an error if it is outside of the map.
+    pub fn try_unset(&mut self, x: u32, y: u32) -> Result<(), OutOfBounds> {
+        self.check_bounds(x, y)?;
+        self.unset(x, y);
+        Ok(())
+    }
+
+    /// Enable collision at the given position on the dynamic layer, or returns an
+    /// error if it is outside of the map.
+    pub fn try_set_dynamic(&mut self, x: u32, y: u32) -> Result<(), OutOfBounds> {
+        self.check_bounds(x, y)?;
+        self.set_dynamic(x, y);
+        Ok(())
+    }
+
+    /// Disable collision at the given position on the dynamic layer, or returns an
+    /// error if it is outside of the map.
+    pub fn try_unset_dynamic(&mut self, x: u32, y: u32) -> Result<(), OutOfBounds> {
+        self.check_bounds(x, y)?;
+        self.unset_dynamic(x, y);
+        Ok(())
+    }
+
+    /// Enable collision on a rectangle of `w` by `h` tiles starting at the given
+    /// position. The parts outside of the map are ignored.
+    pub fn set_rect(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        for (x, y) in self.clipped_rect(x, y, w, h) {
+            self.set(x, y);
+        }
+    }
+
+    /// Disable collision on a rectangle of `w` by `h` tiles starting at the given
+    /// position. The parts outside of the map are ignored.
+    pub fn unset_rect(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        for (x, y) in self.clipped_rect(x, y, w, h) {
+            self.unset(x, y);
+        }
+    }
+
+    /// Enable collision on the border of a rectangle of `w` by `h` tiles starting at
+    /// the given position, like the walls of a room. The parts outside of the map
+    /// are ignored.
+    pub fn fill_border(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        let (right, bottom) = (x.saturating_add(w - 1), y.saturating_add(h - 1));
+        for (tx, ty) in self.clipped_rect(x, y, w, h) {
+            if tx == x || ty == y || tx == right || ty == bottom {
+                self.set(tx, ty);
+            }
+        }
+    }
+
+    /// Enable collision on every tile of the `bresenham_line` between two
+    /// positions, both included, like a straight or diagonal wall. The parts outside
+    /// of the map are ignored.
+    pub fn set_line(&mut self, from: Point, to: Point) {
+        for p in bresenham_line(from, to) {
+            if p.x >= 0 && p.y >= 0 && self.contains(p.x as u32, p.y as u32) {
+                self.set(p.x as u32, p.y as u32);
+            }
+        }
+    }
+
+    /// Enable collision on the row `y`, from `x0` to `x1` included, in any order.
+    /// The parts outside of the map are ignored.
+    pub fn set_row(&mut self, y: u32, x0: u32, x1: u32) {
+        let (x0, x1) = (x0.min(x1), x0.max(x1));
+        self.set_rect(x0, y, (x1 - x0).saturating_add(1), 1);
+    }
+
+    /// Enable collision on the column `x`, from `y0` to `y1` included, in any order.
+    /// The parts outside of the map are ignored.
+    pub fn set_column(&mut self, x: u32, y0: u32, y1: u32) {
+        let (y0, y1) = (y0.min(y1), y0.max(y1));
+        self.set_rect(x, y0, 1, (y1 - y0).saturating_add(1));
+    }
+
+    fn clipped_rect(&self, x: u32, y: u32, w: u32, h: u32) -> Vec<(u32, u32)> {
+        let right = x.saturating_add(w).min(self.width);
+        let bottom = y.saturating_add(h).min(self.height);
+        (y..bottom)
+            .flat_map(|ty| (x..right).map(move |tx| (tx, ty)))
+            .collect()
+    }
+
+    /// Checks if collision is enabled at the given position.
+    /// Returns `None` if the position is outside of the map.
+    pub fn try_is_set(&self, x: u32, y: u32) -> Option<bool> {
+        self.check_bounds(x, y).ok().map(|_| self.is_set(x, y))
+    }
+
+    /// Gives the neighbors of the position that can be walked to from it, using
+    /// the same rules as pathfinding, including diagonal movement and wrapping.
+    /// Returns no neighbors if the position is outside of the map.
+    pub fn walkable_neighbors(&self, p: Point) -> SmallVec<[Point; 8]> {
+        let mut o = SmallVec::new();
+        if p.x < 0 || p.y < 0 || !self.contains(p.x as u32, p.y as u32) {
+            return o;
+        }
+        let idx = self.index_of(p.x as u32, p.y as u32);
+        for (n, _) in self.get_available_exits(idx as usize) {
+            let (x, y) = self.position_of(n as u32);
+            o.push(Point::new(x, y));
+        }
+        o
+    }
+
+    /// Sums the cost of walking along `path`, using the same rules and tile costs
+    /// as pathfinding. The first position is the start and costs nothing.
+    /// Returns `None` if a position is outside of the map or collides, or if a step
+    /// can't be walked in a single move.
+    /// An empty path costs `0.0`, as does a single open position.
+    pub fn path_cost(&self, path: &[Point]) -> Option<f32> {
+        let mut indices = Vec::with_capacity(path.len());
+        for p in path {
+            if p.x < 0 || p.y < 0 || !self.contains(p.x as u32, p.y as u32) {
+                return None;
+            }
+            if self.is_set(p.x as u32, p.y as u32) {
+                return None;
+            }
+            indices.push(self.index_of(p.x as u32, p.y as u32) as usize);
+        }
+        let mut cost = 0.0;
+        for step in indices.windows(2) {
+            cost += self
+                .get_available_exits(step[0])
+                .iter()
+                .find(|(n, _)| *n == step[1])?
+                .1;
+        }
+        Some(cost)
+    }
+
+    /// Gives the open position closest to the given one in straight line, which is
+    /// the position itself when it is open.
+    /// Returns `None` if the position is outside of the map or every tile collides.
+    pub fn nearest_open(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let (cx, cy) = (x as i64, y as i64);
+        let (w, h) = (self.width as i64, self.height as i64);
+        // Ties go to the topmost, then leftmost tile.
+        let mut best: Option<(i64, (u32, u32))> = None;
+        let visit = |best: &mut Option<(i64, (u32, u32))>, tx: i64, ty: i64| {
+            if self.is_set(tx as u32, ty as u32) {
+                return;
+            }
+            let d = (tx - cx).pow(2) + (ty - cy).pow(2);
+            if best
+                .map(|(b, (bx, by))| (d, ty, tx) < (b, by as i64, bx as i64))
+                .unwrap_or(true)
+            {
+                *best = Some((d, (tx as u32, ty as u32)));
+            }
+        };
+        // The last ring that still reaches a tile of the map.
+        let last = cx.max(w - 1 - cx).max(cy).max(h - 1 - cy);
+        for r in 0..=last {
+            // Every tile of this ring and the next ones is at least r tiles away.
+            if best.map(|(d, _)| d < r * r).unwrap_or(false) {
+                break;
+            }
+            // Only the perimeter of the ring, clamped to the map.
+            let (x0, x1) = ((cx - r).max(0), (cx + r).min(w - 1));
+            for ty in [cy - r, cy + r] {
+                if ty >= 0 && ty < h {
+                    for tx in x0..=x1 {
+                        visit(&mut best, tx, ty);
+                    }
+                }
+                if r == 0 {
+                    break;
+                }
+            }
+            for tx in [cx - r, cx + r] {
+                if r > 0 && tx >= 0 && tx < w {
+                    for ty in (cy - r + 1).max(0)..=(cy + r - 1).min(h - 1) {
+                        visit(&mut best, tx, ty);
+                    }
+                }
+            }
+        }
+        best.map(|(_, p)| p)
+    }
+
+    /// Same as `nearest_open`, using points, for example to spawn an entity or to
+    /// snap a clicked destination on a walkable tile.
+    /// The search stops after the farthest tile of the map, so a map where every
+    /// tile collides gives `None`, as does a point outside of the map.
+    pub fn nearest_walkable(&self, from: Point) -> Option<Point> {
+        if from.x < 0 || from.y < 0 {
+            return None;
+        }
+        self.nearest_open(from.x as u32, from.y as u32)
+            .map(|(x, y)| Point::new(x, y))
+    }
+
+    /// Checks if the position is inside of the map, which must hold before calling
+    /// the methods panicking on positions outside of it, like `set` or `is_set`.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Checks that the position is inside of the map and doesn't collide.
+    fn is_open(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && self.contains(x as u32, y as u32) && !self.is_set(x as u32, y as u32)
+    }
+
+    fn check_bounds(&self, x: u32, y: u32) -> Result<(), OutOfBounds> {
+        if self.contains(x, y) {
+            Ok(())
+        } else {
+            Err(OutOfBounds {
+                position: (x, y),
+                size: self.size(),
+            })
+        }
+    }
+
+    /// Iterates over the positions of all tiles with collision enabled, in index order.
+    /// Only visits the set tiles, which makes it fast on sparse maps.
+    pub fn iter_set(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.iter_blocked().map(move |idx| self.position_of(idx))
+    }
+
+    /// Counts the tiles with collision enabled.
+    pub fn count_set(&self) -> usize {
+        self.iter_blocked().count()
+    }
+
+    /// Gives the top-left and bottom-right corners of the smallest rectangle
+    /// containing every tile with collision enabled, both included.
+    /// Returns `None` if no tile collides.
+    pub fn bounding_box(&self) -> Option<(Point, Point)> {
+        let mut tiles = self.iter_set();
+        let (x, y) = tiles.next()?;
+        let (mut min, mut max) = (Point::new(x, y), Point::new(x, y));
+        // The first tile has the smallest y, and the last one the biggest.
+        for (x, y) in tiles {
+            min.x = min.x.min(x as i32);
+            max.x = max.x.max(x as i32);
+            max.y = y as i32;
+        }
+        Some((min, max))
+    }
+
+    /// Checks whether a straight line (using `bresenham_line`) between two positions of
+    /// the map is free of collisions.
+    /// Only the tiles in between are checked: `from` and `to` may themselves collide,
+    /// so that an entity standing on a colliding tile can still see out.
+    /// Tiles outside of the map block the line of sight.
+    pub fn has_line_of_sight(&self, from: Point, to: Point) -> bool {
+        let line = bresenham_line(from, to);
+        line.len() < 3
+            || line[1..line.len() - 1]
+                .iter()
+                .all(|p| self.is_open(p.x, p.y))
+    }
+
+    /// Casts a ray from `from` towards `to` and gives the first colliding tile met.
+    /// The tiles are visited in the order given by `bresenham_line(from, to)`, so
+    /// the result is deterministic but may differ when swapping `from` and `to`.
+    /// Unlike `has_line_of_sight`, `to` is checked too, but `from` still isn't.
+    /// Tiles outside of the map block the ray.
+    pub fn raycast(&self, from: Point, to: Point) -> RaycastHit {
+        let line = bresenham_line(from, to);
+        for pair in line.windows(2) {
+            if !self.is_open(pair[1].x, pair[1].y) {
+                return RaycastHit::Blocked {
+                    last_clear: pair[0],
+                    blocking: pair[1],
+                };
+            }
+        }
+        RaycastHit::Clear
+    }
+
+    /// Casts a ray like `raycast`, but also stops on the first entity of the
+    /// `SpatialHash` accepted by `filter`, for example to skip the shooter.
+    /// The `SpatialHash` must use the coordinates of this map.
+    /// On each tile, entities are checked before the tile itself, so that entities
+    /// whose `Collision` blocks their own tile can be hit. Entities on the same tile
+    /// are checked in the order they arrived on it.
+    pub fn ray_march_entities(
+        &self,
+        from: Point,
+        to: Point,
+        spatial_hash: &SpatialHash,
+        filter: impl Fn(Entity) -> bool,
+    ) -> RayMarchHit {
+        let line = bresenham_line(from, to);
+        for pair in line.windows(2) {
+            let tile = pair[1];
+            if let Some(entity) = spatial_hash.entities_at(tile).iter().find(|e| filter(**e)) {
+                return RayMarchHit::Entity {
+                    entity: *entity,
+                    tile,
+                };
+            }
+            if !self.is_open(tile.x, tile.y) {
+                return RayMarchHit::Wall {
+                    last_clear: pair[0],
+                    blocking: tile,
+                };
+            }
+        }
+        RayMarchHit::Clear
+    }
+
+    /// Gives the tiles at most `radius` tiles away from `center` in straight line,
+    /// `center` included, row by row. Tiles outside of the map are skipped.
+    /// With `stop_at_walls`, only the open tiles that `center` can see using
+    /// `raycast` are given, so that walls stop the area.
+    pub fn tiles_in_circle(&self, center: Point, radius: u32, stop_at_walls: bool) -> Vec<Point> {
+        self.tiles_in_area(center, radius, stop_at_walls, |_, _| true)
+    }
+
+    /// Gives the tiles in the cone starting at `origin` and opening towards
+    /// `direction`, at most `radius` tiles away from `origin`, row by row.
+    /// `angle` is the full opening of the cone in degrees, 90 giving a quarter of a
+    /// circle. `origin` isn't included, nor are tiles outside of the map.
+    /// With `stop_at_walls`, only the open tiles that `origin` can see using
+    /// `raycast` are given.
+    /// `Direction::Up` and `Direction::Down` give no tiles.
+    pub fn tiles_in_cone(
+        &self,
+        origin: Point,
+        direction: Direction,
+        radius: u32,
+        angle: f32,
+        stop_at_walls: bool,
+    ) -> Vec<Point> {
+        let facing = direction.delta();
+        if facing == Point::new(0, 0) {
+            return vec![];
+        }
+        let min_cos = (angle / 2.0).to_radians().cos();
+        self.tiles_in_area(origin, radius, stop_at_walls, |dx, dy| {
+            let len = ((dx * dx + dy * dy) as f32).sqrt();
+            // A small margin keeps the tiles exactly on the edges of the cone.
+            len > 0.0 && (dx * facing.x + dy * facing.y) as f32 / len >= min_cos - 0.0001
+        })
+    }
+
+    /// Gives the `length` tiles following `from` in `direction`, from the closest
+    /// to the farthest, `from` excluded. Stops at the edge of the map and, with
+    /// `stop_at_walls`, before the first colliding tile.
+    /// `Direction::Up` and `Direction::Down` give no tiles.
+    pub fn tiles_in_line(
+        &self,
+        from: Point,
+        direction: Direction,
+        length: u32,
+        stop_at_walls: bool,
+    ) -> Vec<Point> {
+        let mut tiles = vec![];
+        if direction.delta() == Point::new(0, 0) {
+            return tiles;
+        }
+        let mut current = from;
+        for _ in 0..length {
+            current = direction.apply(current);
+            if current.x < 0 || current.y < 0 || !self.contains(current.x as u32, current.y as u32)
+            {
+                break;
+            }
+            if stop_at_walls && self.is_set(current.x as u32, current.y as u32) {
+                break;
+            }
+            tiles.push(current);
+        }
+        tiles
+    }
+
+    /// The tiles of the circle around `origin` accepted by `filter`, which receives
+    /// their offset from `origin`.
+    fn tiles_in_area(
+        &self,
+        origin: Point,
+        radius: u32,
+        stop_at_walls: bool,
+        filter: impl Fn(i32, i32) -> bool,
+    ) -> Vec<Point> {
+        let r = radius as i32;
+        let mut tiles = vec![];
+        for y in (origin.y - r).max(0)..=(origin.y + r).min(self.height as i32 - 1) {
+            for x in (origin.x - r).max(0)..=(origin.x + r).min(self.width as i32 - 1) {
+                let (dx, dy) = (x - origin.x, y - origin.y);
+                if dx * dx + dy * dy > r * r || !filter(dx, dy) {
+                    continue;
+                }
+                let p = Point::new(x, y);
+                if stop_at_walls
+                    && (self.is_set(x as u32, y as u32)
+                        || (p != origin && self.raycast(origin, p) != RaycastHit::Clear))
+                {
+                    continue;
+                }
+                tiles.push(p);
+            }
+        }
+        tiles
+    }
+
+    /// Enables collision on the tiles covered by the `MultiSprite` placed at
+    /// `anchor`. Tiles outside of the map are ignored.
+    pub fn stamp(&mut self, sprite: &MultiSprite, anchor: Point) {
+        for p in sprite.footprint(anchor) {
+            if p.x >= 0 && p.y >= 0 && self.contains(p.x as u32, p.y as u32) {
+                self.set(p.x as u32, p.y as u32);
+            }
+        }
+    }
+
+    /// Disables collision on the tiles covered by the `MultiSprite` placed at
+    /// `anchor`. Tiles outside of the map are ignored.
+    pub fn unstamp(&mut self, sprite: &MultiSprite, anchor: Point) {
+        for p in sprite.footprint(anchor) {
+            if p.x >= 0 && p.y >= 0 && self.contains(p.x as u32, p.y as u32) {
+                self.unset(p.x as u32, p.y as u32);
+            }
+        }
+    }
+
+    /// Gives the tiles visible from `origin` within `radius`, using symmetric
+    /// shadowcasting: if a tile A sees a tile B, B also sees A.
+    /// Colliding tiles are visible but block the view behind them.
+    pub fn field_of_view(&self, origin: Point, radius: u32) -> HashSet<(u32, u32)> {
+        let mut visible = HashSet::new();
+        if origin.x < 0 || origin.y < 0 || !self.contains(origin.x as u32, origin.y as u32) {
+            return visible;
+        }
+        visible.insert((origin.x as u32, origin.y as u32));
+        for quadrant in 0..4 {
+            let quadrant = FovQuadrant {
+                origin,
+                quadrant,
+                radius: radius as i64,
+            };
+            self.scan_fov(&quadrant, 1, (-1, 1), (1, 1), &mut visible);
+        }
+        visible
+    }
+
+    /// Scans one row of a quadrant for `field_of_view`. Slopes are `(numerator, denominator)`.
+    fn scan_fov(
+        &self,
+        q: &FovQuadrant,
+        depth: i64,
+        mut start: (i64, i64),
+        end: (i64, i64),
+        visible: &mut HashSet<(u32, u32)>,
+    ) {
+        if depth > q.radius {
+            return;
+        }
+        let (ox, oy) = (q.origin.x as i64, q.origin.y as i64);
+        let tile = |col: i64| match q.quadrant {
+            0 => (ox + col, oy - depth),
+            1 => (ox + col, oy + depth),
+            2 => (ox + depth, oy + col),
+            _ => (ox - depth, oy + col),
+        };
+        // Rounds depth * start up and depth * end down, ties going outwards.
+        let min_col = (2 * depth * start.0 + start.1).div_euclid(2 * start.1);
+        let max_col = -(-(2 * depth * end.0 - end.1)).div_euclid(2 * end.1);
+        let mut prev_wall = None;
+        for col in min_col..=max_col {
+            let (x, y) = tile(col);
+            let wall = !self.is_open(x as i32, y as i32);
+            let symmetric = col * start.1 >= depth * start.0 && col * end.1 <= depth * end.0;
+            if (wall || symmetric)
+                && x >= 0
+                && y >= 0
+                && self.contains(x as u32, y as u32)
+                && col * col + depth * depth <= q.radius * q.radius
+            {
+                visible.insert((x as u32, y as u32));
+            }
+            if prev_wall == Some(true) && !wall {
+                start = (2 * col - 1, 2 * depth);
+            }
+            if prev_wall == Some(false) && wall {
+                let next_end = (2 * col - 1, 2 * depth);
+                self.scan_fov(q, depth + 1, start, next_end, visible);
+            }
+            prev_wall = Some(wall);
+        }
+        if prev_wall == Some(false) {
+            self.scan_fov(q, depth + 1, start, end, visible);
+        }
+    }
+
+    /// Labels the groups of open tiles that are connected orthogonally.
+    /// Region ids are given in the order in which their first tile appears, row by row.
+    pub fn label_regions(&self) -> RegionMap {
+        self.flood_regions(|idx| {
+            let (x, y) = self.position_of(idx);
+            let (x, y) = (x as i32, y as i32);
+            [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+                .iter()
+                .filter(|(nx, ny)| self.is_open(*nx, *ny))
+                .map(|(nx, ny)| self.index_of(*nx as u32, *ny as u32))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Labels the groups of open tiles, connecting each tile to the tiles given by
+    /// `neighbors`.
+    fn flood_regions<N: IntoIterator<Item = u32>>(
+        &self,
+        neighbors: impl Fn(u32) -> N,
+    ) -> RegionMap {
+        let mut labels = vec![None; (self.width * self.height) as usize];
+        let mut count = 0;
+        let mut queue = std::collections::VecDeque::new();
+        for start in 0..self.width * self.height {
+            if labels[start as usize].is_some() || self.blocks(start) {
+                continue;
+            }
+            labels[start as usize] = Some(count);
+            queue.push_back(start);
+            while let Some(idx) = queue.pop_front() {
+                for n in neighbors(idx) {
+                    if labels[n as usize].is_none() {
+                        labels[n as usize] = Some(count);
+                        queue.push_back(n);
+                    }
+                }
+            }
+            count += 1;
+        }
+        RegionMap {
+            labels,
+            width: self.width,
+            height: self.height,
+            count,
+        }
+    }
+
+    /// Gives the groups of open tiles that are connected orthogonally, indexed by
+    /// the region ids of `label_regions`. Tiles are sorted row by row.
+    pub fn connected_regions(&self) -> Vec<Vec<(u32, u32)>> {
+        let regions = self.label_regions();
+        let mut groups = vec![vec![]; regions.region_count()];
+        for (idx, label) in regions.labels.iter().enumerate() {
+            if let Some(id) = label {
+                groups[*id].push(self.position_of(idx as u32));
+            }
+        }
+        groups
+    }
+
+    /// Gives the number of tiles of each group of open tiles, indexed by the
+    /// region ids of `label_regions`.
+    pub fn region_sizes(&self) -> Vec<usize> {
+        self.label_regions().sizes()
+    }
+
+    /// Enables collision on the groups of open tiles having less than `min_size`
+    /// tiles, like the small pockets left by cave generation.
+    /// Only the static layer is taken into account, and the tiles are grouped by
+    /// the movement rules of the map, including its `DiagonalMovement` and wrapping.
+    /// The tiles are set on the static layer.
+    pub fn fill_small_regions(&mut self, min_size: u32) {
+        let mut terrain = self.clone();
+        terrain.clear_dynamic();
+        let regions = terrain.flood_regions(|idx| {
+            terrain
+                .get_available_exits(idx as usize)
+                .into_iter()
+                .map(|(n, _)| n as u32)
+        });
+        let sizes = regions.sizes();
+        for (idx, label) in regions.labels.iter().enumerate() {
+            if let Some(id) = label {
+                if sizes[*id] < min_size as usize {
+                    self.bitset.add(idx as u32);
+                }
+            }
+        }
+    }
+
+    /// Gives the size of the collision map.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Changes the size of the collision map, keeping collisions at the same
+    /// coordinates. Collisions and costs outside of the new size are dropped.
+    /// Panics if the new size has more than `MAX_TILES` tiles.
+    pub fn resize(&mut self, new_width: u32, new_height: u32) {
+        let mut resized = CollisionMap::with_tiles(
+            new_width,
+            new_height,
+            self.bitset
+                .empty_like(Self::checked_len(new_width, new_height)),
+        );
+        resized.diagonal = self.diagonal;
+        resized.heuristic = self.heuristic;
+        resized.wrap = self.wrap;
+        for (layer, resized_layer) in [
+            (&self.bitset, &mut resized.bitset),
+            (&self.dynamic, &mut resized.dynamic),
+        ] {
+            for idx in layer.iter() {
+                let (x, y) = self.position_of(idx);
+                if x < new_width && y < new_height {
+                    resized_layer.add(y * new_width + x);
+                }
+            }
+        }
+        if !self.costs.is_empty() {
+            for y in 0..self.height.min(new_height) {
+                for x in 0..self.width.min(new_width) {
+                    resized.set_cost(x, y, self.cost_of(x, y));
+                }
+            }
+        }
+        *self = resized;
+    }
+
+    /// Erase the collision map, both layers included.
+    pub fn clear(&mut self) {
+        self.bitset.clear();
+        self.dynamic.clear();
+    }
+
+    /// Flips the collision of every tile of the static layer.
+    pub fn invert(&mut self) {
+        for idx in 0..self.width * self.height {
+            if self.bitset.contains(idx) {
+                self.bitset.remove(idx);
+            } else {
+                self.bitset.add(idx);
+            }
+        }
+    }
+
+    /// Enables collision on every tile that collides in `other`.
+    /// Both maps must have the same size, otherwise nothing changes.
+    pub fn union_with(&mut self, other: &CollisionMap) -> Result<(), SizeMismatch> {
+        self.check_same_size(other)?;
+        for idx in other.iter_blocked() {
+            self.bitset.add(idx);
+        }
+        Ok(())
+    }
+
+    /// Only keeps collision on the tiles that also collide in `other`.
+    /// Both maps must have the same size, otherwise nothing changes.
+    pub fn intersect_with(&mut self, other: &CollisionMap) -> Result<(), SizeMismatch> {
+        self.check_same_size(other)?;
+        let removed = self
+            .bitset
+            .iter()
+            .filter(|idx| !other.blocks(*idx))
+            .collect::<Vec<_>>();
+        for idx in removed {
+            self.bitset.remove(idx);
+        }
+        Ok(())
+    }
+
+    /// Disables collision on every tile that collides in `other`.
+    /// Both maps must have the same size, otherwise nothing changes.
+    pub fn difference_with(&mut self, other: &CollisionMap) -> Result<(), SizeMismatch> {
+        self.check_same_size(other)?;
+        for idx in other.iter_blocked() {
+            self.bitset.remove(idx);
+        }
+        Ok(())
+    }
+
+    /// Gives the tiles whose collision changed since `previous`, with their new state,
+    /// ordered by index. Meant to send only the changes over the network.
+    /// Only the static collisions are compared, not costs or settings. The dynamic
+    /// layer is left out since each side rebuilds it from its own entities.
+    pub fn diff(&self, previous: &CollisionMap) -> Result<Vec<(u32, u32, bool)>, SizeMismatch> {
+        previous.check_same_size(self)?;
+        let (mut current, mut old) = (
+            self.bitset.iter().peekable(),
+            previous.bitset.iter().peekable(),
+        );
+        let mut changes = vec![];
+        loop {
+            let (idx, set) = match (current.peek(), old.peek()) {
+                (Some(a), Some(b)) if a == b => {
+                    current.next();
+                    old.next();
+                    continue;
+                }
+                (Some(a), Some(b)) if a < b => (current.next().unwrap(), true),
+                (Some(_), None) => (current.next().unwrap(), true),
+                (_, Some(_)) => (old.next().unwrap(), false),
+                (None, None) => break,
+            };
+            let (x, y) = self.position_of(idx);
+            changes.push((x, y, set));
+        }
+        Ok(changes)
+    }
+
+    /// Applies the changes given by `diff` to the static layer.
+    /// If any change is outside of the map, nothing changes.
+    pub fn apply_diff(&mut self, changes: &[(u32, u32, bool)]) -> Result<(), OutOfBounds> {
+        for (x, y, _) in changes {
+            self.check_bounds(*x, *y)?;
+        }
+        for (x, y, collides) in changes {
+            if *collides {
+                self.set(*x, *y);
+            } else {
+                self.unset(*x, *y);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_same_size(&self, other: &CollisionMap) -> Result<(), SizeMismatch> {
+        if self.size() != other.size() {
+            return Err(SizeMismatch {
+                expected: self.size(),
+                found: other.size(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Sets the movement cost multiplier of the given tile. Every tile defaults to `1.0`.
+    /// Moving into the tile costs `cost` (or `cost * SQRT_2` diagonally).
+    /// Note: Costs below `1.0` make the pathing distance overestimate, which means that
+    /// A* is no longer guaranteed to find the cheapest path.
+    pub fn set_cost(&mut self, x: u32, y: u32, cost: f32) {
+        let idx = self.checked_index(x, y) as usize;
+        if self.costs.is_empty() {
+            self.costs = vec![1.0; (self.width * self.height) as usize];
+        }
+        self.costs[idx] = cost;
+    }
+
+    /// Sets the movement cost multiplier of a rectangle of `w` by `h` tiles starting
+    /// at the given position. The parts outside of the map are ignored.
+    pub fn set_cost_rect(&mut self, x: u32, y: u32, w: u32, h: u32, cost: f32) {
+        for (x, y) in self.clipped_rect(x, y, w, h) {
+            self.set_cost(x, y, cost);
+        }
+    }
+
+    /// Sets the movement cost multiplier of the tiles within `radius` of `center`,
+    /// using the Euclidean distance: a tile is painted if `dx² + dy² <= radius²`.
+    /// The center may be outside of the map. The parts outside of the map are ignored.
+    pub fn set_cost_circle(&mut self, center: Point, radius: u32, cost: f32) {
+        let r = radius as i64;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let (x, y) = (center.x as i64 + dx, center.y as i64 + dy);
+                if dx * dx + dy * dy <= r * r
+                    && x >= 0
+                    && y >= 0
+                    && self.contains(x as u32, y as u32)
+                {
+                    self.set_cost(x as u32, y as u32, cost);
+                }
+            }
+        }
+    }
+
+    /// Sets the movement cost multiplier of the given tile, or returns an error if it
+    /// is outside of the map.
+    pub fn try_set_cost(&mut self, x: u32, y: u32, cost: f32) -> Result<(), OutOfBounds> {
+        self.check_bounds(x, y)?;
+        self.set_cost(x, y, cost);
+        Ok(())
+    }
+
+    /// Gives the movement cost multiplier of the given tile.
+    pub fn cost_of(&self, x: u32, y: u32) -> f32 {
+        self.tile_cost(self.checked_index(x, y) as usize)
+    }
+
+    /// Gives the movement cost multiplier of the given tile.
+    /// Returns `None` if the position is outside of the map.
+    pub fn try_cost_of(&self, x: u32, y: u32) -> Option<f32> {
+        self.check_bounds(x, y).ok().map(|_| self.cost_of(x, y))
+    }
+
+    /// Checks that no tile has a movement cost other than `1.0`.
+    pub(crate) fn has_uniform_cost(&self) -> bool {
+        self.costs.iter().all(|c| *c == 1.0)
+    }
+
+    fn tile_cost(&self, idx: usize) -> f32 {
+        self.costs.get(idx).copied().unwrap_or(1.0)
+    }
+
+    /// Sets whether pathfinding may move diagonally on this map.
+    /// Diagonal moves cost `SQRT_2` instead of `1.0`.
+    /// Unless a heuristic was chosen with `set_heuristic`, this also selects the
+    /// one matching the movement model: `Heuristic::Octile` with diagonal movement,
+    /// `Heuristic::Manhattan` without.
+    pub fn set_diagonal_movement(&mut self, diagonal: DiagonalMovement) {
+        self.diagonal = diagonal;
+    }
+
+    /// Gives the diagonal movement mode used by this map.
+    pub fn diagonal_movement(&self) -> DiagonalMovement {
+        self.diagonal
+    }
+
+    /// Sets the distance estimate used by A*, which is then kept when the diagonal
+    /// movement changes. Defaults to `Heuristic::Manhattan`, or `Heuristic::Octile`
+    /// with diagonal movement.
+    /// `Manhattan` overestimates diagonal paths, so A* may not find the shortest one.
+    pub fn set_heuristic(&mut self, heuristic: Heuristic) {
+        self.heuristic = Some(heuristic);
+    }
+
+    /// Gives the distance estimate used by A*.
+    pub fn heuristic(&self) -> Heuristic {
+        self.heuristic.unwrap_or(match self.diagonal {
+            DiagonalMovement::Never => Heuristic::Manhattan,
+            _ => Heuristic::Octile,
+        })
+    }
+
+    /// Sets whether pathfinding wraps around the edges of the map, connecting the
+    /// left edge to the right one and the top edge to the bottom one.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Checks whether pathfinding wraps around the edges of the map.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Checks whether the map was created with `new_chunked`.
+    pub fn is_chunked(&self) -> bool {
+        matches!(self.bitset, TileBits::Chunked(_))
+    }
+
+    /// Callers must check that the position is inside of the map first.
+    pub(crate) fn index_of(&self, x: u32, y: u32) -> u32 {
+        debug_assert!(self.contains(x, y));
+        y * self.width + x
+    }
+
+    /// Like `index_of`, but panics with an `OutOfBounds` message for positions
+    /// outside of the map.
+    fn checked_index(&self, x: u32, y: u32) -> u32 {
+        if let Err(e) = self.check_bounds(x, y) {
+            panic!("{}", e);
+        }
+        self.index_of(x, y)
+    }
+
+    pub(crate) fn position_of(&self, idx: u32) -> (u32, u32) {
+        assert!(self.width > 0);
+        assert!(self.height > 0);
+        (idx % self.width, idx / self.width)
+    }
+}
+
+/// One of the four quadrants scanned by `CollisionMap::field_of_view`.
+struct FovQuadrant {
+    origin: Point,
+    /// North, south, east or west.
+    quadrant: u8,
+    radius: i64,
 }
 
-/// Collision of a single tile entity
-pub struct Collision;
-/// Collision of a multi tile entity. Not necessarily colliding everywhere.
-/// Can be both used as a global resource and as a component for individual entities.
-pub struct CollisionMap {
-    bitset: HBitSet,
+/// The connected open regions of a `CollisionMap`, as computed by
+/// `CollisionMap::label_regions`.
+pub struct RegionMap {
+    labels: Vec<Option<usize>>,
     width: u32,
     height: u32,
+    count: usize,
 }
 
-impl CollisionMap {
-    /// Create a new collision map.
-    pub fn new(width: u32, height: u32) -> Self {
-        Self {
-            bitset: HBitSet::with_capacity(width * height),
-            width,
-            height,
+impl RegionMap {
+    /// Gives the id of the region containing the given tile.
+    /// Returns `None` for colliding tiles and positions outside of the map.
+    /// Two tiles can only reach each other if they have the same region id.
+    pub fn region_id_of(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.width && y < self.height {
+            self.labels[(y * self.width + x) as usize]
+        } else {
+            None
         }
     }
 
-    /// Enable collision at the given position.
-    pub fn set(&mut self, x: u32, y: u32) {
-        self.bitset.add(self.index_of(x, y));
-    }
-
-    /// Disable collision at the given position.
-    pub fn unset(&mut self, x: u32, y: u32) {
-        self.bitset.remove(self.index_of(x, y));
+    /// Gives the number of regions.
+    pub fn region_count(&self) -> usize {
+        self.count
     }
 
-    /// Checks if collision is enabled at the given position.
-    pub fn is_set(&self, x: u32, y: u32) -> bool {
-        self.bitset.contains(self.index_of(x, y))
+    /// Gives the number of tiles of each region, indexed by region id.
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.count];
+        for id in self.labels.iter().flatten() {
+            sizes[*id] += 1;
+        }
+        sizes
     }
+}
 
-    /// Gives the size of the collision map.
-    pub fn size(&self) -> (u32, u32) {
-        (self.width, self.height)
-    }
+/// The serialized form of a `CollisionMap`.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct CollisionMapData {
+    width: u32,
+    height: u32,
+    /// Bit `i % 64` of word `i / 64` is set when tile index `i` collides.
+    words: Vec<u64>,
+    #[serde(default)]
+    diagonal: DiagonalMovement,
+    #[serde(default)]
+    heuristic: Option<Heuristic>,
+    #[serde(default)]
+    wrap: bool,
+    #[serde(default)]
+    costs: Vec<f32>,
+    #[serde(default)]
+    chunked: bool,
+}
 
-    /// Erase the collision map.
-    pub fn clear(&mut self) {
-        self.bitset.clear();
+#[cfg(feature = "serde")]
+impl From<CollisionMap> for CollisionMapData {
+    fn from(map: CollisionMap) -> Self {
+        let len = map.width * map.height;
+        let mut words = vec![0u64; ((len + 63) / 64) as usize];
+        for idx in map.bitset.iter() {
+            words[(idx / 64) as usize] |= 1 << (idx % 64);
+        }
+        Self {
+            width: map.width,
+            height: map.height,
+            words,
+            diagonal: map.diagonal,
+            heuristic: map.heuristic,
+            wrap: map.wrap,
+            chunked: map.is_chunked(),
+            costs: map.costs,
+        }
     }
+}
 
-    pub(crate) fn index_of(&self, x: u32, y: u32) -> u32 {
-        let idx = y * self.width + x;
-        assert!(idx <= self.width * self.height - 1);
-        idx
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<CollisionMapData> for CollisionMap {
+    type Error = String;
+    fn try_from(data: CollisionMapData) -> Result<Self, Self::Error> {
+        let len = data
+            .width
+            .checked_mul(data.height)
+            .ok_or_else(|| format!("Map size {}x{} is too big.", data.width, data.height))?;
+        if data.words.len() != ((len as u64 + 63) / 64) as usize {
+            return Err(format!(
+                "Expected {} words for a {}x{} map, got {}.",
+                (len as u64 + 63) / 64,
+                data.width,
+                data.height,
+                data.words.len()
+            ));
+        }
+        if !data.costs.is_empty() && data.costs.len() != len as usize {
+            return Err(format!(
+                "Expected {} tile costs for a {}x{} map, got {}.",
+                len,
+                data.width,
+                data.height,
+                data.costs.len()
+            ));
+        }
+        let mut map = if data.chunked {
+            CollisionMap::new_chunked(data.width, data.height)
+        } else {
+            CollisionMap::new(data.width, data.height)
+        };
+        for (i, word) in data.words.iter().enumerate() {
+            for bit in 0..64 {
+                if word & (1 << bit) != 0 {
+                    let idx = i as u32 * 64 + bit;
+                    if idx >= len {
+                        return Err(format!(
+                            "Collision set outside of the map at index {}.",
+                            idx
+                        ));
+                    }
+                    map.bitset.add(idx);
+                }
+            }
+        }
+        map.diagonal = data.diagonal;
+        map.heuristic = data.heuristic;
+        map.wrap = data.wrap;
+        map.costs = data.costs;
+        Ok(map)
     }
+}
 
-    pub(crate) fn position_of(&self, idx: u32) -> (u32, u32) {
-        assert!(self.width > 0);
-        assert!(self.height > 0);
-        (idx % self.width, idx / self.width)
+impl PartialEq for CollisionMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.size() == other.size()
+            && self.diagonal == other.diagonal
+            && self.heuristic == other.heuristic
+            && self.wrap == other.wrap
+            && (0..(self.width * self.height) as usize)
+                .all(|idx| self.tile_cost(idx) == other.tile_cost(idx))
+            && self.bitset.iter().eq(other.bitset.iter())
+            && self.dynamic.iter().eq(other.dynamic.iter())
     }
 }
 
 impl BaseMap for CollisionMap {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.bitset.contains(idx as u32)
+        self.blocks(idx as u32)
     }
 
     fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
         let mut o = SmallVec::new();
-        //println!("idx: {}", idx);
-        // right
-        if (idx % self.width as usize) < (self.width as usize - 1) {
-            let n = idx + 1;
-            if !self.is_opaque(n) {
-                //println!("ADDING AT {},{}, while it is {} opaque.", self.position_of(idx as u32).0, self.position_of(idx as u32).1, self.is_opaque(idx));
-                o.push((n, 1.0));
+        // Hot path of pathfinding: positions are only computed once and the
+        // neighbors are looked up directly in the layers.
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (x, y) = (idx % w, idx / w);
+        let wrap = self.wrap;
+        let next = |v: usize, size: usize| {
+            if v + 1 < size {
+                Some(v + 1)
+            } else if wrap {
+                Some(0)
+            } else {
+                None
             }
-        }
-        // left
-        if (idx % self.width as usize) > 0 {
-            let n = idx - 1;
-            if !self.is_opaque(n) {
-                o.push((n, 1.0));
+        };
+        let prev = |v: usize, size: usize| {
+            if v > 0 {
+                Some(v - 1)
+            } else if wrap {
+                Some(size - 1)
+            } else {
+                None
             }
-        }
-        // down
-        if (idx / self.width as usize) < (self.height as usize - 1) {
-            let n = idx + self.width as usize;
-            if !self.is_opaque(n) {
-                o.push((n, 1.0));
+        };
+        let open = |nx: usize, ny: usize| {
+            let n = ny * w + nx;
+            if n != idx && !self.blocks(n as u32) {
+                Some(n)
+            } else {
+                None
             }
+        };
+        let (right, left, down, up) = (next(x, w), prev(x, w), next(y, h), prev(y, h));
+        let orthogonal = [
+            right.and_then(|nx| open(nx, y)),
+            left.and_then(|nx| open(nx, y)),
+            down.and_then(|ny| open(x, ny)),
+            up.and_then(|ny| open(x, ny)),
+        ];
+        for n in orthogonal.iter().flatten() {
+            o.push((*n, self.tile_cost(*n)));
         }
-        // up
-        if idx >= (self.width as usize) {
-            let n = idx - self.width as usize;
-            if !self.is_opaque(n) {
-                o.push((n, 1.0));
+        if self.diagonal != DiagonalMovement::Never {
+            for (nx, ny) in [(right, down), (left, down), (right, up), (left, up)].iter() {
+                let (nx, ny) = match (nx, ny) {
+                    (Some(nx), Some(ny)) => (*nx, *ny),
+                    _ => continue,
+                };
+                let n = match open(nx, ny) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let open_sides = open(nx, y).is_some() as u8 + open(x, ny).is_some() as u8;
+                let allowed = match self.diagonal {
+                    DiagonalMovement::Never => false,
+                    DiagonalMovement::IfEitherOpen => open_sides >= 1,
+                    DiagonalMovement::IfBothOpen => open_sides == 2,
+                };
+                if allowed {
+                    o.push((n, std::f32::consts::SQRT_2 * self.tile_cost(n)));
+                }
             }
         }
         o
@@ -156,10 +2296,27 @@ impl BaseMap for CollisionMap {
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
         let (x1, y1) = self.position_of(idx1 as u32);
         let (x2, y2) = self.position_of(idx2 as u32);
-        ((x2 as f32 - x1 as f32).powf(2.0) + (y2 as f32 - y1 as f32).powf(2.0)).sqrt()
+        let mut dx = (x2 as f32 - x1 as f32).abs();
+        let mut dy = (y2 as f32 - y1 as f32).abs();
+        if self.wrap {
+            dx = dx.min(self.width as f32 - dx);
+            dy = dy.min(self.height as f32 - dy);
+        }
+        match self.heuristic() {
+            Heuristic::Manhattan => dx + dy,
+            Heuristic::Euclidean => (dx.powf(2.0) + dy.powf(2.0)).sqrt(),
+            Heuristic::Chebyshev => dx.max(dy),
+            Heuristic::Octile => dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy),
+        }
     }
 }
 
+/// Marks the entity that the `Camera` follows when using `camera_follow_system`.
+pub struct CameraTarget;
+
+/// The smallest zoom used by the `Camera`. Smaller values are treated as this one.
+pub const MIN_CAMERA_ZOOM: f32 = 0.1;
+
 // TODO consider changing this to a component?
 /// Used to change the visible space of the world on screen.
 #[derive(new)]
@@ -168,6 +2325,119 @@ pub struct Camera {
     pub position: Point,
     /// The size in tiles that the camera can view.
     pub size: Point,
+    /// The size of the area in the center of the camera inside of which the
+    /// `CameraTarget` can move without the camera following it.
+    #[new(value = "Point::new(0, 0)")]
+    pub dead_zone: Point,
+    /// The number of screen cells spanned by a world tile on each axis.
+    /// At 2.0, each world tile spans two screen cells; at 0.5, two world tiles
+    /// share one screen cell.
+    /// At fractional zooms, positions are rounded down: the world tile `x` covers the
+    /// screen cells from `floor((x - position.x) * zoom)` up to (excluding)
+    /// `floor((x + 1 - position.x) * zoom)`, and at least one cell.
+    #[new(value = "1.0")]
+    pub zoom: f32,
+    /// An offset in world tiles added to the position when rendering only, set by
+    /// `camera_shake_system`. The conversions between world and screen positions,
+    /// used for input, ignore it.
+    #[new(value = "Point::new(0, 0)")]
+    pub shake: Point,
+}
+
+impl Camera {
+    /// The zoom, clamped to `MIN_CAMERA_ZOOM`.
+    pub(crate) fn scale(&self) -> f32 {
+        self.zoom.max(MIN_CAMERA_ZOOM)
+    }
+
+    fn to_screen_axis(&self, offset: i32) -> i32 {
+        (offset as f32 * self.scale()).floor() as i32
+    }
+
+    /// The number of world tiles visible by the camera on each axis.
+    pub fn visible_size(&self) -> Point {
+        Point::new(
+            (self.size.x as f32 / self.scale()).ceil() as i32,
+            (self.size.y as f32 / self.scale()).ceil() as i32,
+        )
+    }
+
+    /// Converts a world position into a position on screen.
+    /// Returns `None` if the position isn't visible by the camera.
+    pub fn world_to_screen(&self, world: Point) -> Option<Point> {
+        let screen = Point::new(
+            self.to_screen_axis(world.x - self.position.x),
+            self.to_screen_axis(world.y - self.position.y),
+        );
+        if screen.x >= 0 && screen.y >= 0 && screen.x < self.size.x && screen.y < self.size.y {
+            Some(screen)
+        } else {
+            None
+        }
+    }
+
+    /// Gives the screen cells covered by a world tile, clipped to the screen.
+    pub fn tile_to_screen(&self, world: Point) -> Vec<Point> {
+        let span = |offset: i32, size: i32| {
+            let start = self.to_screen_axis(offset);
+            let end = self.to_screen_axis(offset + 1).max(start + 1);
+            start.max(0)..end.min(size)
+        };
+        let xs = span(world.x - self.position.x, self.size.x);
+        span(world.y - self.position.y, self.size.y)
+            .flat_map(|y| xs.clone().map(move |x| Point::new(x, y)))
+            .collect()
+    }
+
+    /// Checks if any tile of the area starting at `world` (top-left) is visible by the camera.
+    pub fn is_area_visible(&self, world: Point, width: u32, height: u32) -> bool {
+        let offset = Point::new(world.x - self.position.x, world.y - self.position.y);
+        let visible = self.visible_size();
+        offset.x + (width as i32) > 0
+            && offset.y + (height as i32) > 0
+            && offset.x < visible.x
+            && offset.y < visible.y
+    }
+
+    /// Moves the camera so that the visible area stays inside of a map starting at (0, 0).
+    /// When the map is smaller than the camera on an axis, that axis is set to 0.
+    /// Run it after `camera_follow_system` to follow a target without showing the
+    /// outside of the map.
+    pub fn clamp_to(&mut self, map_width: u32, map_height: u32) {
+        let visible = self.visible_size();
+        self.position.x = clamp_axis(self.position.x, visible.x, map_width as i32);
+        self.position.y = clamp_axis(self.position.y, visible.y, map_height as i32);
+    }
+
+    /// The camera seen by the render functions, moved by the `shake` offset.
+    pub fn render_view(&self) -> Camera {
+        Camera {
+            position: Point::new(
+                self.position.x + self.shake.x,
+                self.position.y + self.shake.y,
+            ),
+            size: self.size,
+            dead_zone: self.dead_zone,
+            zoom: self.zoom,
+            shake: Point::new(0, 0),
+        }
+    }
+
+    /// Converts a position on screen (for example, a mouse click) into a world position.
+    pub fn screen_to_world(&self, screen: Point) -> Point {
+        Point::new(
+            self.position.x + (screen.x as f32 / self.scale()).floor() as i32,
+            self.position.y + (screen.y as f32 / self.scale()).floor() as i32,
+        )
+    }
+}
+
+fn clamp_axis(position: i32, size: i32, map_size: i32) -> i32 {
+    if map_size <= size {
+        0
+    } else {
+        position.max(0).min(map_size - size)
+    }
 }
 
 impl Default for Camera {
@@ -175,12 +2445,18 @@ impl Default for Camera {
         Self {
             position: Point::new(0, 0),
             size: Point::new(80, 50),
+            dead_zone: Point::new(0, 0),
+            zoom: 1.0,
+            shake: Point::new(0, 0),
         }
     }
 }
 
 /// A direction towards one of the 3d axis.
+/// Uses screen coordinates: x grows towards the East and y grows towards the South.
 #[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Direction {
     North,
     East,
@@ -190,10 +2466,383 @@ pub enum Direction {
     Down,
 }
 
+impl Direction {
+    /// Gives the offset of one tile in this direction on the 2d plane.
+    /// `Up` and `Down` are not part of the plane and give (0, 0).
+    pub fn delta(self) -> Point {
+        match self {
+            Direction::North => Point::new(0, -1),
+            Direction::East => Point::new(1, 0),
+            Direction::South => Point::new(0, 1),
+            Direction::West => Point::new(-1, 0),
+            Direction::Up | Direction::Down => Point::new(0, 0),
+        }
+    }
+
+    /// Moves the point by one tile in this direction.
+    pub fn apply(self, p: Point) -> Point {
+        let delta = self.delta();
+        Point::new(p.x + delta.x, p.y + delta.y)
+    }
+
+    /// Gives the main 2d direction to go from one point to the other, or `None` if
+    /// both points are the same.
+    /// On exact diagonals, the horizontal direction is chosen.
+    pub fn between(from: Point, to: Point) -> Option<Direction> {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        if dx == 0 && dy == 0 {
+            None
+        } else if dx.abs() >= dy.abs() {
+            Some(if dx > 0 {
+                Direction::East
+            } else {
+                Direction::West
+            })
+        } else {
+            Some(if dy > 0 {
+                Direction::South
+            } else {
+                Direction::North
+            })
+        }
+    }
+
+    /// Gives the opposite direction. `Up` and `Down` are opposites.
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    /// Turns a quarter clockwise: North, East, South, West.
+    /// `Up` and `Down` are returned unchanged.
+    pub fn rotate_cw(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+            other => other,
+        }
+    }
+
+    /// Turns a quarter counter-clockwise: North, West, South, East.
+    /// `Up` and `Down` are returned unchanged.
+    pub fn rotate_ccw(self) -> Direction {
+        match self {
+            Direction::North => Direction::West,
+            Direction::West => Direction::South,
+            Direction::South => Direction::East,
+            Direction::East => Direction::North,
+            other => other,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
     #[test]
+    fn heuristics() {
+        let mut map = CollisionMap::new(10, 10);
+        let (a, b) = (map.index_of(1, 1) as usize, map.index_of(4, 5) as usize);
+        assert_eq!(map.heuristic(), Heuristic::Manhattan);
+        assert_eq!(map.get_pathing_distance(a, b), 7.0);
+        map.set_heuristic(Heuristic::Euclidean);
+        assert_eq!(map.get_pathing_distance(a, b), 5.0);
+        map.set_heuristic(Heuristic::Chebyshev);
+        assert_eq!(map.get_pathing_distance(a, b), 4.0);
+        map.set_heuristic(Heuristic::Octile);
+        assert!(
+            (map.get_pathing_distance(a, b) - (1.0 + 3.0 * std::f32::consts::SQRT_2)).abs()
+                < 0.0001
+        );
+    }
+    #[test]
+    fn boolean_combinators() {
+        let mut map = CollisionMap::new(3, 2);
+        map.set(0, 0);
+        map.set(1, 1);
+        let original = map.clone();
+        let mut other = CollisionMap::new(3, 2);
+        other.set(2, 0);
+        other.set(2, 1);
+
+        map.union_with(&other).unwrap();
+        assert_eq!(map.count_set(), 4);
+        map.difference_with(&other).unwrap();
+        assert!(map == original);
+
+        map.intersect_with(&other).unwrap();
+        assert_eq!(map.count_set(), 0);
+        map.invert();
+        assert_eq!(map.count_set(), 6);
+        map.intersect_with(&other).unwrap();
+        assert!(map == other);
+
+        assert_eq!(
+            map.union_with(&CollisionMap::new(2, 3)),
+            Err(SizeMismatch {
+                expected: (3, 2),
+                found: (2, 3)
+            })
+        );
+    }
+    #[test]
+    fn sync_diff() {
+        let mut previous = CollisionMap::new(4, 3);
+        previous.set(1, 0);
+        previous.set(3, 2);
+        let mut map = previous.clone();
+        assert!(map.diff(&previous).unwrap().is_empty());
+        map.unset(1, 0);
+        map.set(2, 1);
+        let changes = map.diff(&previous).unwrap();
+        assert_eq!(changes, vec![(1, 0, false), (2, 1, true)]);
+
+        previous.apply_diff(&changes).unwrap();
+        assert!(previous == map);
+        assert_eq!(
+            map.diff(&CollisionMap::new(3, 4)),
+            Err(SizeMismatch {
+                expected: (3, 4),
+                found: (4, 3)
+            })
+        );
+        assert!(previous.apply_diff(&[(0, 0, true), (4, 0, true)]).is_err());
+        assert!(!previous.is_set(0, 0));
+
+        // Entity collisions stay out of the diff and of the receiver's static layer.
+        map.set_dynamic(0, 2);
+        map.set_dynamic(2, 1);
+        assert!(map.diff(&previous).unwrap().is_empty());
+        previous.set_dynamic(3, 0);
+        map.set(0, 1);
+        let changes = map.diff(&previous).unwrap();
+        assert_eq!(changes, vec![(0, 1, true)]);
+        previous.apply_diff(&changes).unwrap();
+        previous.clear_dynamic();
+        assert!(!previous.is_set(3, 0) && !previous.is_set(0, 2));
+        assert!(previous.is_set_static(0, 1));
+    }
+    #[test]
+    fn straight_walls() {
+        let mut map = CollisionMap::new(4, 4);
+        map.set_line(Point::new(0, 0), Point::new(3, 3));
+        assert_eq!(
+            map.iter_set().collect::<Vec<_>>(),
+            vec![(0, 0), (1, 1), (2, 2), (3, 3)]
+        );
+
+        // Clipped at the edges of the map.
+        let mut map = CollisionMap::new(4, 4);
+        map.set_line(Point::new(-2, 1), Point::new(6, 1));
+        assert_eq!(map.count_set(), 4);
+        assert!((0..4).all(|x| map.is_set(x, 1)));
+        map.clear();
+        map.set_row(3, 5, 2);
+        map.set_column(0, 1, 9);
+        assert_eq!(
+            map.iter_set().collect::<Vec<_>>(),
+            vec![(0, 1), (0, 2), (0, 3), (2, 3), (3, 3)]
+        );
+        map.set_row(u32::MAX, 0, u32::MAX);
+        assert_eq!(map.count_set(), 5);
+    }
+    #[test]
+    fn rect_fill() {
+        let mut map = CollisionMap::new(5, 4);
+        map.set_rect(3, 2, 10, 10);
+        assert_eq!(
+            map.iter_set().collect::<Vec<_>>(),
+            vec![(3, 2), (4, 2), (3, 3), (4, 3)]
+        );
+        map.unset_rect(0, 0, 4, 3);
+        assert_eq!(map.count_set(), 3);
+        map.clear();
+        map.fill_border(0, 0, 4, 4);
+        assert_eq!(map.count_set(), 12);
+        assert!(!map.is_set(1, 1) && !map.is_set(2, 2));
+        map.clear();
+        // The right and bottom walls are outside of the map.
+        map.fill_border(2, 1, 10, 10);
+        assert_eq!(
+            map.iter_set().collect::<Vec<_>>(),
+            vec![(2, 1), (3, 1), (4, 1), (2, 2), (2, 3)]
+        );
+        map.fill_border(0, 0, 0, 3);
+        assert_eq!(map.count_set(), 5);
+    }
+    #[test]
+    fn stamp_multisprite() {
+        let mut map = CollisionMap::new(4, 4);
+        let sprite =
+            MultiSprite::from_string("####", 2, 2, RGBA::named(WHITE), Some(RGBA::named(BLACK)));
+        map.stamp(&sprite, Point::new(1, 1));
+        assert_eq!(
+            map.iter_set().collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1), (1, 2), (2, 2)]
+        );
+        map.stamp(&sprite, Point::new(3, -1));
+        assert!(map.is_set(3, 0));
+        assert_eq!(map.count_set(), 5);
+        map.unstamp(&sprite, Point::new(1, 1));
+        map.unstamp(&sprite, Point::new(3, -1));
+        assert_eq!(map.count_set(), 0);
+    }
+    #[test]
+    fn fov_pillar_shadow() {
+        let mut map = CollisionMap::new(5, 3);
+        map.set(2, 1);
+        let visible = map.field_of_view(Point::new(0, 1), 10);
+        let mut expected = std::collections::HashSet::new();
+        for y in 0..3 {
+            for x in 0..5 {
+                expected.insert((x, y));
+            }
+        }
+        expected.remove(&(3, 1));
+        expected.remove(&(4, 1));
+        assert_eq!(visible, expected);
+
+        for (x, y) in visible {
+            if !map.is_set(x, y) {
+                let back = map.field_of_view(Point::new(x as i32, y as i32), 10);
+                assert!(back.contains(&(0, 1)));
+            }
+        }
+
+        let visible = map.field_of_view(Point::new(0, 1), 1);
+        assert_eq!(visible.len(), 4);
+        assert!(map.field_of_view(Point::new(-1, 0), 5).is_empty());
+    }
+    #[test]
+    fn glyph_transform() {
+        let arrow = to_cp437('↑');
+        assert_eq!(
+            GlyphTransform::facing(Direction::West).apply(arrow),
+            to_cp437('←')
+        );
+        assert_eq!(GlyphTransform::facing(Direction::Up).apply(arrow), arrow);
+        let transform = GlyphTransform {
+            flip_y: true,
+            quarter_turns: 1,
+            ..Default::default()
+        };
+        assert_eq!(transform.apply(arrow), to_cp437('←'));
+        assert_eq!(transform.apply(to_cp437('@')), to_cp437('@'));
+    }
+    #[test]
+    fn multisprite_footprint() {
+        let sprite =
+            MultiSprite::from_string("abcdef", 3, 2, RGBA::named(WHITE), Some(RGBA::named(BLACK)));
+        let footprint = sprite.footprint(Point::new(-1, 4));
+        assert_eq!(footprint.len(), 6);
+        assert_eq!(footprint[0], Point::new(-1, 4));
+        assert_eq!(footprint[5], Point::new(1, 5));
+        for p in footprint {
+            assert!(sprite.occupies(Point::new(-1, 4), p));
+        }
+        assert!(!sprite.occupies(Point::new(-1, 4), Point::new(2, 4)));
+        assert!(!sprite.occupies(Point::new(-1, 4), Point::new(0, 6)));
+        assert!(!sprite.occupies(Point::new(-1, 4), Point::new(-2, 5)));
+    }
+    #[test]
+    fn multisprite_transparency() {
+        // An L-shaped building.
+        let mut sprite =
+            MultiSprite::from_string("##.#", 2, 2, RGBA::named(WHITE), Some(RGBA::named(BLACK)));
+        sprite.tiles[2].glyph = TRANSPARENT_GLYPH;
+        let anchor = Point::new(1, 1);
+        assert_eq!(
+            sprite.footprint(anchor),
+            vec![Point::new(1, 1), Point::new(2, 1), Point::new(2, 2)]
+        );
+        assert!(!sprite.occupies(anchor, Point::new(1, 2)));
+        assert_eq!(Footprint::tiles_of(None, Some(&sprite), anchor).len(), 3);
+        assert_eq!(
+            Footprint::tiles_of(Some(&Footprint::new(2, 2)), Some(&sprite), anchor).len(),
+            4
+        );
+        let mut map = CollisionMap::new(4, 4);
+        map.stamp(&sprite, anchor);
+        assert_eq!(map.count_set(), 3);
+        assert!(!map.is_set(1, 2));
+        assert_eq!(sprite.rotated(1).transparent_glyph, TRANSPARENT_GLYPH);
+
+        let sprite = MultiSprite::from_string("# ##", 2, 2, RGBA::named(WHITE), None)
+            .with_transparent_glyph(to_cp437(' '));
+        assert!(sprite.is_transparent(1, 0));
+        assert_eq!(sprite.rotated(2).footprint(Point::new(0, 0)).len(), 3);
+        assert!(sprite.rotated(2).is_transparent(0, 1));
+    }
+    #[test]
+    fn multisprite_from_xp() {
+        let mut xp = XpFile::new(2, 2);
+        let mut top = xp.layers[0].clone();
+        let wall = XpCell {
+            ch: to_cp437('#') as u32,
+            fg: XpColor::new(255, 255, 255),
+            bg: XpColor::new(0, 0, 0),
+        };
+        *xp.layers[0].get_mut(0, 0).unwrap() = wall;
+        *xp.layers[0].get_mut(1, 1).unwrap() = wall;
+        *top.get_mut(1, 1).unwrap() = XpCell {
+            ch: to_cp437('@') as u32,
+            ..wall
+        };
+        xp.layers.push(top);
+        let sprite = MultiSprite::from_xp(&xp);
+        assert_eq!((sprite.width, sprite.height), (2, 2));
+        assert_eq!(sprite.get(0, 0).unwrap().glyph, to_cp437('#'));
+        assert_eq!(sprite.get(1, 1).unwrap().glyph, to_cp437('@'));
+        assert_eq!(sprite.get(0, 0).unwrap().bg, Some(RGBA::named(BLACK)));
+        assert!(sprite.is_transparent(1, 0) && sprite.is_transparent(0, 1));
+    }
+    #[test]
+    fn multisprite_rotation() {
+        let sprite =
+            MultiSprite::from_string("abcdef", 3, 2, RGBA::named(WHITE), Some(RGBA::named(BLACK)));
+        let rotated = sprite.rotated(1);
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        let glyphs = rotated.tiles.iter().map(|t| t.glyph).collect::<Vec<_>>();
+        assert_eq!(
+            glyphs,
+            "dae bfc"
+                .replace(' ', "")
+                .chars()
+                .map(to_cp437)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(rotated.rotated(1), sprite.rotated(2));
+        assert_eq!(sprite.rotated(1).rotated(1).rotated(1).rotated(1), sprite);
+        assert_eq!(sprite.rotated(4), sprite);
+
+        let mut remap = HashMap::new();
+        remap.insert(to_cp437('-'), to_cp437('|'));
+        remap.insert(to_cp437('|'), to_cp437('-'));
+        let line =
+            MultiSprite::from_string("--", 2, 1, RGBA::named(WHITE), Some(RGBA::named(BLACK)));
+        let rotated = line.rotated_with(1, &remap);
+        assert_eq!(rotated.tiles[0].glyph, to_cp437('|'));
+        assert_eq!(rotated.rotated_with(3, &remap), line);
+
+        // Missing tiles are holes instead of panicking.
+        let short = MultiSprite::new(2, 2, line.tiles.clone());
+        let rotated = short.rotated(1);
+        assert_eq!(rotated.tiles.len(), 4);
+        assert_eq!(rotated.tiles[1].glyph, to_cp437('-'));
+        assert!(rotated.is_transparent(0, 0) && rotated.is_transparent(0, 1));
+        assert!(MultiSprite::new(3, 1, vec![])
+            .rotated(3)
+            .is_transparent(0, 2));
+    }
+    #[test]
     fn collision_map_set_unset_clear() {
         let mut map = CollisionMap::new(5, 5);
         assert!(!map.is_set(3, 3));
@@ -206,6 +2855,435 @@ mod tests {
         assert!(!map.is_set(3, 3));
     }
     #[test]
+    fn static_and_dynamic_layers() {
+        let mut map = CollisionMap::new(5, 5);
+        map.set(1, 0);
+        map.set_dynamic(1, 0);
+        map.set_dynamic(3, 0);
+        assert!(map.is_set(1, 0) && map.is_set(3, 0));
+        assert!(!map.is_set_static(3, 0) && map.is_set_dynamic(3, 0));
+        assert_eq!(map.iter_set().collect::<Vec<_>>(), vec![(1, 0), (3, 0)]);
+        assert_eq!(map.count_set(), 2);
+        assert!(map.is_opaque(3));
+        assert!(!map.has_line_of_sight(Point::new(2, 0), Point::new(4, 0)));
+
+        map.clear_dynamic();
+        assert!(map.is_set(1, 0) && !map.is_set(3, 0));
+        map.set_dynamic(2, 1);
+        map.unset(1, 0);
+        assert!(!map.is_set(1, 0) && map.is_set_dynamic(2, 1));
+        map.resize(3, 3);
+        assert!(map.is_set_dynamic(2, 1) && !map.is_set_static(2, 1));
+        map.clear();
+        assert_eq!(map.count_set(), 0);
+    }
+    #[test]
+    fn try_set_out_of_bounds() {
+        let mut map = CollisionMap::new(5, 3);
+        assert_eq!(map.try_set(4, 2), Ok(()));
+        assert_eq!(map.try_is_set(4, 2), Some(true));
+        assert_eq!(map.try_unset(4, 2), Ok(()));
+        assert_eq!(map.try_is_set(4, 2), Some(false));
+        let err = OutOfBounds {
+            position: (5, 2),
+            size: (5, 3),
+        };
+        assert_eq!(map.try_set(5, 2), Err(err));
+        assert_eq!(map.try_unset(5, 2), Err(err));
+        assert_eq!(map.try_is_set(5, 2), None);
+        assert_eq!(map.try_is_set(0, 3), None);
+        assert_eq!(CollisionMap::new(0, 0).try_is_set(0, 0), None);
+    }
+    #[test]
+    fn empty_map() {
+        let mut map = CollisionMap::new(0, 0);
+        let err = OutOfBounds {
+            position: (0, 0),
+            size: (0, 0),
+        };
+        assert!(!map.contains(0, 0));
+        assert_eq!(map.try_set(0, 0), Err(err));
+        assert_eq!(map.try_set_dynamic(0, 0), Err(err));
+        assert_eq!(map.try_set_cost(0, 0, 2.0), Err(err));
+        assert_eq!(map.try_cost_of(0, 0), None);
+        map.set_rect(0, 0, 3, 3);
+        map.fill_border(0, 0, 3, 3);
+        assert_eq!(map.count_set(), 0);
+        assert_eq!(map.iter_set().next(), None);
+        assert_eq!(map.field_of_view(Point::new(0, 0), 5).len(), 0);
+        assert_eq!(find_path(&map, Point::new(0, 0), Point::new(0, 0)), None);
+    }
+    #[test]
+    fn resize_grow() {
+        let mut map = CollisionMap::new(3, 2);
+        map.set(2, 0);
+        map.set(0, 1);
+        map.set_cost(1, 1, 4.0);
+        map.resize(5, 2);
+        assert_eq!(map.size(), (5, 2));
+        assert!(map.is_set(2, 0));
+        assert!(map.is_set(0, 1));
+        assert!(!map.is_set(3, 0));
+        assert!(!map.is_set(4, 1));
+        assert_eq!(map.cost_of(1, 1), 4.0);
+        assert_eq!(map.cost_of(4, 1), 1.0);
+        map.resize(5, 4);
+        assert!(map.is_set(2, 0));
+        assert!(map.is_set(0, 1));
+        assert!(!map.is_set(0, 3));
+        map.set(4, 3);
+        assert!(map.is_set(4, 3));
+    }
+    #[test]
+    fn resize_shrink() {
+        let mut map = CollisionMap::new(4, 4);
+        map.set(1, 1);
+        map.set(3, 0);
+        map.set(0, 3);
+        map.resize(3, 4);
+        assert!(map.is_set(1, 1));
+        assert!(map.is_set(0, 3));
+        assert_eq!(map.try_is_set(3, 0), None);
+        map.resize(3, 2);
+        assert!(map.is_set(1, 1));
+        assert_eq!(map.try_is_set(0, 3), None);
+        map.resize(3, 4);
+        assert!(!map.is_set(0, 3));
+        assert!(!map.is_set(2, 0));
+    }
+    #[test]
+    fn str_layout() {
+        let map = CollisionMap::from_str_layout("###\n#.#\n###", '#').unwrap();
+        assert_eq!(map.size(), (3, 3));
+        assert!(map.is_set(0, 0));
+        assert!(!map.is_set(1, 1));
+        assert!(map.is_set(2, 2));
+        let map = CollisionMap::from_str_layout("#\n..##\n#.\n", '#').unwrap();
+        assert_eq!(map.size(), (4, 3));
+        assert!(map.is_set(0, 0));
+        assert!(!map.is_set(1, 0));
+        assert!(!map.is_set(3, 0));
+        assert!(map.is_set(3, 1));
+        assert!(!map.is_set(3, 2));
+        assert_eq!(
+            CollisionMap::from_str_layout("", '#').err(),
+            Some(LayoutError::Empty)
+        );
+        assert_eq!(
+            CollisionMap::from_str_layout("\n\n", '#').err(),
+            Some(LayoutError::Empty)
+        );
+    }
+    #[test]
+    fn iterate_set_tiles() {
+        let mut map = CollisionMap::new(100, 100);
+        assert_eq!(map.iter_set().next(), None);
+        assert_eq!(map.count_set(), 0);
+        map.set(99, 99);
+        map.set(3, 0);
+        map.set(7, 20);
+        assert_eq!(
+            map.iter_set().collect::<Vec<_>>(),
+            vec![(3, 0), (7, 20), (99, 99)]
+        );
+        assert_eq!(map.count_set(), 3);
+        map.unset(7, 20);
+        assert_eq!(map.count_set(), 2);
+    }
+    #[test]
+    fn line_of_sight() {
+        let mut map = CollisionMap::new(5, 5);
+        map.set(0, 0);
+        map.set(4, 0);
+        assert!(map.has_line_of_sight(Point::new(0, 0), Point::new(4, 0)));
+        assert!(map.has_line_of_sight(Point::new(2, 0), Point::new(2, 4)));
+        assert!(map.has_line_of_sight(Point::new(0, 0), Point::new(4, 4)));
+        assert!(map.has_line_of_sight(Point::new(3, 3), Point::new(3, 3)));
+        map.set(2, 0);
+        assert!(!map.has_line_of_sight(Point::new(0, 0), Point::new(4, 0)));
+        assert!(map.has_line_of_sight(Point::new(2, 0), Point::new(2, 4)));
+        map.set(2, 2);
+        assert!(!map.has_line_of_sight(Point::new(0, 0), Point::new(4, 4)));
+        assert!(!map.has_line_of_sight(Point::new(4, 4), Point::new(0, 0)));
+        assert!(map.has_line_of_sight(Point::new(1, 1), Point::new(2, 2)));
+        assert!(!map.has_line_of_sight(Point::new(0, 4), Point::new(-2, 4)));
+    }
+    #[test]
+    fn raycast_hit() {
+        let mut map = CollisionMap::new(5, 5);
+        map.set(0, 0);
+        assert_eq!(
+            map.raycast(Point::new(0, 0), Point::new(4, 0)),
+            RaycastHit::Clear
+        );
+        map.set(3, 0);
+        assert_eq!(
+            map.raycast(Point::new(0, 0), Point::new(4, 0)),
+            RaycastHit::Blocked {
+                last_clear: Point::new(2, 0),
+                blocking: Point::new(3, 0),
+            }
+        );
+        assert_eq!(
+            map.raycast(Point::new(0, 0), Point::new(3, 0)),
+            RaycastHit::Blocked {
+                last_clear: Point::new(2, 0),
+                blocking: Point::new(3, 0),
+            }
+        );
+        assert_eq!(
+            map.raycast(Point::new(2, 2), Point::new(2, 6)),
+            RaycastHit::Blocked {
+                last_clear: Point::new(2, 4),
+                blocking: Point::new(2, 5),
+            }
+        );
+        assert_eq!(
+            map.raycast(Point::new(1, 1), Point::new(1, 1)),
+            RaycastHit::Clear
+        );
+    }
+    #[test]
+    fn regions() {
+        let map = CollisionMap::from_str_layout("..#..\n..#..\n###.#\n....#", '#').unwrap();
+        let regions = map.connected_regions();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0], vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(
+            regions[1],
+            vec![
+                (3, 0),
+                (4, 0),
+                (3, 1),
+                (4, 1),
+                (3, 2),
+                (0, 3),
+                (1, 3),
+                (2, 3),
+                (3, 3)
+            ]
+        );
+        let labels = map.label_regions();
+        assert_eq!(labels.region_count(), 2);
+        assert_eq!(labels.region_id_of(1, 1), Some(0));
+        assert_eq!(labels.region_id_of(0, 3), Some(1));
+        assert_eq!(labels.region_id_of(2, 0), None);
+        assert_eq!(labels.region_id_of(10, 10), None);
+        let big = CollisionMap::new(1000, 1000);
+        assert_eq!(big.label_regions().region_count(), 1);
+    }
+    #[test]
+    fn small_regions() {
+        let mut map =
+            CollisionMap::from_str_layout("..#..\n..#..\n###.#\n....#\n##.##", '#').unwrap();
+        assert_eq!(map.region_sizes(), vec![4, 10]);
+        map.fill_small_regions(5);
+        assert_eq!(map.region_sizes(), vec![10]);
+        assert!(map.is_set(0, 0) && map.is_set(1, 1));
+        assert!(!map.is_set(2, 4) && !map.is_set(4, 0));
+        // Regions of exactly `min_size` tiles are kept.
+        map.fill_small_regions(10);
+        assert_eq!(map.region_sizes(), vec![10]);
+        assert_eq!(CollisionMap::new(0, 0).region_sizes(), Vec::<usize>::new());
+
+        // Movers on the dynamic layer don't split the regions.
+        let mut map = CollisionMap::from_str_layout(
+            ".....
+#####",
+            '#',
+        )
+        .unwrap();
+        map.set_dynamic(2, 0);
+        map.fill_small_regions(5);
+        assert!(!map.is_set(0, 0) && !map.is_set(4, 0));
+
+        // Pathfinding can't cut between two wall corners, so regions touching by a
+        // corner stay apart with every diagonal movement.
+        let layout = "..#\n..#\n##.";
+        for diagonal in [
+            DiagonalMovement::Never,
+            DiagonalMovement::IfEitherOpen,
+            DiagonalMovement::IfBothOpen,
+        ] {
+            let mut map = CollisionMap::from_str_layout(layout, '#').unwrap();
+            map.set_diagonal_movement(diagonal);
+            assert!(find_path(&map, Point::new(0, 0), Point::new(2, 2)).is_none());
+            map.fill_small_regions(2);
+            assert!(!map.is_set(0, 0) && map.is_set(2, 2));
+        }
+        // The edges of a wrapping map are connected.
+        let mut map = CollisionMap::from_str_layout(".#.\n###", '#').unwrap();
+        map.fill_small_regions(2);
+        assert!(map.is_set(0, 0) && map.is_set(2, 0));
+        let mut map = CollisionMap::from_str_layout(".#.\n###", '#').unwrap();
+        map.set_wrap(true);
+        map.fill_small_regions(2);
+        assert!(!map.is_set(0, 0) && !map.is_set(2, 0));
+    }
+    #[test]
+    fn camera_conversion() {
+        let camera = Camera::new(Point::new(10, -5), Point::new(20, 10));
+        assert_eq!(
+            camera.world_to_screen(Point::new(10, -5)),
+            Some(Point::new(0, 0))
+        );
+        assert_eq!(
+            camera.world_to_screen(Point::new(29, 4)),
+            Some(Point::new(19, 9))
+        );
+        assert_eq!(camera.world_to_screen(Point::new(30, 4)), None);
+        assert_eq!(camera.world_to_screen(Point::new(29, 5)), None);
+        assert_eq!(camera.world_to_screen(Point::new(9, 0)), None);
+        assert_eq!(camera.world_to_screen(Point::new(15, -6)), None);
+        assert_eq!(camera.screen_to_world(Point::new(19, 9)), Point::new(29, 4));
+        let p = Point::new(17, 2);
+        assert_eq!(
+            camera.screen_to_world(camera.world_to_screen(p).unwrap()),
+            p
+        );
+    }
+    #[test]
+    fn camera_zoom() {
+        let mut camera = Camera::new(Point::new(10, 10), Point::new(20, 10));
+        camera.zoom = 2.0;
+        assert_eq!(camera.visible_size(), Point::new(10, 5));
+        assert_eq!(
+            camera.world_to_screen(Point::new(12, 11)),
+            Some(Point::new(4, 2))
+        );
+        assert_eq!(camera.world_to_screen(Point::new(20, 11)), None);
+        assert_eq!(camera.screen_to_world(Point::new(5, 3)), Point::new(12, 11));
+        assert_eq!(
+            camera.tile_to_screen(Point::new(10, 10)),
+            vec![
+                Point::new(0, 0),
+                Point::new(1, 0),
+                Point::new(0, 1),
+                Point::new(1, 1)
+            ]
+        );
+        camera.zoom = 0.5;
+        assert_eq!(camera.visible_size(), Point::new(40, 20));
+        assert_eq!(
+            camera.world_to_screen(Point::new(13, 10)),
+            Some(Point::new(1, 0))
+        );
+        assert_eq!(camera.screen_to_world(Point::new(1, 0)), Point::new(12, 10));
+        assert_eq!(
+            camera.tile_to_screen(Point::new(12, 10)),
+            vec![Point::new(1, 0)]
+        );
+        assert_eq!(
+            camera.tile_to_screen(Point::new(13, 10)),
+            vec![Point::new(1, 0)]
+        );
+        assert!(camera.is_area_visible(Point::new(49, 29), 1, 1));
+        camera.zoom = 0.0;
+        assert_eq!(camera.visible_size(), Point::new(200, 100));
+    }
+    #[test]
+    fn camera_clamp() {
+        let mut camera = Camera::new(Point::new(-5, 95), Point::new(20, 10));
+        camera.clamp_to(100, 100);
+        assert_eq!(camera.position, Point::new(0, 90));
+        camera.position = Point::new(85, 40);
+        camera.clamp_to(100, 100);
+        assert_eq!(camera.position, Point::new(80, 40));
+        camera.clamp_to(10, 100);
+        assert_eq!(camera.position, Point::new(0, 40));
+        camera.position = Point::new(3, 3);
+        camera.clamp_to(20, 10);
+        assert_eq!(camera.position, Point::new(0, 0));
+    }
+    #[test]
+    fn direction_delta() {
+        assert_eq!(Direction::North.delta(), Point::new(0, -1));
+        assert_eq!(Direction::East.delta(), Point::new(1, 0));
+        assert_eq!(Direction::South.delta(), Point::new(0, 1));
+        assert_eq!(Direction::West.delta(), Point::new(-1, 0));
+        assert_eq!(Direction::Up.delta(), Point::new(0, 0));
+        assert_eq!(Direction::Down.delta(), Point::new(0, 0));
+        let p = Point::new(3, 3);
+        for dir in [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .iter()
+        {
+            assert_eq!(dir.apply(p), move_position(&p, *dir));
+        }
+        assert_eq!(Direction::Up.apply(p), p);
+    }
+    #[test]
+    fn direction_rotation() {
+        let planar = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ];
+        for dir in planar.iter() {
+            assert_eq!(dir.rotate_cw().rotate_cw(), dir.opposite());
+            assert_eq!(dir.rotate_ccw().rotate_ccw(), dir.opposite());
+            assert_eq!(dir.rotate_cw().rotate_ccw(), *dir);
+            assert_eq!(dir.opposite().opposite(), *dir);
+            assert_eq!(dir.rotate_cw().rotate_cw().rotate_cw().rotate_cw(), *dir);
+            assert_eq!(
+                dir.rotate_ccw().rotate_ccw().rotate_ccw().rotate_ccw(),
+                *dir
+            );
+            let delta = dir.delta();
+            let opposite = dir.opposite().delta();
+            assert_eq!((delta.x + opposite.x, delta.y + opposite.y), (0, 0));
+        }
+        assert_eq!(Direction::North.rotate_cw(), Direction::East);
+        assert_eq!(Direction::North.rotate_ccw(), Direction::West);
+        assert_eq!(Direction::Up.rotate_cw(), Direction::Up);
+        assert_eq!(Direction::Down.rotate_ccw(), Direction::Down);
+        assert_eq!(Direction::Up.opposite(), Direction::Down);
+    }
+    #[test]
+    fn direction_between() {
+        let o = Point::new(0, 0);
+        assert_eq!(Direction::between(o, o), None);
+        assert_eq!(
+            Direction::between(o, Point::new(0, -3)),
+            Some(Direction::North)
+        );
+        assert_eq!(
+            Direction::between(o, Point::new(0, 3)),
+            Some(Direction::South)
+        );
+        assert_eq!(
+            Direction::between(o, Point::new(4, 3)),
+            Some(Direction::East)
+        );
+        assert_eq!(
+            Direction::between(o, Point::new(-1, 3)),
+            Some(Direction::South)
+        );
+        assert_eq!(
+            Direction::between(o, Point::new(2, 2)),
+            Some(Direction::East)
+        );
+        assert_eq!(
+            Direction::between(o, Point::new(-2, -2)),
+            Some(Direction::West)
+        );
+        for dir in [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .iter()
+        {
+            assert_eq!(Direction::between(o, dir.apply(o)), Some(*dir));
+        }
+    }
+    #[test]
     fn small_map() {
         let _ = CollisionMap::new(0, 0);
         let mut map = CollisionMap::new(1, 1);
@@ -213,19 +3291,491 @@ mod tests {
         assert!(map.is_set(0, 0));
     }
     #[test]
+    fn flat_capacity() {
+        assert!(!CollisionMap::new(4096, 4096).is_chunked());
+        let mut big = CollisionMap::new(10_000, 10_000);
+        assert!(big.is_chunked());
+        big.set(9_999, 9_999);
+        assert!(big.is_set(9_999, 9_999));
+        let mut map = CollisionMap::new(10, 10);
+        map.resize(5_000, 5_000);
+        assert!(map.is_chunked());
+    }
+    #[test]
     fn huge_map() {
         let mut map = CollisionMap::new(1000, 1000);
         map.set(999, 999);
     }
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "outside of the 0x0 collision map")]
     fn small_map_out_of_bounds() {
         let mut map = CollisionMap::new(0, 0);
         map.set(0, 0);
         assert!(map.is_set(0, 0));
     }
     #[test]
-    #[should_panic]
+    fn wrap_path() {
+        let mut map = CollisionMap::new(10, 3);
+        assert_eq!(
+            find_path(&map, Point::new(1, 1), Point::new(8, 1))
+                .unwrap()
+                .len(),
+            8
+        );
+        map.set_wrap(true);
+        assert_eq!(
+            find_path(&map, Point::new(1, 1), Point::new(8, 1)),
+            Some(vec![
+                Point::new(1, 1),
+                Point::new(0, 1),
+                Point::new(9, 1),
+                Point::new(8, 1)
+            ])
+        );
+        assert_eq!(
+            find_path(&map, Point::new(4, 0), Point::new(4, 2)),
+            Some(vec![Point::new(4, 0), Point::new(4, 2)])
+        );
+        map.set(0, 1);
+        assert_eq!(
+            find_path(&map, Point::new(1, 1), Point::new(8, 1))
+                .unwrap()
+                .len(),
+            6
+        );
+    }
+    #[test]
+    fn diagonal_exits() {
+        let mut map = CollisionMap::new(3, 3);
+        let center = map.index_of(1, 1) as usize;
+        assert_eq!(map.get_available_exits(center).len(), 4);
+        map.set_diagonal_movement(DiagonalMovement::IfBothOpen);
+        assert_eq!(map.get_available_exits(center).len(), 8);
+        let corner = map.index_of(2, 2) as usize;
+        assert!(map
+            .get_available_exits(center)
+            .iter()
+            .any(|(i, c)| *i == corner && *c == std::f32::consts::SQRT_2));
+    }
+    #[test]
+    fn diagonal_corner_cutting() {
+        let mut map = CollisionMap::new(3, 3);
+        let start = map.index_of(0, 0) as usize;
+        let corner = map.index_of(1, 1) as usize;
+        let has_corner = |map: &CollisionMap| {
+            map.get_available_exits(start)
+                .iter()
+                .any(|(i, _)| *i == corner)
+        };
+        map.set(1, 0);
+        map.set_diagonal_movement(DiagonalMovement::IfEitherOpen);
+        assert!(has_corner(&map));
+        map.set_diagonal_movement(DiagonalMovement::IfBothOpen);
+        assert!(!has_corner(&map));
+        map.set(0, 1);
+        map.set_diagonal_movement(DiagonalMovement::IfEitherOpen);
+        assert!(!has_corner(&map));
+        map.set_diagonal_movement(DiagonalMovement::Never);
+        map.unset(1, 0);
+        map.unset(0, 1);
+        assert!(!has_corner(&map));
+    }
+    #[test]
+    fn diagonal_path() {
+        let mut map = CollisionMap::new(3, 3);
+        map.set_diagonal_movement(DiagonalMovement::IfBothOpen);
+        let path = a_star_search(map.index_of(0, 0), map.index_of(2, 2), &map);
+        assert!(path.success);
+        assert_eq!(path.steps.len(), 3);
+    }
+    #[test]
+    fn octile_heuristic() {
+        let mut map = CollisionMap::from_str_layout(
+            "........\n\
+             ##.#.#..\n\
+             #...##..\n\
+             ..#...#.\n\
+             #.#.....\n\
+             ........",
+            '#',
+        )
+        .unwrap();
+        map.set_diagonal_movement(DiagonalMovement::IfBothOpen);
+        assert_eq!(map.heuristic(), Heuristic::Octile);
+        let cost = |map: &CollisionMap, heuristic: Heuristic| {
+            let mut map = map.clone();
+            map.set_heuristic(heuristic);
+            let path = a_star_search(map.index_of(0, 0), map.index_of(7, 5), &map);
+            assert!(path.success);
+            path.steps
+                .windows(2)
+                .map(|w| {
+                    map.get_available_exits(w[0])
+                        .iter()
+                        .find(|(n, _)| *n == w[1])
+                        .unwrap()
+                        .1
+                })
+                .sum::<f32>()
+        };
+        // The admissible heuristics find the shortest path, Manhattan doesn't.
+        let octile = cost(&map, Heuristic::Octile);
+        assert!((octile - (8.0 + 2.0 * std::f32::consts::SQRT_2)).abs() < 0.0001);
+        assert_eq!(cost(&map, Heuristic::Euclidean), octile);
+        assert_eq!(cost(&map, Heuristic::Chebyshev), octile);
+        assert!(cost(&map, Heuristic::Manhattan) > octile + 0.1);
+        map.set_diagonal_movement(DiagonalMovement::Never);
+        assert_eq!(map.heuristic(), Heuristic::Manhattan);
+        // A chosen heuristic isn't replaced when the movement changes.
+        map.set_heuristic(Heuristic::Euclidean);
+        map.set_diagonal_movement(DiagonalMovement::IfEitherOpen);
+        assert_eq!(map.heuristic(), Heuristic::Euclidean);
+        map.set_diagonal_movement(DiagonalMovement::Never);
+        assert_eq!(map.heuristic(), Heuristic::Euclidean);
+    }
+    #[test]
+    fn ray_march_entities() {
+        let mut map = CollisionMap::from_str_layout(".....\n....#", '#').unwrap();
+        let mut entities = Entities::default();
+        let mut spatial_hash = SpatialHash::default();
+        let shooter = entities.create();
+        spatial_hash.insert(shooter, Point::new(0, 0));
+        let ally = entities.create();
+        spatial_hash.insert(ally, Point::new(2, 0));
+        let enemy = entities.create();
+        spatial_hash.insert(enemy, Point::new(3, 0));
+
+        let from = Point::new(0, 0);
+        assert_eq!(
+            map.ray_march_entities(from, Point::new(4, 0), &spatial_hash, |_| true),
+            RayMarchHit::Entity {
+                entity: ally,
+                tile: Point::new(2, 0)
+            }
+        );
+        let not_ally = |e| e != ally && e != shooter;
+        assert_eq!(
+            map.ray_march_entities(from, Point::new(4, 0), &spatial_hash, not_ally),
+            RayMarchHit::Entity {
+                entity: enemy,
+                tile: Point::new(3, 0)
+            }
+        );
+        // The enemy's collision blocks its tile, but it still gets hit.
+        map.set(3, 0);
+        assert_eq!(
+            map.ray_march_entities(from, Point::new(4, 0), &spatial_hash, not_ally),
+            RayMarchHit::Entity {
+                entity: enemy,
+                tile: Point::new(3, 0)
+            }
+        );
+        assert_eq!(
+            map.ray_march_entities(from, Point::new(4, 1), &spatial_hash, not_ally),
+            RayMarchHit::Wall {
+                last_clear: Point::new(3, 1),
+                blocking: Point::new(4, 1)
+            }
+        );
+        assert_eq!(
+            map.ray_march_entities(from, Point::new(2, 1), &spatial_hash, not_ally),
+            RayMarchHit::Clear
+        );
+    }
+    #[test]
+    fn bounding_box() {
+        let mut map = CollisionMap::new(10, 8);
+        assert_eq!(map.bounding_box(), None);
+        map.set(4, 3);
+        assert_eq!(
+            map.bounding_box(),
+            Some((Point::new(4, 3), Point::new(4, 3)))
+        );
+        map.set(7, 1);
+        map.set(2, 6);
+        assert_eq!(
+            map.bounding_box(),
+            Some((Point::new(2, 1), Point::new(7, 6)))
+        );
+        let mut chunked = CollisionMap::new_chunked(2000, 2000);
+        chunked.set(1500, 1900);
+        chunked.set(3, 1000);
+        assert_eq!(
+            chunked.bounding_box(),
+            Some((Point::new(3, 1000), Point::new(1500, 1900)))
+        );
+    }
+    #[test]
+    fn path_cost() {
+        let mut map = CollisionMap::from_str_layout("...\n.#.\n...", '#').unwrap();
+        map.set_cost(2, 0, 3.0);
+        let route = [Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)];
+        assert_eq!(map.path_cost(&route), Some(4.0));
+        assert_eq!(map.path_cost(&[]), Some(0.0));
+        assert_eq!(map.path_cost(&[Point::new(2, 2)]), Some(0.0));
+        assert_eq!(map.path_cost(&[Point::new(1, 1)]), None);
+        assert_eq!(map.path_cost(&[Point::new(0, 0), Point::new(0, -1)]), None);
+        assert_eq!(map.path_cost(&[Point::new(0, 0), Point::new(0, 2)]), None);
+        assert_eq!(map.path_cost(&[Point::new(0, 1), Point::new(1, 1)]), None);
+
+        let diagonal = [Point::new(0, 1), Point::new(1, 2)];
+        assert_eq!(map.path_cost(&diagonal), None);
+        map.set_diagonal_movement(DiagonalMovement::IfEitherOpen);
+        assert_eq!(map.path_cost(&diagonal), Some(std::f32::consts::SQRT_2));
+    }
+    #[test]
+    fn walkable_neighbors() {
+        let mut map = CollisionMap::new(3, 3);
+        map.set(1, 0);
+        assert_eq!(
+            map.walkable_neighbors(Point::new(0, 0)).to_vec(),
+            vec![Point::new(0, 1)]
+        );
+        assert_eq!(map.walkable_neighbors(Point::new(1, 1)).len(), 3);
+        assert!(map.walkable_neighbors(Point::new(-1, 0)).is_empty());
+        assert!(map.walkable_neighbors(Point::new(3, 0)).is_empty());
+
+        map.set_diagonal_movement(DiagonalMovement::IfEitherOpen);
+        assert_eq!(map.walkable_neighbors(Point::new(1, 1)).len(), 7);
+        map.set_wrap(true);
+        assert_eq!(map.walkable_neighbors(Point::new(0, 0)).len(), 7);
+    }
+
+    #[test]
+    fn chunked_storage() {
+        let mut flat = CollisionMap::from_str_layout("..#.\n#...\n..##", '#').unwrap();
+        let mut chunked = CollisionMap::new_chunked(4, 3);
+        for (x, y) in flat.iter_set() {
+            chunked.set(x, y);
+        }
+        assert!(chunked.is_chunked());
+        assert!(!flat.is_chunked());
+        assert!(chunked == flat);
+        assert_eq!(
+            chunked.iter_set().collect::<Vec<_>>(),
+            vec![(2, 0), (0, 1), (2, 2), (3, 2)]
+        );
+        assert_eq!(
+            find_path(&chunked, Point::new(0, 0), Point::new(3, 1)),
+            find_path(&flat, Point::new(0, 0), Point::new(3, 1))
+        );
+        flat.unset(0, 1);
+        assert_eq!(flat.diff(&chunked), Ok(vec![(0, 1, false)]));
+        chunked.resize(5, 3);
+        assert!(chunked.is_chunked());
+        assert_eq!(chunked.count_set(), 4);
+
+        // Chunks are freed once empty, far tiles don't allocate the ones before them.
+        let mut huge = CollisionMap::new_chunked(10_000, 10_000);
+        huge.set(9_999, 9_999);
+        huge.set(0, 0);
+        assert!(huge.is_set(9_999, 9_999));
+        assert!(!huge.is_set(9_998, 9_999));
+        huge.unset(0, 0);
+        assert_eq!(huge.iter_set().collect::<Vec<_>>(), vec![(9_999, 9_999)]);
+        assert!(matches!(&huge.bitset, super::TileBits::Chunked(chunks) if chunks.len() == 1));
+    }
+    #[test]
+    fn max_tiles() {
+        // 65_535 * 65_537 is exactly `MAX_TILES`.
+        let mut map = CollisionMap::new(65_535, 65_537);
+        assert!(map.is_chunked());
+        map.set(65_534, 65_536);
+        map.set(65_534, 65_535);
+        assert!(!map.is_set(65_533, 65_536));
+        assert_eq!(map.iter_set().count(), 2);
+        assert_eq!(map.index_of(65_534, 65_536) as u64, MAX_TILES - 1);
+    }
+    #[test]
+    #[should_panic(expected = "Map size 100000x100000 is over the 4294967295 tiles limit")]
+    fn too_many_tiles() {
+        CollisionMap::new(100_000, 100_000);
+    }
+
+    #[test]
+    fn area_tiles() {
+        let mut map = CollisionMap::new(7, 7);
+        assert_eq!(map.tiles_in_circle(Point::new(3, 3), 1, false).len(), 5);
+        assert_eq!(map.tiles_in_circle(Point::new(3, 3), 2, false).len(), 13);
+        // Clipped to the map.
+        assert_eq!(
+            map.tiles_in_circle(Point::new(0, 0), 1, false),
+            vec![Point::new(0, 0), Point::new(1, 0), Point::new(0, 1)]
+        );
+
+        map.set(4, 3);
+        let blocked = map.tiles_in_circle(Point::new(3, 3), 2, true);
+        assert_eq!(blocked.len(), 11);
+        assert!(!blocked.contains(&Point::new(4, 3)));
+        assert!(!blocked.contains(&Point::new(5, 3)));
+
+        assert_eq!(
+            map.tiles_in_cone(Point::new(3, 3), Direction::North, 2, 90.0, false),
+            vec![
+                Point::new(3, 1),
+                Point::new(2, 2),
+                Point::new(3, 2),
+                Point::new(4, 2)
+            ]
+        );
+        assert_eq!(
+            map.tiles_in_cone(Point::new(3, 3), Direction::East, 3, 10.0, true),
+            vec![]
+        );
+        assert!(map
+            .tiles_in_cone(Point::new(3, 3), Direction::Up, 3, 90.0, false)
+            .is_empty());
+
+        assert_eq!(
+            map.tiles_in_line(Point::new(3, 3), Direction::East, 5, false),
+            vec![Point::new(4, 3), Point::new(5, 3), Point::new(6, 3)]
+        );
+        assert!(map
+            .tiles_in_line(Point::new(3, 3), Direction::East, 5, true)
+            .is_empty());
+        assert_eq!(
+            map.tiles_in_line(Point::new(3, 3), Direction::South, 2, true),
+            vec![Point::new(3, 4), Point::new(3, 5)]
+        );
+    }
+
+    #[test]
+    fn contains() {
+        let map = CollisionMap::new(3, 2);
+        assert!(map.contains(0, 0));
+        assert!(map.contains(2, 1));
+        assert!(!map.contains(3, 0));
+        assert!(!map.contains(0, 2));
+        assert!(!CollisionMap::new(0, 0).contains(0, 0));
+    }
+
+    #[test]
+    fn nearest_walkable() {
+        let mut map = CollisionMap::from_str_layout(".....\n.###.\n.#.#.\n.###.", '#').unwrap();
+        assert_eq!(
+            map.nearest_walkable(Point::new(2, 2)),
+            Some(Point::new(2, 2))
+        );
+        // Surrounded by walls, the closest tile behind them is found.
+        map.set(2, 2);
+        assert_eq!(
+            map.nearest_walkable(Point::new(2, 2)),
+            Some(Point::new(2, 0))
+        );
+        assert_eq!(
+            map.nearest_walkable(Point::new(1, 2)),
+            Some(Point::new(0, 2))
+        );
+        assert_eq!(map.nearest_walkable(Point::new(-1, 0)), None);
+        map.set_rect(0, 0, 5, 4);
+        assert_eq!(map.nearest_walkable(Point::new(2, 2)), None);
+    }
+
+    #[test]
+    fn nearest_open() {
+        let mut map = CollisionMap::new(5, 5);
+        assert_eq!(map.nearest_open(2, 2), Some((2, 2)));
+        assert_eq!(map.nearest_open(5, 0), None);
+        map.set_rect(0, 0, 5, 4);
+        map.unset(0, 0);
+        assert_eq!(map.nearest_open(2, 3), Some((2, 4)));
+        assert_eq!(map.nearest_open(1, 1), Some((0, 0)));
+        map.set_rect(0, 0, 5, 5);
+        assert_eq!(map.nearest_open(2, 2), None);
+
+        // Ties go to the topmost, then leftmost tile.
+        let mut map = CollisionMap::new(5, 5);
+        map.set_rect(1, 1, 3, 3);
+        assert_eq!(map.nearest_open(2, 2), Some((2, 0)));
+        map.unset(1, 3);
+        map.unset(3, 1);
+        assert_eq!(map.nearest_open(2, 2), Some((3, 1)));
+
+        // Only the open tile in a far corner of a big blocked map.
+        let mut map = CollisionMap::new(1000, 1000);
+        map.set_rect(0, 0, 1000, 1000);
+        map.unset(999, 0);
+        assert_eq!(map.nearest_open(0, 999), Some((999, 0)));
+        assert_eq!(map.nearest_open(999, 0), Some((999, 0)));
+    }
+
+    #[test]
+    fn cost_brushes() {
+        let mut map = CollisionMap::new(5, 5);
+        map.set_cost_rect(3, 3, 4, 4, 2.0);
+        assert_eq!(map.cost_of(3, 3), 2.0);
+        assert_eq!(map.cost_of(4, 4), 2.0);
+        assert_eq!(map.cost_of(2, 3), 1.0);
+        map.set_cost_circle(Point::new(0, 0), 2, 5.0);
+        assert_eq!(map.cost_of(0, 0), 5.0);
+        assert_eq!(map.cost_of(2, 0), 5.0);
+        assert_eq!(map.cost_of(1, 1), 5.0);
+        assert_eq!(map.cost_of(2, 1), 1.0);
+        map.set_cost_circle(Point::new(-1, 2), 1, 3.0);
+        assert_eq!(map.cost_of(0, 2), 3.0);
+        assert_eq!(map.cost_of(0, 3), 1.0);
+    }
+    #[test]
+    fn weighted_costs() {
+        let mut map = CollisionMap::new(3, 3);
+        assert_eq!(map.cost_of(1, 1), 1.0);
+        let start = map.index_of(0, 1);
+        let goal = map.index_of(2, 1);
+        assert_eq!(a_star_search(start, goal, &map).steps.len(), 3);
+        map.set_cost(1, 1, 10.0);
+        assert_eq!(map.cost_of(1, 1), 10.0);
+        assert_eq!(map.cost_of(0, 0), 1.0);
+        let path = a_star_search(start, goal, &map);
+        assert!(path.success);
+        assert_eq!(path.steps.len(), 5);
+        assert!(!path.steps.contains(&(map.index_of(1, 1) as usize)));
+        map.set(1, 0);
+        map.set_cost(1, 0, 0.1);
+        let from = map.index_of(0, 0) as usize;
+        assert!(map
+            .get_available_exits(from)
+            .iter()
+            .all(|(i, _)| *i != map.index_of(1, 0) as usize));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use super::CollisionMapData;
+        use std::convert::TryFrom;
+        let mut map = CollisionMap::new(1000, 1000);
+        for i in 0..1000 {
+            map.set(i, i);
+            map.set(999 - i, i / 3);
+        }
+        map.set_cost(5, 5, 3.0);
+        let data = CollisionMapData::from(map.clone());
+        assert_eq!(data.words.len(), 15625);
+        let loaded = CollisionMap::try_from(data).unwrap();
+        assert_eq!(loaded.size(), (1000, 1000));
+        assert_eq!(loaded.cost_of(5, 5), 3.0);
+        for x in 0..1000 {
+            for y in 0..1000 {
+                assert_eq!(map.is_set(x, y), loaded.is_set(x, y));
+            }
+        }
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_malformed() {
+        use super::CollisionMapData;
+        use std::convert::TryFrom;
+        let mut data = CollisionMapData::from(CollisionMap::new(10, 10));
+        data.words[1] = 1 << 40;
+        assert!(CollisionMap::try_from(data).is_err());
+        let mut data = CollisionMapData::from(CollisionMap::new(10, 10));
+        data.height = 20;
+        assert!(CollisionMap::try_from(data).is_err());
+        let mut data = CollisionMapData::from(CollisionMap::new(10, 10));
+        data.width = u32::MAX;
+        assert!(CollisionMap::try_from(data).is_err());
+    }
+    #[test]
+    #[should_panic(expected = "Position (1000, 1000) is outside")]
     fn big_map_out_of_bounds() {
         let mut map = CollisionMap::new(1000, 1000);
         map.set(1000, 1000);
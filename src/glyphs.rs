@@ -0,0 +1,137 @@
+use crate::*;
+
+/// Glyphs that turn into the next one of their group when rotated clockwise.
+const ROTATIONS: [[char; 4]; 10] = [
+    ['↑', '→', '↓', '←'],
+    ['▲', '►', '▼', '◄'],
+    ['^', '>', 'v', '<'],
+    ['┌', '┐', '┘', '└'],
+    ['┬', '┤', '┴', '├'],
+    ['╔', '╗', '╝', '╚'],
+    ['╦', '╣', '╩', '╠'],
+    ['▀', '▐', '▄', '▌'],
+    ['─', '│', '─', '│'],
+    ['═', '║', '═', '║'],
+];
+
+/// Glyphs swapped when mirrored horizontally.
+const MIRRORS_X: [(char, char); 13] = [
+    ('→', '←'),
+    ('►', '◄'),
+    ('>', '<'),
+    ('/', '\\'),
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('┌', '┐'),
+    ('└', '┘'),
+    ('├', '┤'),
+    ('╔', '╗'),
+    ('╚', '╝'),
+    ('╠', '╣'),
+];
+
+/// Glyphs swapped when mirrored vertically.
+const MIRRORS_Y: [(char, char); 10] = [
+    ('↑', '↓'),
+    ('▲', '▼'),
+    ('^', 'v'),
+    ('/', '\\'),
+    ('┌', '└'),
+    ('┐', '┘'),
+    ('┬', '┴'),
+    ('╔', '╚'),
+    ('╗', '╝'),
+    ('╦', '╩'),
+];
+
+/// The glyph used for characters missing from codepage 437: `?`.
+pub const FALLBACK_GLYPH: u16 = 63;
+
+/// Gives the codepage 437 glyph showing the character, so that sprites can be
+/// written as `Sprite { glyph: glyph_from_char('@'), .. }`.
+/// Characters missing from codepage 437 give `FALLBACK_GLYPH`.
+pub fn glyph_from_char(c: char) -> u16 {
+    match to_cp437(c) {
+        0 if c != '\0' => FALLBACK_GLYPH,
+        glyph => glyph,
+    }
+}
+
+/// Gives the character shown by the codepage 437 glyph.
+/// Glyphs above 255, which are outside of codepage 437, give `?`.
+pub fn char_from_glyph(glyph: u16) -> char {
+    if glyph > 255 {
+        '?'
+    } else {
+        to_char(glyph as u8)
+    }
+}
+
+/// Gives the glyph rotated clockwise by 90 degrees.
+/// Glyphs without a rotated counterpart (like letters) are returned unchanged.
+pub fn rotate_glyph_cw(glyph: u16) -> u16 {
+    for group in ROTATIONS.iter() {
+        if let Some(i) = group.iter().position(|c| to_cp437(*c) == glyph) {
+            return to_cp437(group[(i + 1) % 4]);
+        }
+    }
+    glyph
+}
+
+/// Gives the glyph mirrored horizontally, or the same glyph if it is symmetric
+/// or has no mirrored counterpart.
+pub fn mirror_glyph_x(glyph: u16) -> u16 {
+    swap_glyph(&MIRRORS_X, glyph)
+}
+
+/// Gives the glyph mirrored vertically, or the same glyph if it is symmetric
+/// or has no mirrored counterpart.
+pub fn mirror_glyph_y(glyph: u16) -> u16 {
+    swap_glyph(&MIRRORS_Y, glyph)
+}
+
+fn swap_glyph(pairs: &[(char, char)], glyph: u16) -> u16 {
+    for (a, b) in pairs.iter() {
+        if to_cp437(*a) == glyph {
+            return to_cp437(*b);
+        } else if to_cp437(*b) == glyph {
+            return to_cp437(*a);
+        }
+    }
+    glyph
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn rotate_and_mirror() {
+        let arrow = to_cp437('↑');
+        assert_eq!(rotate_glyph_cw(arrow), to_cp437('→'));
+        let mut glyph = arrow;
+        for _ in 0..4 {
+            glyph = rotate_glyph_cw(glyph);
+        }
+        assert_eq!(glyph, arrow);
+        assert_eq!(rotate_glyph_cw(to_cp437('│')), to_cp437('─'));
+        assert_eq!(rotate_glyph_cw(to_cp437('@')), to_cp437('@'));
+        assert_eq!(mirror_glyph_x(to_cp437('┌')), to_cp437('┐'));
+        assert_eq!(mirror_glyph_x(to_cp437('↑')), to_cp437('↑'));
+        assert_eq!(mirror_glyph_y(to_cp437('↑')), to_cp437('↓'));
+        assert_eq!(mirror_glyph_y(to_cp437('╝')), to_cp437('╗'));
+    }
+    #[test]
+    fn char_conversions() {
+        assert_eq!(glyph_from_char('@'), 64);
+        assert_eq!(glyph_from_char('☺'), 1);
+        assert_eq!(glyph_from_char('█'), 219);
+        assert_eq!(glyph_from_char('語'), FALLBACK_GLYPH);
+        assert_eq!(char_from_glyph(64), '@');
+        assert_eq!(char_from_glyph(219), '█');
+        assert_eq!(char_from_glyph(1000), '?');
+        for c in &['#', '.', '↑', '╬', '♥'] {
+            assert_eq!(char_from_glyph(glyph_from_char(*c)), *c);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::*;
+
+/// Maps keyboard keys to the `Direction` they move towards.
+/// Insert it as a resource and change the bindings to let players configure
+/// their controls.
+/// `Direction` has no diagonals, so only the cardinal keys of the numpad can
+/// be bound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    /// The bound keys.
+    pub directions: HashMap<VirtualKeyCode, Direction>,
+}
+
+impl KeyBindings {
+    /// Creates bindings without any key bound.
+    pub fn empty() -> Self {
+        Self {
+            directions: HashMap::new(),
+        }
+    }
+
+    /// Binds the arrow keys.
+    pub fn with_arrows(self) -> Self {
+        self.with(VirtualKeyCode::Up, Direction::North)
+            .with(VirtualKeyCode::Down, Direction::South)
+            .with(VirtualKeyCode::Right, Direction::East)
+            .with(VirtualKeyCode::Left, Direction::West)
+    }
+
+    /// Binds the W, A, S and D keys.
+    pub fn with_wasd(self) -> Self {
+        self.with(VirtualKeyCode::W, Direction::North)
+            .with(VirtualKeyCode::S, Direction::South)
+            .with(VirtualKeyCode::D, Direction::East)
+            .with(VirtualKeyCode::A, Direction::West)
+    }
+
+    /// Binds the 8, 2, 6 and 4 keys of the numpad.
+    pub fn with_numpad(self) -> Self {
+        self.with(VirtualKeyCode::Numpad8, Direction::North)
+            .with(VirtualKeyCode::Numpad2, Direction::South)
+            .with(VirtualKeyCode::Numpad6, Direction::East)
+            .with(VirtualKeyCode::Numpad4, Direction::West)
+    }
+
+    /// Binds a key, replacing its previous binding.
+    pub fn with(mut self, key: VirtualKeyCode, direction: Direction) -> Self {
+        self.bind(key, direction);
+        self
+    }
+
+    /// Binds a key, replacing its previous binding.
+    pub fn bind(&mut self, key: VirtualKeyCode, direction: Direction) {
+        self.directions.insert(key, direction);
+    }
+
+    /// Removes the binding of a key.
+    pub fn unbind(&mut self, key: VirtualKeyCode) {
+        self.directions.remove(&key);
+    }
+
+    /// Gives the direction bound to a key, or `None` if the key isn't bound.
+    pub fn direction(&self, key: VirtualKeyCode) -> Option<Direction> {
+        self.directions.get(&key).copied()
+    }
+}
+
+impl Default for KeyBindings {
+    /// Binds the arrow keys and WASD.
+    fn default() -> Self {
+        Self::empty().with_arrows().with_wasd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn key_directions() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(
+            bindings.direction(VirtualKeyCode::Up),
+            Some(Direction::North)
+        );
+        assert_eq!(bindings.direction(VirtualKeyCode::A), Some(Direction::West));
+        assert_eq!(bindings.direction(VirtualKeyCode::Numpad6), None);
+        assert_eq!(bindings.direction(VirtualKeyCode::Space), None);
+
+        bindings = bindings.with_numpad();
+        assert_eq!(
+            bindings.direction(VirtualKeyCode::Numpad6),
+            Some(Direction::East)
+        );
+        bindings.bind(VirtualKeyCode::Space, Direction::Down);
+        bindings.unbind(VirtualKeyCode::W);
+        assert_eq!(
+            bindings.direction(VirtualKeyCode::Space),
+            Some(Direction::Down)
+        );
+        assert_eq!(bindings.direction(VirtualKeyCode::W), None);
+    }
+}
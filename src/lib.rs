@@ -8,9 +8,10 @@ pub extern crate hibitset;
 extern crate crossterm;
 
 pub use bracket_lib::prelude::{
-    a_star_search, add_wasm_support, main_loop, to_cp437, BError, BEvent, BTerm, BTermBuilder,
-    BaseMap, GameState, MultiTileSprite, NavigationPath, Point, Rect, SmallVec, SpriteSheet,
-    VirtualKeyCode, BLACK, BLUE, EMBED, GREEN, INPUT, RED, RGBA, WHITE, YELLOW,
+    a_star_search, add_wasm_support, main_loop, to_char, to_cp437, BError, BEvent, BTerm,
+    BTermBuilder, BaseMap, GameState, MultiTileSprite, NavigationPath, Point, Rect, SmallVec,
+    SpriteSheet, VirtualKeyCode, XpCell, XpColor, XpFile, XpLayer, BLACK, BLUE, EMBED, GREEN,
+    INPUT, RED, RGBA, WHITE, YELLOW,
 };
 pub use game_clock::*;
 pub use game_features::*;
@@ -25,16 +26,28 @@ pub use derive_new::*;
 pub use spin_sleep::LoopHelper;
 
 mod components;
+mod glyphs;
+mod input;
 mod macros;
+mod pathfinding;
 mod render;
 mod resources;
+#[cfg(feature = "serde")]
+mod save;
+mod spawn;
 mod systems;
 mod utils;
 
 pub use self::components::*;
+pub use self::glyphs::*;
+pub use self::input::*;
 pub use self::macros::*;
+pub use self::pathfinding::*;
 pub use self::render::*;
 pub use self::resources::*;
+#[cfg(feature = "serde")]
+pub use self::save::*;
+pub use self::spawn::*;
 pub use self::systems::*;
 pub use self::utils::*;
 
@@ -46,6 +59,8 @@ use std::hash::Hash;
 state_machine!(StateMachine; State; world: &mut World, dispatcher: &mut Dispatcher, ctx: &mut BTerm);
 
 /// Runs the engine until the state machine quits.
+/// The `Time` resource is advanced by the real duration of each frame, which the
+/// time based systems read in seconds with `Time::delta_time`.
 pub fn mini_loop<I: State + 'static>(
     world: &mut World,
     dispatcher: &mut Dispatcher,
@@ -0,0 +1,930 @@
+use crate::*;
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Finds a path between two positions relative to the `CollisionMap` using A*.
+/// The path includes both `start` and `goal`.
+/// Returns `None` if no path exists or if a position is outside of the map.
+pub fn find_path(map: &CollisionMap, start: Point, goal: Point) -> Option<Vec<Point>> {
+    let inside = |p: Point| p.x >= 0 && p.y >= 0 && map.contains(p.x as u32, p.y as u32);
+    if !inside(start) || !inside(goal) {
+        return None;
+    }
+    if start == goal {
+        return Some(vec![start]);
+    }
+    let path = a_star_search(
+        map.index_of(start.x as u32, start.y as u32),
+        map.index_of(goal.x as u32, goal.y as u32),
+        map,
+    );
+    if !path.success {
+        return None;
+    }
+    Some(
+        path.steps
+            .into_iter()
+            .map(|idx| {
+                let (x, y) = map.position_of(idx as u32);
+                Point::new(x as i32, y as i32)
+            })
+            .collect(),
+    )
+}
+
+/// Gives the clearance of every tile of the map, in index order: the size of the
+/// largest open square having the tile as its top left corner. Colliding tiles
+/// have a clearance of 0. Wrapping isn't taken into account.
+pub fn clearance_field(map: &CollisionMap) -> Vec<u32> {
+    let (w, h) = map.size();
+    let mut clearance = vec![0; (w * h) as usize];
+    let at = |clearance: &Vec<u32>, x: u32, y: u32| {
+        if x < w && y < h {
+            clearance[(y * w + x) as usize]
+        } else {
+            0
+        }
+    };
+    for y in (0..h).rev() {
+        for x in (0..w).rev() {
+            if !map.is_set(x, y) {
+                let smallest = at(&clearance, x + 1, y)
+                    .min(at(&clearance, x, y + 1))
+                    .min(at(&clearance, x + 1, y + 1));
+                clearance[(y * w + x) as usize] = smallest + 1;
+            }
+        }
+    }
+    clearance
+}
+
+/// The `clearance_field` of a `CollisionMap`, kept between searches along with the
+/// open anchors of each footprint searched with `find_path_clearance`.
+/// Call `update` when the map changes to rebuild it.
+pub struct ClearanceField {
+    map: CollisionMap,
+    clearance: Vec<u32>,
+    anchors: HashMap<(u32, u32), CollisionMap>,
+}
+
+impl ClearanceField {
+    /// Computes the clearance of every tile of the map.
+    pub fn new(map: &CollisionMap) -> Self {
+        ClearanceField {
+            map: map.clone(),
+            clearance: clearance_field(map),
+            anchors: HashMap::new(),
+        }
+    }
+
+    /// Rebuilds the field if the map changed since the last build.
+    /// Returns whether it was rebuilt.
+    pub fn update(&mut self, map: &CollisionMap) -> bool {
+        if self.map == *map {
+            return false;
+        }
+        *self = ClearanceField::new(map);
+        true
+    }
+
+    /// The clearance of every tile, in index order.
+    pub fn clearance(&self) -> &[u32] {
+        &self.clearance
+    }
+
+    /// Whether a footprint of `width` by `height` tiles anchored at `(x, y)` is open.
+    fn fits(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let (w, h) = self.map.size();
+        if x.saturating_add(width) > w || y.saturating_add(height) > h {
+            return false;
+        }
+        // The footprint is covered by squares of `side` tiles along its long axis.
+        let (side, long) = (width.min(height), width.max(height));
+        let mut offset = 0;
+        loop {
+            let offset_clamped = offset.min(long - side);
+            let (sx, sy) = if width >= height {
+                (x + offset_clamped, y)
+            } else {
+                (x, y + offset_clamped)
+            };
+            if self.clearance[(sy * w + sx) as usize] < side {
+                return false;
+            }
+            if offset_clamped == long - side {
+                return true;
+            }
+            offset += side;
+        }
+    }
+
+    /// The map where the tiles that can't anchor the footprint collide.
+    fn anchors(&mut self, width: u32, height: u32) -> &CollisionMap {
+        if !self.anchors.contains_key(&(width, height)) {
+            // Keeps the movement rules and tile costs of the map.
+            let mut anchors = self.map.clone();
+            anchors.clear();
+            let (w, h) = self.map.size();
+            for y in 0..h {
+                for x in 0..w {
+                    if !self.fits(x, y, width, height) {
+                        anchors.set(x, y);
+                    }
+                }
+            }
+            self.anchors.insert((width, height), anchors);
+        }
+        &self.anchors[&(width, height)]
+    }
+}
+
+/// Finds a path for an entity covering `width` by `height` tiles, like a
+/// `MultiSprite`, whose top left corner is at `start`. A tile is only used as a
+/// step if the whole footprint anchored on it is open, which is checked with the
+/// `ClearanceField` of the map. The path is made of the positions of the anchor
+/// and includes both `start` and `goal`.
+/// Sizes of 0 are handled as 1. The footprint doesn't wrap around the map.
+/// Returns `None` if no path exists or if a position is outside of the map.
+pub fn find_path_clearance(
+    field: &mut ClearanceField,
+    start: Point,
+    goal: Point,
+    width: u32,
+    height: u32,
+) -> Option<Vec<Point>> {
+    let (width, height) = (width.max(1), height.max(1));
+    find_path(field.anchors(width, height), start, goal)
+}
+
+/// Removes the waypoints of `path` that can be skipped by walking in a straight line,
+/// leaving only the turning points. The start and the goal are always kept.
+/// Each leg is checked by walking it with `step_towards`, the way `GotoStraight`
+/// moves, so it can follow the remaining waypoints without hitting a wall.
+pub fn simplify_path(map: &CollisionMap, path: &[Point]) -> Vec<Point> {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+    let mut simplified = vec![path[0]];
+    for i in 1..path.len() - 1 {
+        let anchor = *simplified.last().unwrap();
+        if !straight_walkable(map, anchor, path[i + 1]) {
+            simplified.push(path[i]);
+        }
+    }
+    simplified.push(path[path.len() - 1]);
+    simplified
+}
+
+// Checks that walking from `from` to `to` with `step_towards` only crosses open
+// tiles of the map. Those steps are orthogonal, so they don't depend on the
+// diagonal movement of the map.
+fn straight_walkable(map: &CollisionMap, from: Point, to: Point) -> bool {
+    let open = |p: Point| {
+        p.x >= 0
+            && p.y >= 0
+            && map.contains(p.x as u32, p.y as u32)
+            && !map.is_set(p.x as u32, p.y as u32)
+    };
+    let mut current = from;
+    while current != to {
+        current = step_towards(current, to);
+        if !open(current) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Finds the path to the closest goal, using a single Dijkstra search from `start`.
+/// Returns the chosen goal and the path to it, which includes `start` and the goal.
+///
+/// When multiple goals are equally close, the first one in `goals` is chosen.
+/// Goals outside of the map are ignored.
+pub fn find_path_nearest(
+    map: &CollisionMap,
+    start: Point,
+    goals: &[Point],
+) -> Option<(Point, Vec<Point>)> {
+    let (w, h) = map.size();
+    let index = |p: Point| {
+        if p.x >= 0 && p.y >= 0 && map.contains(p.x as u32, p.y as u32) {
+            Some(map.index_of(p.x as u32, p.y as u32) as usize)
+        } else {
+            None
+        }
+    };
+    let start_idx = index(start)?;
+    let goal_ids = goals.iter().map(|g| index(*g)).collect::<Vec<_>>();
+    let goal_order = |idx: usize| goal_ids.iter().position(|g| *g == Some(idx));
+
+    let mut costs = vec![f32::INFINITY; (w * h) as usize];
+    let mut parents = vec![None; (w * h) as usize];
+    let mut open = BinaryHeap::new();
+    costs[start_idx] = 0.0;
+    open.push(OpenTile {
+        cost: 0.0,
+        idx: start_idx,
+    });
+    // (order in goals, tile index, cost)
+    let mut found: Option<(usize, usize, f32)> = None;
+    while let Some(OpenTile { cost, idx }) = open.pop() {
+        if cost > costs[idx] {
+            continue;
+        }
+        if let Some((_, _, found_cost)) = found {
+            if cost > found_cost {
+                break;
+            }
+        }
+        if let Some(order) = goal_order(idx) {
+            if found.map(|(o, _, _)| order < o).unwrap_or(true) {
+                found = Some((order, idx, cost));
+            }
+            continue;
+        }
+        for (n, step) in map.get_available_exits(idx) {
+            let next = cost + step;
+            if next < costs[n] {
+                costs[n] = next;
+                parents[n] = Some(idx);
+                open.push(OpenTile { cost: next, idx: n });
+            }
+        }
+    }
+
+    let (order, mut idx, _) = found?;
+    let mut path = vec![];
+    loop {
+        let (x, y) = map.position_of(idx as u32);
+        path.push(Point::new(x as i32, y as i32));
+        match parents[idx] {
+            Some(parent) => idx = parent,
+            None => break,
+        }
+    }
+    path.reverse();
+    Some((goals[order], path))
+}
+
+/// Gives every tile reachable from `start` for a total cost of at most `budget`,
+/// with the cost to reach it, using a Dijkstra search bounded by the budget.
+/// Costs follow the same rules as pathfinding, including tile costs and diagonal
+/// movement. `start` is always included with a cost of 0, even if it collides.
+/// Returns nothing if `start` is outside of the map.
+pub fn reachable(map: &CollisionMap, start: Point, budget: f32) -> HashMap<Point, f32> {
+    if start.x < 0 || start.y < 0 || !map.contains(start.x as u32, start.y as u32) {
+        return HashMap::new();
+    }
+    let mut costs = HashMap::new();
+    let start_idx = map.index_of(start.x as u32, start.y as u32) as usize;
+    let mut open = BinaryHeap::new();
+    costs.insert(start_idx, 0.0);
+    open.push(OpenTile {
+        cost: 0.0,
+        idx: start_idx,
+    });
+    while let Some(OpenTile { cost, idx }) = open.pop() {
+        if cost > costs[&idx] {
+            continue;
+        }
+        for (n, step) in map.get_available_exits(idx) {
+            let next = cost + step;
+            if next <= budget && next < *costs.get(&n).unwrap_or(&f32::INFINITY) {
+                costs.insert(n, next);
+                open.push(OpenTile { cost: next, idx: n });
+            }
+        }
+    }
+    costs
+        .into_iter()
+        .map(|(idx, cost)| {
+            let (x, y) = map.position_of(idx as u32);
+            (Point::new(x, y), cost)
+        })
+        .collect()
+}
+
+/// Gives the remaining steps of every entity's `AiPath` as world positions, which
+/// is useful to display them when debugging. Failed paths are skipped.
+/// Returns nothing if the world has no `CollisionResource`.
+pub fn collect_paths(world: &World) -> Vec<(Entity, Vec<Point>)> {
+    let (entities, paths, global_map) = match (
+        world.get::<Entities>(),
+        world.get::<Components<AiPath>>(),
+        world.get::<Option<CollisionResource>>(),
+    ) {
+        (Ok(entities), Ok(paths), Ok(global_map)) => (entities, paths, global_map),
+        _ => return vec![],
+    };
+    let global_map = match global_map.as_ref() {
+        Some(global_map) => global_map,
+        None => return vec![],
+    };
+    let mut collected = vec![];
+    for (e, path) in join!(&entities && &paths) {
+        let path = &path.unwrap().path;
+        if !path.success {
+            continue;
+        }
+        let points = path
+            .steps
+            .iter()
+            .map(|idx| {
+                let (x, y) = global_map.map.position_of(*idx as u32);
+                Point::new(
+                    x as i32 + global_map.position.x,
+                    y as i32 + global_map.position.y,
+                )
+            })
+            .collect();
+        collected.push((e.unwrap(), points));
+    }
+    collected
+}
+
+/// Finds a path between two positions relative to the `CollisionMap` using Jump
+/// Point Search, which is much faster than A* on large open maps.
+/// Gives a path as short as the one of `find_path`, in the same format, although
+/// it may take a different route when there are many shortest paths.
+///
+/// Jump Point Search only works on maps where every move costs the same. It falls
+/// back to `find_path` on maps having tile costs, wrapping or
+/// `DiagonalMovement::IfEitherOpen`.
+pub fn find_path_jps(map: &CollisionMap, start: Point, goal: Point) -> Option<Vec<Point>> {
+    let (w, h) = map.size();
+    let inside = |p: Point| p.x >= 0 && p.y >= 0 && map.contains(p.x as u32, p.y as u32);
+    if !inside(start) || !inside(goal) {
+        return None;
+    }
+    if start == goal {
+        return Some(vec![start]);
+    }
+    let diagonal = match map.diagonal_movement() {
+        DiagonalMovement::Never => false,
+        DiagonalMovement::IfBothOpen => true,
+        DiagonalMovement::IfEitherOpen => return find_path(map, start, goal),
+    };
+    if map.wraps() || !map.has_uniform_cost() {
+        return find_path(map, start, goal);
+    }
+    let jps = Jps {
+        map,
+        goal,
+        diagonal,
+    };
+    let index = |p: Point| (p.y as u32 * w + p.x as u32) as usize;
+    let estimate = |p: Point| jps.distance(p, goal);
+    let mut costs = vec![f32::INFINITY; (w * h) as usize];
+    let mut parents = vec![None; (w * h) as usize];
+    let mut closed = vec![false; (w * h) as usize];
+    let mut open = BinaryHeap::new();
+    costs[index(start)] = 0.0;
+    open.push(OpenTile {
+        cost: estimate(start),
+        idx: index(start),
+    });
+    let mut found = false;
+    while let Some(OpenTile { idx, .. }) = open.pop() {
+        if closed[idx] {
+            continue;
+        }
+        closed[idx] = true;
+        let current = Point::new((idx as u32 % w) as i32, (idx as u32 / w) as i32);
+        if current == goal {
+            found = true;
+            break;
+        }
+        for &(dx, dy) in jps.directions() {
+            if let Some(next) = jps.jump(current, dx, dy) {
+                let n = index(next);
+                let cost = costs[idx] + jps.distance(current, next);
+                if !closed[n] && cost < costs[n] {
+                    costs[n] = cost;
+                    parents[n] = Some(current);
+                    open.push(OpenTile {
+                        cost: cost + estimate(next),
+                        idx: n,
+                    });
+                }
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+    // Fills the straight or diagonal lines between the jump points.
+    let mut path = vec![goal];
+    let mut current = goal;
+    while let Some(parent) = parents[index(current)] {
+        let (dx, dy) = (
+            (parent.x - current.x).signum(),
+            (parent.y - current.y).signum(),
+        );
+        while current != parent {
+            current = Point::new(current.x + dx, current.y + dy);
+            path.push(current);
+        }
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// The jumping rules of `find_path_jps`. Diagonal moves never cut corners.
+struct Jps<'a> {
+    map: &'a CollisionMap,
+    goal: Point,
+    diagonal: bool,
+}
+
+impl<'a> Jps<'a> {
+    fn open(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && self.map.contains(x as u32, y as u32)
+            && !self.map.is_set(x as u32, y as u32)
+    }
+
+    fn directions(&self) -> &'static [(i32, i32)] {
+        const DIRECTIONS: [(i32, i32); 8] = [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (-1, 1),
+            (1, -1),
+            (-1, -1),
+        ];
+        if self.diagonal {
+            &DIRECTIONS
+        } else {
+            &DIRECTIONS[..4]
+        }
+    }
+
+    /// The cost of the shortest move between two tiles on an empty map.
+    fn distance(&self, from: Point, to: Point) -> f32 {
+        let (dx, dy) = ((to.x - from.x).abs() as f32, (to.y - from.y).abs() as f32);
+        if self.diagonal {
+            dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+        } else {
+            dx + dy
+        }
+    }
+
+    /// Moves from `from` in the direction until finding the goal or a tile where the
+    /// path may turn. Returns `None` when hitting a wall first.
+    fn jump(&self, from: Point, dx: i32, dy: i32) -> Option<Point> {
+        if dx == 0 || dy == 0 {
+            return self.jump_straight(from, dx, dy);
+        }
+        let (mut x, mut y) = (from.x + dx, from.y + dy);
+        loop {
+            if !self.open(x, y) || !self.open(x - dx, y) || !self.open(x, y - dy) {
+                return None;
+            }
+            let p = Point::new(x, y);
+            if p == self.goal
+                || self.jump_straight(p, dx, 0).is_some()
+                || self.jump_straight(p, 0, dy).is_some()
+            {
+                return Some(p);
+            }
+            x += dx;
+            y += dy;
+        }
+    }
+
+    fn jump_straight(&self, from: Point, dx: i32, dy: i32) -> Option<Point> {
+        let (mut x, mut y) = (from.x + dx, from.y + dy);
+        loop {
+            if !self.open(x, y) {
+                return None;
+            }
+            let p = Point::new(x, y);
+            if p == self.goal {
+                return Some(p);
+            }
+            // A side tile that opens up past a wall can only be reached optimally
+            // by turning here.
+            let forced = if dx != 0 {
+                (self.open(x, y - 1) && !self.open(x - dx, y - 1))
+                    || (self.open(x, y + 1) && !self.open(x - dx, y + 1))
+            } else {
+                (self.open(x - 1, y) && !self.open(x - 1, y - dy))
+                    || (self.open(x + 1, y) && !self.open(x + 1, y - dy))
+            };
+            if forced {
+                return Some(p);
+            }
+            // Without diagonals, vertical moves stop where a horizontal jump succeeds.
+            if !self.diagonal
+                && dy != 0
+                && (self.jump_straight(p, 1, 0).is_some() || self.jump_straight(p, -1, 0).is_some())
+            {
+                return Some(p);
+            }
+            x += dx;
+            y += dy;
+        }
+    }
+}
+
+/// A flow field holding the distance from every tile of a `CollisionMap` to the
+/// closest of a set of goals. Lets many entities step towards the same goals
+/// without running a search each.
+///
+/// Positions are relative to the `CollisionMap`.
+pub struct DijkstraMap {
+    map: CollisionMap,
+    goals: Vec<Point>,
+    distances: Vec<f32>,
+}
+
+impl DijkstraMap {
+    /// Builds the flow field towards the goals. Goals outside of the map are ignored.
+    pub fn new(map: &CollisionMap, goals: &[Point]) -> Self {
+        let mut dijkstra = DijkstraMap {
+            map: map.clone(),
+            goals: goals.to_vec(),
+            distances: vec![],
+        };
+        dijkstra.build();
+        dijkstra
+    }
+
+    /// Rebuilds the flow field if the map or the goals changed since the last build.
+    /// Returns whether it was rebuilt.
+    pub fn update(&mut self, map: &CollisionMap, goals: &[Point]) -> bool {
+        if self.map == *map && self.goals == goals {
+            return false;
+        }
+        self.map = map.clone();
+        self.goals = goals.to_vec();
+        self.build();
+        true
+    }
+
+    /// The cost to reach the closest goal from this position, if it is reachable.
+    pub fn distance(&self, p: Point) -> Option<f32> {
+        self.index(p)
+            .map(|idx| self.distances[idx])
+            .filter(|d| d.is_finite())
+    }
+
+    /// The neighbor to move to in order to get closer to a goal.
+    /// Returns `None` when already on a goal or if no goal can be reached.
+    pub fn next_step(&self, from: Point) -> Option<Point> {
+        let idx = self.index(from)?;
+        let mut best = (idx, self.distances[idx]);
+        for (n, _) in self.map.get_available_exits(idx) {
+            if self.distances[n] < best.1 {
+                best = (n, self.distances[n]);
+            }
+        }
+        if best.0 == idx {
+            return None;
+        }
+        let (x, y) = self.map.position_of(best.0 as u32);
+        Some(Point::new(x as i32, y as i32))
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        if p.x < 0 || p.y < 0 || !self.map.contains(p.x as u32, p.y as u32) {
+            return None;
+        }
+        Some(self.map.index_of(p.x as u32, p.y as u32) as usize)
+    }
+
+    fn build(&mut self) {
+        let (w, h) = self.map.size();
+        self.distances = vec![f32::INFINITY; (w * h) as usize];
+        let mut open = BinaryHeap::new();
+        for goal in self.goals.iter() {
+            if let Some(idx) = self.index(*goal) {
+                self.distances[idx] = 0.0;
+                open.push(OpenTile { cost: 0.0, idx });
+            }
+        }
+        while let Some(OpenTile { cost, idx }) = open.pop() {
+            if cost > self.distances[idx] {
+                continue;
+            }
+            // We want the cost of moving from the neighbor towards this tile,
+            // which can differ from the opposite direction with weighted tiles.
+            for (n, _) in self.map.get_available_exits(idx) {
+                let step = self
+                    .map
+                    .get_available_exits(n)
+                    .into_iter()
+                    .find(|(to, _)| *to == idx)
+                    .map(|(_, c)| c);
+                if let Some(step) = step {
+                    let next = cost + step;
+                    if next < self.distances[n] {
+                        self.distances[n] = next;
+                        open.push(OpenTile { cost: next, idx: n });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A tile waiting to be expanded, ordered so the `BinaryHeap` pops the cheapest first.
+struct OpenTile {
+    cost: f32,
+    idx: usize,
+}
+
+impl PartialEq for OpenTile {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenTile {}
+
+impl PartialOrd for OpenTile {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenTile {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.idx.cmp(&self.idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn path_clear() {
+        let map = CollisionMap::new(5, 5);
+        let path = find_path(&map, Point::new(0, 0), Point::new(2, 0)).unwrap();
+        assert_eq!(
+            path,
+            vec![Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)]
+        );
+    }
+    #[test]
+    fn path_blocked() {
+        let mut map = CollisionMap::new(5, 5);
+        for y in 0..5 {
+            map.set(2, y);
+        }
+        assert_eq!(find_path(&map, Point::new(0, 0), Point::new(4, 0)), None);
+        assert_eq!(find_path(&map, Point::new(0, 0), Point::new(9, 0)), None);
+    }
+    #[test]
+    fn path_same_tile() {
+        let map = CollisionMap::new(5, 5);
+        assert_eq!(
+            find_path(&map, Point::new(3, 3), Point::new(3, 3)),
+            Some(vec![Point::new(3, 3)])
+        );
+    }
+    #[test]
+    fn path_nearest() {
+        let mut map = CollisionMap::new(7, 3);
+        map.set(2, 0);
+        map.set(2, 1);
+        let goals = [Point::new(0, 0), Point::new(5, 0), Point::new(6, 2)];
+        let (goal, path) = find_path_nearest(&map, Point::new(3, 0), &goals).unwrap();
+        assert_eq!(goal, Point::new(5, 0));
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[0], Point::new(3, 0));
+
+        // Both goals are 2 steps away, the first one wins.
+        let goals = [Point::new(3, 2), Point::new(5, 0)];
+        let (goal, _) = find_path_nearest(&map, Point::new(3, 0), &goals).unwrap();
+        assert_eq!(goal, Point::new(3, 2));
+        let goals = [Point::new(5, 0), Point::new(3, 2)];
+        let (goal, _) = find_path_nearest(&map, Point::new(3, 0), &goals).unwrap();
+        assert_eq!(goal, Point::new(5, 0));
+
+        let (goal, path) = find_path_nearest(&map, Point::new(3, 0), &[Point::new(3, 0)]).unwrap();
+        assert_eq!((goal, path), (Point::new(3, 0), vec![Point::new(3, 0)]));
+        assert!(find_path_nearest(&map, Point::new(3, 0), &[Point::new(2, 0)]).is_none());
+    }
+    #[test]
+    fn clearance_corridor() {
+        let map = CollisionMap::from_str_layout(
+            "\
+......
+......
+###.##
+......
+......",
+            '#',
+        )
+        .unwrap();
+        assert_eq!(&clearance_field(&map)[0..6], &[2, 2, 2, 2, 2, 1]);
+        assert_eq!(clearance_field(&map)[15], 1);
+        let (start, goal) = (Point::new(0, 0), Point::new(0, 3));
+        let mut field = ClearanceField::new(&map);
+        assert_eq!(field.clearance(), &clearance_field(&map)[..]);
+        // The corridor is one tile wide.
+        assert_eq!(find_path_clearance(&mut field, start, goal, 2, 2), None);
+        assert_eq!(find_path_clearance(&mut field, start, goal, 2, 1), None);
+        let path = find_path_clearance(&mut field, start, goal, 1, 2).unwrap();
+        assert!(path.contains(&Point::new(3, 1)) && path.contains(&Point::new(3, 2)));
+        assert_eq!(
+            find_path_clearance(&mut field, start, goal, 1, 1).map(|p| p.len()),
+            find_path(&map, start, goal).map(|p| p.len())
+        );
+        // Huge footprints don't overflow.
+        assert_eq!(
+            find_path_clearance(&mut field, start, goal, u32::MAX, 1),
+            None
+        );
+
+        let mut wide = map.clone();
+        wide.unset(4, 2);
+        assert!(!field.update(&map));
+        assert!(field.update(&wide));
+        let path = find_path_clearance(&mut field, start, goal, 2, 2).unwrap();
+        assert!(path.contains(&Point::new(3, 2)));
+        assert_eq!(
+            find_path_clearance(&mut field, start, Point::new(5, 3), 2, 2),
+            None
+        );
+    }
+    #[test]
+    fn simplify() {
+        let map = CollisionMap::from_str_layout(
+            "......\n\
+             ####..\n\
+             ......",
+            '#',
+        )
+        .unwrap();
+        let path = find_path(&map, Point::new(0, 0), Point::new(0, 2)).unwrap();
+        let simplified = simplify_path(&map, &path);
+        assert_eq!(simplified.first(), Some(&Point::new(0, 0)));
+        assert_eq!(simplified.last(), Some(&Point::new(0, 2)));
+        assert!(simplified.len() < path.len());
+        for pair in simplified.windows(2) {
+            assert!(super::straight_walkable(&map, pair[0], pair[1]));
+        }
+
+        let straight = find_path(&map, Point::new(0, 0), Point::new(5, 0)).unwrap();
+        assert_eq!(
+            simplify_path(&map, &straight),
+            vec![Point::new(0, 0), Point::new(5, 0)]
+        );
+        assert_eq!(simplify_path(&map, &straight[..2]), straight[..2].to_vec());
+        assert!(simplify_path(&map, &[]).is_empty());
+
+        // Bresenham sees from (0, 0) to (4, 2), but walking there goes through (2, 0).
+        let map = CollisionMap::from_str_layout(
+            "..#..\n\
+             .....\n\
+             .....",
+            '#',
+        )
+        .unwrap();
+        assert!(map.has_line_of_sight(Point::new(0, 0), Point::new(4, 2)));
+        let path = find_path(&map, Point::new(0, 0), Point::new(4, 2)).unwrap();
+        let simplified = simplify_path(&map, &path);
+        assert!(simplified.len() > 2);
+        for pair in simplified.windows(2) {
+            let mut p = pair[0];
+            while p != pair[1] {
+                p = step_towards(p, pair[1]);
+                assert!(!map.is_set(p.x as u32, p.y as u32));
+            }
+        }
+
+        // No cutting the corner of a wall on an orthogonal map.
+        let map = CollisionMap::from_str_layout(".#\n..", '#').unwrap();
+        assert!(map.has_line_of_sight(Point::new(0, 0), Point::new(1, 1)));
+        let corner = [Point::new(0, 0), Point::new(0, 1), Point::new(1, 1)];
+        assert_eq!(simplify_path(&map, &corner), corner.to_vec());
+    }
+    #[test]
+    fn reachable_budget() {
+        let mut map = CollisionMap::from_str_layout("..#.\n....\n....", '#').unwrap();
+        map.set_cost(1, 1, 3.0);
+        let expected = |tiles: &[(i32, i32, f32)]| {
+            tiles
+                .iter()
+                .map(|(x, y, c)| (Point::new(*x, *y), *c))
+                .collect::<HashMap<_, _>>()
+        };
+        let start = Point::new(0, 0);
+        assert_eq!(
+            reachable(&map, start, 3.0),
+            expected(&[
+                (0, 0, 0.0),
+                (1, 0, 1.0),
+                (0, 1, 1.0),
+                (0, 2, 2.0),
+                (1, 2, 3.0)
+            ])
+        );
+        let more = reachable(&map, start, 4.0);
+        assert_eq!(more.len(), 7);
+        assert_eq!(more[&Point::new(1, 1)], 4.0);
+        assert_eq!(more[&Point::new(2, 2)], 4.0);
+        assert_eq!(reachable(&map, start, 0.0), expected(&[(0, 0, 0.0)]));
+        assert!(reachable(&map, Point::new(-1, 0), 5.0).is_empty());
+    }
+    #[test]
+    fn jps_optimal() {
+        let mut map = CollisionMap::from_str_layout(
+            "\
+..........
+.####.###.
+.#......#.
+.#.####.#.
+...#..#...
+.#.#..###.
+.#........",
+            '#',
+        )
+        .unwrap();
+        let pairs = [
+            (Point::new(0, 0), Point::new(4, 4)),
+            (Point::new(9, 6), Point::new(2, 2)),
+            (Point::new(5, 5), Point::new(0, 6)),
+        ];
+        for diagonal in &[DiagonalMovement::Never, DiagonalMovement::IfBothOpen] {
+            map.set_diagonal_movement(*diagonal);
+            map.set_heuristic(Heuristic::Chebyshev);
+            for (start, goal) in pairs.iter() {
+                let a_star = find_path(&map, *start, *goal).unwrap();
+                let jps = find_path_jps(&map, *start, *goal).unwrap();
+                assert_eq!(jps.len(), a_star.len());
+                assert_eq!((jps[0], jps[jps.len() - 1]), (*start, *goal));
+                assert!(jps.iter().all(|p| !map.is_set(p.x as u32, p.y as u32)));
+            }
+        }
+        map.set(0, 1);
+        map.set(1, 0);
+        assert_eq!(
+            find_path_jps(&map, Point::new(0, 0), Point::new(9, 6)),
+            None
+        );
+        assert_eq!(
+            find_path_jps(&map, Point::new(4, 4), Point::new(4, 4)),
+            Some(vec![Point::new(4, 4)])
+        );
+    }
+    #[test]
+    fn collect_world_paths() {
+        let mut world = World::default();
+        world.initialize::<Entities>();
+        world.initialize::<Components<AiPath>>();
+        assert!(collect_paths(&world).is_empty());
+        world.initialize::<Option<CollisionResource>>();
+        *world.get_mut::<Option<CollisionResource>>().unwrap() = Some(CollisionResource::new(
+            CollisionMap::new(5, 5),
+            Point::new(10, 20),
+        ));
+
+        let e = world.get_mut::<Entities>().unwrap().create();
+        let map = CollisionMap::new(5, 5);
+        let path = a_star_search(map.index_of(0, 0), map.index_of(0, 2), &map);
+        world
+            .get_mut::<Components<AiPath>>()
+            .unwrap()
+            .insert(e, AiPath::new(path));
+        assert_eq!(
+            collect_paths(&world),
+            vec![(
+                e,
+                vec![Point::new(10, 20), Point::new(10, 21), Point::new(10, 22)]
+            )]
+        );
+    }
+    #[test]
+    fn dijkstra_flow() {
+        let map = CollisionMap::from_str_layout(
+            "\
+.....
+.###.
+...#.",
+            '#',
+        )
+        .unwrap();
+        let mut dijkstra = DijkstraMap::new(&map, &[Point::new(0, 2)]);
+        assert_eq!(dijkstra.distance(Point::new(0, 2)), Some(0.0));
+        assert_eq!(dijkstra.distance(Point::new(4, 2)), Some(8.0));
+        assert_eq!(dijkstra.distance(Point::new(2, 1)), None);
+        assert_eq!(dijkstra.next_step(Point::new(0, 2)), None);
+        assert_eq!(dijkstra.next_step(Point::new(4, 2)), Some(Point::new(4, 1)));
+        assert_eq!(dijkstra.next_step(Point::new(2, 2)), Some(Point::new(1, 2)));
+
+        assert!(!dijkstra.update(&map, &[Point::new(0, 2)]));
+        assert!(dijkstra.update(&map, &[Point::new(4, 2)]));
+        assert_eq!(dijkstra.next_step(Point::new(4, 0)), Some(Point::new(4, 1)));
+    }
+}
@@ -1,64 +1,916 @@
 use crate::*;
 
-/// Renders ascii characters.
-pub fn render_ascii<'a>(
+enum AsciiDrawable<'a> {
+    Multi(&'a MultiSprite),
+    Single(Sprite),
+}
+
+/// The tile at which an entity is drawn in ascii, taking its `RenderOffset` into
+/// account. Ascii consoles can only draw on whole tiles, so the offset is rounded.
+fn draw_position(pos: &Point, offset: Option<&RenderOffset>) -> Point {
+    match offset {
+        Some(offset) => Point::new(
+            pos.x + offset.x.round() as i32,
+            pos.y + offset.y.round() as i32,
+        ),
+        None => *pos,
+    }
+}
+
+/// What `render_ascii_with`, `render_ascii_cached` and `render_sprites_with` draw.
+/// Only the camera, entities and positions are required. The sprites, other
+/// components and resources are added with the `with_` methods: `with_sprites` and
+/// `with_multi_sprites` for ascii rendering, `with_sprite_indices` for sprite
+/// rendering.
+pub struct RenderParams<'a> {
+    camera: &'a Camera,
+    // Only `None` for `render_ascii` and `render_sprites`, which don't get them.
+    entities: Option<&'a Entities>,
+    positions: &'a Components<Point>,
+    multi_sprites: Option<&'a Components<MultiSprite>>,
+    sprites: Option<&'a Components<Sprite>>,
+    #[cfg(feature = "opengl")]
+    sprite_indices: Option<&'a Components<SpriteIndex>>,
+    #[cfg(feature = "opengl")]
+    tile_size: Point,
+    layers: Option<&'a Components<Layer>>,
+    offsets: Option<&'a Components<RenderOffset>>,
+    tints: Option<&'a Components<Tint>>,
+    transforms: Option<&'a Components<GlyphTransform>>,
+    blinks: Option<&'a Components<Blink>>,
+    remembered: Option<&'a Components<Remembered>>,
+    light_map: Option<&'a LightMap>,
+    highlights: Option<&'a HighlightOverlay>,
+    visible: Option<&'a VisibleSet>,
+    background: RGBA,
+    frame: u64,
+}
+
+impl<'a> RenderParams<'a> {
+    /// Draws the entities seen by the camera.
+    pub fn new(
+        camera: &'a Camera,
+        entities: &'a Entities,
+        positions: &'a Components<Point>,
+    ) -> Self {
+        Self::from_parts(camera, Some(entities), positions)
+    }
+
+    fn from_parts(
+        camera: &'a Camera,
+        entities: Option<&'a Entities>,
+        positions: &'a Components<Point>,
+    ) -> Self {
+        Self {
+            camera,
+            entities,
+            positions,
+            multi_sprites: None,
+            sprites: None,
+            #[cfg(feature = "opengl")]
+            sprite_indices: None,
+            #[cfg(feature = "opengl")]
+            tile_size: Point::new(1, 1),
+            layers: None,
+            offsets: None,
+            tints: None,
+            transforms: None,
+            blinks: None,
+            remembered: None,
+            light_map: None,
+            highlights: None,
+            visible: None,
+            background: RGBA::named(BLACK),
+            frame: 0,
+        }
+    }
+
+    /// Draws the single tile `Sprite`s in ascii.
+    pub fn with_sprites(mut self, sprites: &'a Components<Sprite>) -> Self {
+        self.sprites = Some(sprites);
+        self
+    }
+
+    /// Draws the `MultiSprite`s in ascii.
+    pub fn with_multi_sprites(mut self, multi_sprites: &'a Components<MultiSprite>) -> Self {
+        self.multi_sprites = Some(multi_sprites);
+        self
+    }
+
+    /// Draws the `SpriteIndex`s of the sprite sheet, `tile_size` being the size of a
+    /// tile of the sprite console in pixels.
+    /// Only available using the `opengl` feature.
+    #[cfg(feature = "opengl")]
+    pub fn with_sprite_indices(
+        mut self,
+        sprite_indices: &'a Components<SpriteIndex>,
+        tile_size: Point,
+    ) -> Self {
+        self.sprite_indices = Some(sprite_indices);
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// Sorts the sprites by their `Layer`.
+    pub fn with_layers(mut self, layers: &'a Components<Layer>) -> Self {
+        self.layers = Some(layers);
+        self
+    }
+
+    /// Draws entities away from their position by their `RenderOffset`.
+    pub fn with_offsets(mut self, offsets: &'a Components<RenderOffset>) -> Self {
+        self.offsets = Some(offsets);
+        self
+    }
+
+    /// Multiplies the colors of entities by their `Tint`.
+    pub fn with_tints(mut self, tints: &'a Components<Tint>) -> Self {
+        self.tints = Some(tints);
+        self
+    }
+
+    /// Applies the `GlyphTransform` of entities having a single tile `Sprite`.
+    pub fn with_transforms(mut self, transforms: &'a Components<GlyphTransform>) -> Self {
+        self.transforms = Some(transforms);
+        self
+    }
+
+    /// Skips the entities having a `Blink` hidden during `frame`, which is usually
+    /// `Time::frame_number`.
+    pub fn with_blinks(mut self, blinks: &'a Components<Blink>, frame: u64) -> Self {
+        self.blinks = Some(blinks);
+        self.frame = frame;
+        self
+    }
+
+    /// Draws the entities having `Remembered` where they were last seen, when
+    /// rendering with a `VisibleSet`.
+    pub fn with_remembered(mut self, remembered: &'a Components<Remembered>) -> Self {
+        self.remembered = Some(remembered);
+        self
+    }
+
+    /// Multiplies the colors by the light level of each tile.
+    pub fn with_light_map(mut self, light_map: &'a LightMap) -> Self {
+        self.light_map = Some(light_map);
+        self
+    }
+
+    /// Blends the colors of the overlay onto the background of the highlighted
+    /// tiles.
+    pub fn with_highlights(mut self, highlights: &'a HighlightOverlay) -> Self {
+        self.highlights = Some(highlights);
+        self
+    }
+
+    /// Skips the sprites on the tiles that aren't visible.
+    pub fn with_visible(mut self, visible: &'a VisibleSet) -> Self {
+        self.visible = Some(visible);
+        self
+    }
+
+    /// The background already on the console, usually the color it was cleared
+    /// with. It is drawn under the cells that have no background, as the console
+    /// can't set a glyph without its background. Black by default.
+    pub fn with_background(mut self, background: RGBA) -> Self {
+        self.background = background;
+        self
+    }
+}
+
+/// The component of `e` in an optional storage.
+fn component<T>(storage: Option<&Components<T>>, e: Option<Entity>) -> Option<&T> {
+    storage.zip(e).and_then(|(s, e)| s.get(e))
+}
+
+/// The position and component of each entity having both, along with the entity
+/// when the `RenderParams` know them.
+fn drawables<'b, T>(
+    params: &RenderParams<'b>,
+    storage: Option<&'b Components<T>>,
+) -> Vec<(Option<Entity>, &'b Point, &'b T)> {
+    let (positions, mut drawables) = (params.positions, vec![]);
+    let storage = match storage {
+        Some(storage) => storage,
+        None => return drawables,
+    };
+    match params.entities {
+        Some(entities) => {
+            for (e, pos, item) in join!(&entities && &positions && &storage) {
+                drawables.push((e, pos.unwrap(), item.unwrap()));
+            }
+        }
+        None => {
+            for (pos, item) in join!(&positions && &storage) {
+                drawables.push((None, pos.unwrap(), item.unwrap()));
+            }
+        }
+    }
+    drawables
+}
+
+/// Renders the ascii `Sprite`s and `MultiSprite`s seen by the camera.
+/// Without the entities, none of the `RenderParams` options apply, not even the
+/// `Layer`s: use `render_ascii_with` for them.
+pub fn render_ascii(
     ctx: &mut BTerm,
     camera: &Camera,
     positions: &Components<Point>,
     multi_sprites: &Components<MultiSprite>,
     sprites: &Components<Sprite>,
 ) {
+    let params = RenderParams::from_parts(camera, None, positions)
+        .with_multi_sprites(multi_sprites)
+        .with_sprites(sprites);
+    render_ascii_with(ctx, &params);
+}
+
+/// Renders ascii characters, sorted by their `Layer`.
+/// The `Camera::shake` offset is applied.
+/// Entities having a `RenderOffset` are drawn away from their position, snapped to
+/// the nearest tile.
+/// The transparent tiles of a `MultiSprite` aren't drawn.
+/// Sprites without a background let the background drawn below them show, or the
+/// `RenderParams` background where nothing below has one.
+/// Entities having a `Tint` get their colors multiplied by it.
+/// The `GlyphTransform` of entities having a single tile `Sprite` is applied.
+/// With a `LightMap`, the colors are multiplied by the light level of each tile.
+/// With a `HighlightOverlay`, its colors are blended onto the background of the
+/// highlighted tiles, between the sprites of the layers below and above its own.
+/// Entities having a `Blink` are skipped when hidden.
+/// With a `VisibleSet`, the sprites on the tiles that aren't visible are skipped.
+/// Entities having `Remembered` are instead drawn dimmed where they were last seen,
+/// as long as that tile isn't visible.
+/// See `RenderParams` to choose which of those are used.
+pub fn render_ascii_with(ctx: &mut BTerm, params: &RenderParams) {
+    #[cfg(not(feature = "headless"))]
+    {
+        for (pos, cell) in ascii_cells(params) {
+            draw_cell(ctx, pos, &cell, params.background);
+        }
+    }
+}
+
+/// Same as `render_ascii_with`, but only draws the cells that changed since the last
+/// frame drawn with this `RenderCache`. Cells that are no longer covered are
+/// erased.
+/// Made for mostly static scenes: the console must not be cleared between frames.
+/// Call `RenderCache::invalidate` if it was, to redraw everything.
+pub fn render_ascii_cached(ctx: &mut BTerm, cache: &mut RenderCache, params: &RenderParams) {
     #[cfg(not(feature = "headless"))]
     {
-        for (pos, sprite) in join!(&positions && &multi_sprites) {
-            sprite.unwrap().tile.render(
-                ctx,
-                Point::new(
-                    pos.unwrap().x - camera.position.x,
-                    pos.unwrap().y - camera.position.y,
+        for (pos, cell) in cache.update(ascii_cells(params)) {
+            match cell {
+                Some(cell) => draw_cell(ctx, pos, &cell, params.background),
+                None => ctx.set(
+                    pos.x,
+                    pos.y,
+                    params.background,
+                    params.background,
+                    to_cp437(' '),
                 ),
-            );
+            }
         }
-        for (pos, sprite) in join!(&positions && &sprites) {
-            let pos = pos.unwrap();
-            let sprite = sprite.unwrap();
-            ctx.set(
-                pos.x - camera.position.x,
-                pos.y - camera.position.y,
-                sprite.fg,
-                sprite.bg,
-                sprite.glyph,
-            );
+    }
+}
+
+#[cfg(not(feature = "headless"))]
+fn draw_cell(ctx: &mut BTerm, pos: Point, cell: &Sprite, background: RGBA) {
+    ctx.set(
+        pos.x,
+        pos.y,
+        cell.fg,
+        cell.bg.unwrap_or(background),
+        cell.glyph,
+    );
+}
+
+/// Gives the cells to draw on screen with their screen position.
+/// Entities outside of the view of the camera are skipped.
+fn ascii_cells(params: &RenderParams) -> Vec<(Point, Sprite)> {
+    let camera = &params.camera.render_view();
+    let (light_map, visible) = (params.light_map, params.visible);
+    let layer_of = |e: Option<Entity>| component(params.layers, e).map(|l| l.0).unwrap_or(0);
+    let hidden = |e: Option<Entity>| {
+        component(params.blinks, e)
+            .map(|b| !b.is_visible(params.frame))
+            .unwrap_or(false)
+    };
+    // Where the entity was last seen, only when rendering with a `VisibleSet`.
+    let memory = |e: Option<Entity>| {
+        visible?;
+        let memory = component(params.remembered, e)?;
+        Some((memory.last_seen?, memory))
+    };
+    let mut draws = vec![];
+    for (e, pos, sprite) in drawables(params, params.multi_sprites) {
+        if let Some((last_seen, memory)) = memory(e) {
+            let remembered = match memory.marker {
+                // Drawn with the single tile sprite of the entity.
+                Some(_) if component(params.sprites, e).is_some() => None,
+                Some(marker) => Some((AsciiDrawable::Single(marker), 1, 1)),
+                None => Some((AsciiDrawable::Multi(sprite), sprite.width, sprite.height)),
+            };
+            if let Some((drawable, _, _)) = remembered
+                .filter(|(_, width, height)| camera.is_area_visible(last_seen, *width, *height))
+            {
+                draws.push((
+                    layer_of(e),
+                    last_seen,
+                    drawable,
+                    component(params.tints, e),
+                    Some(memory.dim),
+                ));
+            }
+        }
+        let pos = draw_position(pos, component(params.offsets, e));
+        if hidden(e) || !camera.is_area_visible(pos, sprite.width, sprite.height) {
+            continue;
+        }
+        draws.push((
+            layer_of(e),
+            pos,
+            AsciiDrawable::Multi(sprite),
+            component(params.tints, e),
+            None,
+        ));
+    }
+    for (e, pos, sprite) in drawables(params, params.sprites) {
+        let mut sprite = *sprite;
+        if let Some(transform) = component(params.transforms, e) {
+            sprite.glyph = transform.apply(sprite.glyph);
+        }
+        if let Some((last_seen, memory)) = memory(e) {
+            if camera.is_area_visible(last_seen, 1, 1) {
+                let drawable = AsciiDrawable::Single(memory.marker.unwrap_or(sprite));
+                draws.push((
+                    layer_of(e),
+                    last_seen,
+                    drawable,
+                    component(params.tints, e),
+                    Some(memory.dim),
+                ));
+            }
+        }
+        let pos = draw_position(pos, component(params.offsets, e));
+        if hidden(e) || !camera.is_area_visible(pos, 1, 1) {
+            continue;
+        }
+        draws.push((
+            layer_of(e),
+            pos,
+            AsciiDrawable::Single(sprite),
+            component(params.tints, e),
+            None,
+        ));
+    }
+    // Remembered entities show on the tiles that aren't visible, the others on the
+    // visible ones.
+    let shown = |world: Point, dim: Option<f32>| {
+        visible
+            .map(|v| v.is_visible(&world) != dim.is_some())
+            .unwrap_or(true)
+    };
+    // Stable sort, multi sprites stay under sprites of the same layer.
+    draws.sort_by_key(|d| d.0);
+    let mut tiles = vec![];
+    let mut highlights = params.highlights;
+    for (layer, pos, drawable, tint, dim) in draws {
+        if let Some(overlay) = highlights.filter(|o| layer > o.layer) {
+            tiles = highlighted(compose_cells(tiles), camera, overlay);
+            highlights = None;
+        }
+        match drawable {
+            AsciiDrawable::Multi(sprite) => {
+                for (i, tile) in sprite.tiles.iter().enumerate() {
+                    if tile.glyph == sprite.transparent_glyph {
+                        continue;
+                    }
+                    let (x, y) = (i as u32 % sprite.width, i as u32 / sprite.width);
+                    let world = Point::new(pos.x + x as i32, pos.y + y as i32);
+                    if !shown(world, dim) {
+                        continue;
+                    }
+                    let cell = dimmed(lit(tinted(*tile, tint), light_map, world), dim);
+                    for screen in camera.tile_to_screen(world) {
+                        tiles.push((screen, cell));
+                    }
+                }
+            }
+            AsciiDrawable::Single(sprite) => {
+                if !shown(pos, dim) {
+                    continue;
+                }
+                let cell = dimmed(lit(tinted(sprite, tint), light_map, pos), dim);
+                for screen in camera.tile_to_screen(pos) {
+                    tiles.push((screen, cell));
+                }
+            }
+        }
+    }
+    if let Some(overlay) = highlights {
+        tiles = highlighted(compose_cells(tiles), camera, overlay);
+    }
+    // Tiles are already in screen space, so zoomed out tiles sharing a cell are
+    // composed like stacked ones.
+    compose_cells(tiles)
+}
+
+/// Blends the colors of the `HighlightOverlay` onto the background of the composed
+/// cells, by the alpha of each color. Highlighted cells without a sprite get the
+/// color as background.
+fn highlighted(
+    mut cells: Vec<(Point, Sprite)>,
+    camera: &Camera,
+    overlay: &HighlightOverlay,
+) -> Vec<(Point, Sprite)> {
+    let mut indices = HashMap::<Point, usize>::new();
+    for (i, (pos, _)) in cells.iter().enumerate() {
+        indices.insert(*pos, i);
+    }
+    for (tile, color) in overlay.tiles.iter() {
+        for screen in camera.tile_to_screen(*tile) {
+            if let Some(i) = indices.get(&screen) {
+                let cell = &mut cells[*i].1;
+                cell.bg = Some(match cell.bg {
+                    Some(bg) => lerp_rgba(bg, RGBA { a: bg.a, ..*color }, color.a),
+                    None => *color,
+                });
+            } else {
+                cells.push((
+                    screen,
+                    Sprite {
+                        glyph: to_cp437(' '),
+                        fg: *color,
+                        bg: Some(*color),
+                    },
+                ));
+            }
+        }
+    }
+    cells
+}
+
+/// Multiplies the colors of the sprite by the light level of its tile, if there is
+/// a `LightMap`. The alpha is kept.
+fn lit(sprite: Sprite, light_map: Option<&LightMap>, pos: Point) -> Sprite {
+    dimmed(sprite, light_map.map(|l| l.level(pos)))
+}
+
+/// Multiplies the colors of the sprite by the level, if any. The alpha is kept.
+fn dimmed(sprite: Sprite, level: Option<f32>) -> Sprite {
+    let level = match level {
+        Some(level) => level,
+        None => return sprite,
+    };
+    let darken = |c: RGBA| RGBA::from_f32(c.r * level, c.g * level, c.b * level, c.a);
+    Sprite {
+        glyph: sprite.glyph,
+        fg: darken(sprite.fg),
+        bg: sprite.bg.map(darken),
+    }
+}
+
+/// Multiplies the colors of the sprite by the tint, if any.
+fn tinted(sprite: Sprite, tint: Option<&Tint>) -> Sprite {
+    let tint = match tint {
+        Some(tint) => tint.0,
+        None => return sprite,
+    };
+    let multiply = |c: RGBA| {
+        RGBA::from_f32(
+            (c.r * tint.r).clamp(0.0, 1.0),
+            (c.g * tint.g).clamp(0.0, 1.0),
+            (c.b * tint.b).clamp(0.0, 1.0),
+            (c.a * tint.a).clamp(0.0, 1.0),
+        )
+    };
+    Sprite {
+        glyph: sprite.glyph,
+        fg: multiply(sprite.fg),
+        bg: sprite.bg.map(multiply),
+    }
+}
+
+/// Merges the sprites, given from the bottom to the top, into the cells to draw.
+/// Each cell shows its top sprite. Sprites without a background keep the one of
+/// the sprite below them, or a transparent one.
+fn compose_cells(sprites: Vec<(Point, Sprite)>) -> Vec<(Point, Sprite)> {
+    let mut cells: Vec<(Point, Sprite)> = vec![];
+    let mut indices = HashMap::<Point, usize>::new();
+    for (pos, sprite) in sprites {
+        if let Some(i) = indices.get(&pos) {
+            let cell = &mut cells[*i].1;
+            let bg = sprite.bg.or(cell.bg);
+            *cell = sprite;
+            cell.bg = bg;
+        } else {
+            indices.insert(pos, cells.len());
+            cells.push((pos, sprite));
         }
     }
+    cells
 }
 
-/// Renders 2d tile sprites characters.
+/// The pixel area covered by an entity's sprite, or `None` when it is outside of
+/// the camera. `tile_size` is the size of a tile in pixels. The `RenderOffset` isn't
+/// rounded, so that sprites move smoothly between tiles.
+#[cfg(any(feature = "opengl", test))]
+fn sprite_rect(
+    camera: &Camera,
+    pos: &Point,
+    offset: Option<&RenderOffset>,
+    tile_size: Point,
+) -> Option<Rect> {
+    let (offset_x, offset_y) = offset.map(|o| (o.x, o.y)).unwrap_or((0.0, 0.0));
+    let scale = camera.scale();
+    let width = (tile_size.x as f32 * scale).ceil().max(1.0) as i32;
+    let height = (tile_size.y as f32 * scale).ceil().max(1.0) as i32;
+    let x = (((pos.x - camera.position.x) as f32 + offset_x) * scale * tile_size.x as f32).round()
+        as i32;
+    let y = (((pos.y - camera.position.y) as f32 + offset_y) * scale * tile_size.y as f32).round()
+        as i32;
+    if x + width <= 0
+        || y + height <= 0
+        || x >= camera.size.x * tile_size.x
+        || y >= camera.size.y * tile_size.y
+    {
+        return None;
+    }
+    Some(Rect::with_size(x, y, width, height))
+}
+
+/// Renders 2d tile sprites characters, with tiles of 1 by 1 pixels.
+/// Without the entities, none of the `RenderParams` options apply: use
+/// `render_sprites_with` for them and to set the tile size.
 /// Only available using the `opengl` feature.
 #[cfg(feature = "opengl")]
-pub fn render_sprites<'a>(
+pub fn render_sprites(
     ctx: &mut BTerm,
     camera: &Camera,
     positions: &Components<Point>,
     sprites: &Components<SpriteIndex>,
 ) {
+    let params = RenderParams::from_parts(camera, None, positions)
+        .with_sprite_indices(sprites, Point::new(1, 1));
+    render_sprites_with(ctx, &params);
+}
+
+/// Renders the `SpriteIndex`s given to `RenderParams::with_sprite_indices`, sorted
+/// by their `Layer`.
+/// Entities having a `RenderOffset` are drawn away from their position, in
+/// fractions of tiles.
+/// Entities having a `Blink` are skipped when hidden.
+/// The `Camera::shake` offset is applied.
+/// Only available using the `opengl` feature.
+#[cfg(feature = "opengl")]
+pub fn render_sprites_with(ctx: &mut BTerm, params: &RenderParams) {
     #[cfg(not(feature = "headless"))]
     {
-        for (pos, sprite) in join!(&positions && &sprites) {
-            let pos = pos.unwrap();
-            let sprite = sprite.unwrap();
-            ctx.add_sprite(
-                Rect::with_size(
-                    (pos.x - camera.position.x) * 1,
-                    (pos.y - camera.position.y) * 1,
-                    // TODO make this dynamic.
-                    1,
-                    1,
-                ),
-                0,
-                RGBA::named(WHITE),
-                sprite.0,
-            );
+        let camera = params.camera.render_view();
+        let mut draws = vec![];
+        for (e, pos, sprite) in drawables(params, params.sprite_indices) {
+            let hidden = component(params.blinks, e)
+                .map(|b| !b.is_visible(params.frame))
+                .unwrap_or(false);
+            if hidden {
+                continue;
+            }
+            let offset = component(params.offsets, e);
+            let rect = match sprite_rect(&camera, pos, offset, params.tile_size) {
+                Some(rect) => rect,
+                None => continue,
+            };
+            let layer = component(params.layers, e).map(|l| l.0).unwrap_or(0);
+            draws.push((layer, rect, sprite));
+        }
+        draws.sort_by_key(|d| d.0);
+        for (layer, rect, sprite) in draws {
+            ctx.add_sprite(rect, layer, RGBA::named(WHITE), sprite.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ascii_cells, compose_cells, lit, sprite_rect, tinted, RenderParams};
+    use crate::*;
+    #[test]
+    fn sprite_offset_not_rounded() {
+        let camera = Camera::new(Point::new(10, 10), Point::new(20, 10));
+        let tile_size = Point::new(16, 16);
+        let offset = RenderOffset::new(0.25, -0.5);
+        let rect = sprite_rect(&camera, &Point::new(12, 11), Some(&offset), tile_size).unwrap();
+        assert_eq!((rect.x1, rect.y1, rect.x2, rect.y2), (36, 8, 52, 24));
+        let rect = sprite_rect(&camera, &Point::new(12, 11), None, tile_size).unwrap();
+        assert_eq!((rect.x1, rect.y1), (32, 16));
+        assert!(sprite_rect(&camera, &Point::new(9, 11), None, tile_size).is_none());
+    }
+    #[test]
+    fn tint_multiplies() {
+        let sprite = Sprite {
+            glyph: to_cp437('@'),
+            fg: RGBA::from_f32(1.0, 0.5, 0.2, 1.0),
+            bg: None,
+        };
+        assert_eq!(tinted(sprite, None), sprite);
+        let tint = Tint(RGBA::from_f32(0.5, 2.0, 0.0, 1.0));
+        assert_eq!(
+            tinted(sprite, Some(&tint)),
+            Sprite {
+                glyph: to_cp437('@'),
+                fg: RGBA::from_f32(0.5, 1.0, 0.0, 1.0),
+                bg: None,
+            }
+        );
+    }
+    #[test]
+    fn light_darkens() {
+        let sprite = Sprite {
+            glyph: to_cp437('@'),
+            fg: RGBA::from_f32(1.0, 0.5, 0.2, 1.0),
+            bg: Some(RGBA::from_f32(1.0, 1.0, 1.0, 0.5)),
+        };
+        let p = Point::new(0, 0);
+        assert_eq!(lit(sprite, None, p), sprite);
+        let unlit = lit(sprite, Some(&LightMap::new(0.5)), p);
+        assert_eq!(unlit.fg, RGBA::from_f32(0.5, 0.25, 0.1, 1.0));
+        assert_eq!(unlit.bg, Some(RGBA::from_f32(0.5, 0.5, 0.5, 0.5)));
+    }
+    #[test]
+    fn compose_transparent_bg() {
+        let sprite = |glyph: char, bg: Option<RGBA>| Sprite {
+            glyph: to_cp437(glyph),
+            fg: RGBA::named(WHITE),
+            bg,
+        };
+        let floor = sprite('.', Some(RGBA::named(GREEN)));
+        let cells = compose_cells(vec![
+            (Point::new(0, 0), floor),
+            (Point::new(1, 0), floor),
+            (Point::new(0, 0), sprite('@', None)),
+            (Point::new(2, 0), sprite('@', None)),
+        ]);
+        assert_eq!(
+            cells,
+            vec![
+                (Point::new(0, 0), sprite('@', Some(RGBA::named(GREEN)))),
+                (Point::new(1, 0), floor),
+                (Point::new(2, 0), sprite('@', None)),
+            ]
+        );
+    }
+    #[test]
+    fn cull_outside_camera() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut multi_sprites = Components::<MultiSprite>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let sprite = Sprite {
+            glyph: to_cp437('@'),
+            fg: RGBA::named(WHITE),
+            bg: None,
+        };
+        let camera = Camera::new(Point::new(10, 10), Point::new(5, 5));
+        for pos in &[Point::new(12, 12), Point::new(9, 12), Point::new(15, 12)] {
+            let e = entities.create();
+            positions.insert(e, *pos);
+            sprites.insert(e, sprite);
         }
+        let big = entities.create();
+        positions.insert(big, Point::new(8, 8));
+        multi_sprites.insert(big, MultiSprite::new(3, 3, vec![sprite; 9]));
+        let hidden = entities.create();
+        positions.insert(hidden, Point::new(5, 5));
+        multi_sprites.insert(hidden, MultiSprite::new(3, 3, vec![sprite; 9]));
+
+        let mut cells = ascii_cells(
+            &RenderParams::new(&camera, &entities, &positions)
+                .with_multi_sprites(&multi_sprites)
+                .with_sprites(&sprites),
+        )
+        .into_iter()
+        .map(|(pos, _)| (pos.x, pos.y))
+        .collect::<Vec<_>>();
+        cells.sort();
+        assert_eq!(cells, vec![(0, 0), (2, 2)]);
+    }
+    #[test]
+    fn transparent_tiles() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut multi_sprites = Components::<MultiSprite>::default();
+        let e = entities.create();
+        positions.insert(e, Point::new(0, 0));
+        multi_sprites.insert(
+            e,
+            MultiSprite::from_string("# ##", 2, 2, RGBA::named(WHITE), None)
+                .with_transparent_glyph(to_cp437(' ')),
+        );
+        let camera = Camera::new(Point::new(0, 0), Point::new(5, 5));
+        let mut cells = ascii_cells(
+            &RenderParams::new(&camera, &entities, &positions).with_multi_sprites(&multi_sprites),
+        )
+        .into_iter()
+        .map(|(pos, _)| (pos.x, pos.y))
+        .collect::<Vec<_>>();
+        cells.sort();
+        assert_eq!(cells, vec![(0, 0), (0, 1), (1, 1)]);
+    }
+    #[test]
+    fn without_entities() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut layers = Components::<Layer>::default();
+        let sprite = |c: char| Sprite {
+            glyph: to_cp437(c),
+            fg: RGBA::named(WHITE),
+            bg: None,
+        };
+        let top = entities.create();
+        positions.insert(top, Point::new(1, 1));
+        sprites.insert(top, sprite('@'));
+        layers.insert(top, Layer(1));
+        let bottom = entities.create();
+        positions.insert(bottom, Point::new(1, 1));
+        sprites.insert(bottom, sprite('.'));
+        let camera = Camera::new(Point::new(0, 0), Point::new(4, 4));
+        let cells = |params: RenderParams| {
+            ascii_cells(&params.with_sprites(&sprites).with_layers(&layers))
+                .into_iter()
+                .map(|(p, c)| (p.x, p.y, c.glyph))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            cells(RenderParams::new(&camera, &entities, &positions)),
+            vec![(1, 1, to_cp437('@'))]
+        );
+        // `render_ascii` doesn't know the entities, so it can't sort them.
+        assert_eq!(
+            cells(RenderParams::from_parts(&camera, None, &positions)),
+            vec![(1, 1, to_cp437('.'))]
+        );
+    }
+    #[test]
+    fn highlight_layers() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut layers = Components::<Layer>::default();
+        let floor = entities.create();
+        positions.insert(floor, Point::new(0, 0));
+        sprites.insert(
+            floor,
+            Sprite {
+                glyph: to_cp437('.'),
+                fg: RGBA::named(WHITE),
+                bg: Some(RGBA::named(BLACK)),
+            },
+        );
+        let unit = entities.create();
+        positions.insert(unit, Point::new(0, 0));
+        layers.insert(unit, Layer(1));
+        sprites.insert(
+            unit,
+            Sprite {
+                glyph: to_cp437('@'),
+                fg: RGBA::named(WHITE),
+                bg: None,
+            },
+        );
+        let red = RGBA::from_f32(1.0, 0.0, 0.0, 0.5);
+        let mut overlay = HighlightOverlay::new(0);
+        overlay.highlight(vec![Point::new(0, 0), Point::new(1, 0)], red);
+        let cells = |overlay: &HighlightOverlay| {
+            let camera = Camera::new(Point::new(0, 0), Point::new(5, 5));
+            let params = RenderParams::new(&camera, &entities, &positions)
+                .with_sprites(&sprites)
+                .with_layers(&layers)
+                .with_highlights(overlay);
+            let mut cells = ascii_cells(&params);
+            cells.sort_by_key(|(p, _)| p.x);
+            cells
+                .into_iter()
+                .map(|(_, c)| (c.glyph, c.bg.unwrap()))
+                .collect::<Vec<_>>()
+        };
+        // Above the floor, under the unit.
+        assert_eq!(
+            cells(&overlay),
+            vec![
+                (to_cp437('@'), RGBA::from_f32(0.5, 0.0, 0.0, 1.0)),
+                (to_cp437(' '), red),
+            ]
+        );
+        // Under the floor, which hides it.
+        overlay.layer = -1;
+        assert_eq!(cells(&overlay)[0], (to_cp437('@'), RGBA::named(BLACK)));
+    }
+    #[test]
+    fn fog_of_war() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut remembered = Components::<Remembered>::default();
+        let sprite = |c: char| Sprite {
+            glyph: to_cp437(c),
+            fg: RGBA::named(WHITE),
+            bg: None,
+        };
+        let wanderer = entities.create();
+        positions.insert(wanderer, Point::new(2, 0));
+        sprites.insert(wanderer, sprite('g'));
+        let monster = entities.create();
+        positions.insert(monster, Point::new(3, 0));
+        sprites.insert(monster, sprite('o'));
+        let mut memory = Remembered::new(0.5).with_marker(sprite('?'));
+        memory.last_seen = Some(Point::new(1, 0));
+        remembered.insert(monster, memory);
+        // Only has a multi sprite, its marker is drawn instead.
+        let mut multi_sprites = Components::<MultiSprite>::default();
+        let dragon = entities.create();
+        positions.insert(dragon, Point::new(3, 2));
+        multi_sprites.insert(dragon, MultiSprite::new(2, 1, vec![sprite('D'); 2]));
+        let mut memory = Remembered::new(0.5).with_marker(sprite('!'));
+        memory.last_seen = Some(Point::new(4, 0));
+        remembered.insert(dragon, memory);
+        let mut visible = VisibleSet::default();
+        visible.tiles.insert(Point::new(0, 0));
+
+        let cells = |visible: Option<&VisibleSet>| {
+            let camera = Camera::new(Point::new(0, 0), Point::new(5, 5));
+            let mut params = RenderParams::new(&camera, &entities, &positions)
+                .with_multi_sprites(&multi_sprites)
+                .with_sprites(&sprites)
+                .with_remembered(&remembered);
+            if let Some(visible) = visible {
+                params = params.with_visible(visible);
+            }
+            let mut cells = ascii_cells(&params);
+            cells.sort_by_key(|(p, _)| (p.x, p.y));
+            cells
+                .into_iter()
+                .map(|(p, c)| (p.x, p.y, c.glyph, c.fg))
+                .collect::<Vec<_>>()
+        };
+        let white = RGBA::named(WHITE);
+        // Opt-in: without a `VisibleSet`, everything is drawn.
+        assert_eq!(
+            cells(None),
+            vec![
+                (2, 0, to_cp437('g'), white),
+                (3, 0, to_cp437('o'), white),
+                (3, 2, to_cp437('D'), white),
+                (4, 2, to_cp437('D'), white),
+            ]
+        );
+        let grey = RGBA::from_f32(0.5, 0.5, 0.5, 1.0);
+        assert_eq!(
+            cells(Some(&visible)),
+            vec![(1, 0, to_cp437('?'), grey), (4, 0, to_cp437('!'), grey)]
+        );
+        // The player sees that the monster left.
+        visible.tiles.insert(Point::new(1, 0));
+        visible.tiles.insert(Point::new(2, 0));
+        assert_eq!(
+            cells(Some(&visible)),
+            vec![(2, 0, to_cp437('g'), white), (4, 0, to_cp437('!'), grey)]
+        );
+    }
+    #[test]
+    fn blink_hides() {
+        let blink = Blink::new(2, 1);
+        let frames = (0..6).map(|f| blink.is_visible(f)).collect::<Vec<_>>();
+        assert_eq!(frames, vec![true, true, false, true, true, false]);
+        let mut offset = blink;
+        offset.offset = 1;
+        assert!(!offset.is_visible(1));
+        assert!(Blink::new(0, 0).is_visible(3));
+
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut blinks = Components::<Blink>::default();
+        let e = entities.create();
+        positions.insert(e, Point::new(1, 1));
+        sprites.insert(
+            e,
+            Sprite {
+                glyph: to_cp437('@'),
+                fg: RGBA::named(WHITE),
+                bg: None,
+            },
+        );
+        blinks.insert(e, blink);
+        let camera = Camera::new(Point::new(0, 0), Point::new(4, 4));
+        let drawn = |frame: u64| {
+            let params = RenderParams::new(&camera, &entities, &positions)
+                .with_sprites(&sprites)
+                .with_blinks(&blinks, frame);
+            !ascii_cells(&params).is_empty()
+        };
+        assert!(drawn(1));
+        assert!(!drawn(2));
     }
 }
@@ -1,8 +1,14 @@
 use crate::*;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Holds the collision map used to calculate movements, ai pathing and collisions
 /// between entities and the map.
-#[derive(new)]
+#[derive(new, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CollisionResource {
     /// The inner `CollisionMap`.
     pub map: CollisionMap,
@@ -29,6 +35,624 @@ impl CollisionResource {
             (p.y - self.position.y) as u32,
         )
     }
+    /// Checks whether the position is inside of the `CollisionMap` and collides.
+    pub fn is_blocked(&self, p: &Point) -> bool {
+        if !self.is_inside(p) {
+            return false;
+        }
+        let (x, y) = self.relative_point(p);
+        self.map.is_set(x, y)
+    }
+    /// Calculates a path between two positions using A*.
+    /// Returns `None` if either position is outside of the `CollisionMap`.
+    pub fn find_path(&self, from: &Point, to: &Point) -> Option<NavigationPath> {
+        if !self.is_inside(from) || !self.is_inside(to) {
+            return None;
+        }
+        let (fx, fy) = self.relative_point(from);
+        let (tx, ty) = self.relative_point(to);
+        Some(a_star_search(
+            self.map.index_of(fx, fy),
+            self.map.index_of(tx, ty),
+            &self.map,
+        ))
+    }
+}
+
+/// Limits how many paths `ai_pathing_system` calculates each frame, to avoid frame
+/// spikes when many entities need a path at the same time.
+/// Entities wait in a queue in the order they asked for a path, so each of them is
+/// served after at most `queued / max_per_frame` frames.
+pub struct PathingBudget {
+    /// The maximum number of paths calculated each frame.
+    pub max_per_frame: usize,
+    queue: VecDeque<Entity>,
+    /// The entities last seen outside of the map, so they are only logged once.
+    outside: HashSet<Entity>,
+}
+
+impl PathingBudget {
+    /// Creates a budget calculating at most `max_per_frame` paths each frame.
+    pub fn new(max_per_frame: usize) -> Self {
+        Self {
+            max_per_frame,
+            queue: VecDeque::new(),
+            outside: HashSet::new(),
+        }
+    }
+
+    /// The number of entities waiting for a path.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub(crate) fn push(&mut self, e: Entity) {
+        self.queue.push_back(e);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Entity> {
+        self.queue.pop_front()
+    }
+
+    /// Remembers that the entity is outside of the map. Returns false if it already
+    /// was the last time.
+    pub(crate) fn mark_outside(&mut self, e: Entity) -> bool {
+        self.outside.insert(e)
+    }
+
+    pub(crate) fn forget_outside(&mut self, e: Entity) {
+        self.outside.remove(&e);
+    }
+}
+
+/// Calculates every path on the frame it is requested.
+impl Default for PathingBudget {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+/// Configures how `stuck_detection_system` decides that an entity following an
+/// `AiPath` is stuck.
+/// An entity is stuck when it stayed within `min_distance` tiles of the same
+/// position for `max_ticks` chances to step. Entities get one chance per run of the
+/// system, none while their `ActionPoints` are spent, and the number of tiles
+/// their `MoveCooldown` allows during the frame otherwise.
+pub struct StuckDetection {
+    /// How many chances to step an entity may go without progressing.
+    pub max_ticks: u32,
+    /// How far in tiles an entity has to move to count as progressing.
+    pub min_distance: f32,
+    history: HashMap<Entity, (Point, f32)>,
+}
+
+impl StuckDetection {
+    /// Creates a detection using the given thresholds.
+    pub fn new(max_ticks: u32, min_distance: f32) -> Self {
+        Self {
+            max_ticks,
+            min_distance,
+            history: HashMap::new(),
+        }
+    }
+
+    /// Registers the position of a path following entity, which had `chances` to
+    /// step since the last call, and checks if it is stuck.
+    /// Being stuck restarts the count from that position.
+    pub(crate) fn track(&mut self, e: Entity, pos: Point, chances: f32) -> bool {
+        let min_distance = self.min_distance;
+        let (start, ticks) = self.history.entry(e).or_insert((pos, 0.0));
+        let (dx, dy) = ((pos.x - start.x) as f32, (pos.y - start.y) as f32);
+        if (dx * dx + dy * dy).sqrt() > min_distance {
+            *start = pos;
+            *ticks = 0.0;
+            return false;
+        }
+        *ticks += chances;
+        if *ticks >= self.max_ticks as f32 {
+            *ticks = 0.0;
+            return true;
+        }
+        false
+    }
+
+    /// Forgets the entities that aren't following a path anymore.
+    pub(crate) fn retain(&mut self, keep: impl Fn(Entity) -> bool) {
+        self.history.retain(|e, _| keep(*e));
+    }
+}
+
+/// Considers an entity stuck after 120 chances to step within 1.5 tiles, so that
+/// walking back and forth between neighbor tiles doesn't count as progressing.
+impl Default for StuckDetection {
+    fn default() -> Self {
+        Self::new(120, 1.5)
+    }
+}
+
+/// Collision layers for entities with different passability, such as walkers,
+/// swimmers and flyers, selected by their `MovementClass`.
+/// Entities without a `MovementClass`, or whose class has no layer, use the
+/// `CollisionResource`.
+#[derive(Default)]
+pub struct CollisionMapStack {
+    /// The layer of each class.
+    pub layers: HashMap<MovementClass, CollisionResource>,
+}
+
+impl CollisionMapStack {
+    /// Sets the layer of a class, returning the previous one.
+    pub fn insert(
+        &mut self,
+        class: MovementClass,
+        layer: CollisionResource,
+    ) -> Option<CollisionResource> {
+        self.layers.insert(class, layer)
+    }
+
+    /// The collision map of a class, if it has a layer.
+    pub fn for_class(&self, class: &MovementClass) -> Option<&CollisionMap> {
+        self.layers.get(class).map(|layer| &layer.map)
+    }
+
+    /// The layer to use for an entity of the given class, falling back to `global_map`.
+    /// Returns `None` if the class has no layer and there is no global map.
+    pub fn layer_or<'a>(
+        &'a self,
+        global_map: Option<&'a CollisionResource>,
+        class: Option<&MovementClass>,
+    ) -> Option<&'a CollisionResource> {
+        class
+            .and_then(|class| self.layers.get(class))
+            .or(global_map)
+    }
+}
+
+/// The positions at which `track_collision_system` set `Collision` entities in the
+/// `CollisionResource` during the last frame.
+#[derive(Default)]
+pub struct CollisionTracker {
+    /// The last registered tiles of each entity.
+    pub tiles: HashMap<Entity, Vec<Point>>,
+}
+
+/// Event sent in the `Vec<BlockedMove>` resource when a movement system refuses
+/// to move an entity because the `CollisionResource` blocks the next tile.
+/// Consumers should drain the `Vec` every frame.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockedMove {
+    /// The entity that tried to move.
+    pub entity: Entity,
+    /// The tile it tried to move into.
+    pub target: Point,
+    /// The direction in which it tried to move.
+    pub direction: Direction,
+}
+
+/// Event sent in the `Vec<Adjacent>` resource by `adjacency_system` when an entity
+/// becomes orthogonally adjacent to an entity having an `AdjacencyTrigger`.
+/// Consumers should drain the `Vec` every frame.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjacent {
+    /// The entity having the `AdjacencyTrigger`.
+    pub actor: Entity,
+    /// The entity now next to it.
+    pub target: Entity,
+}
+
+/// The movement components watched by `movement_events_system`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MovementComponent {
+    /// `AiPath`.
+    AiPath,
+    /// `AiDestination`.
+    AiDestination,
+    /// `GotoEntity`.
+    GotoEntity,
+}
+
+/// Event sent in the `Vec<MovementChange>` resource by `movement_events_system`
+/// when one of the `MovementComponent`s is added to or removed from an entity.
+/// Consumers should drain the `Vec` every frame.
+#[derive(new, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovementChange {
+    /// The entity that changed.
+    pub entity: Entity,
+    /// The component that was added or removed.
+    pub component: MovementComponent,
+    /// Whether the component was added, otherwise it was removed.
+    pub added: bool,
+}
+
+/// The entities that had each `MovementComponent` during the last run of
+/// `movement_events_system`.
+#[derive(Default)]
+pub struct MovementTracker {
+    /// The last registered entities of each component, in storage order.
+    pub entities: HashMap<MovementComponent, Vec<Entity>>,
+}
+
+/// Finds the entities standing on a tile without scanning all of them.
+/// Kept up to date by `spatial_hash_system`.
+#[derive(Default)]
+pub struct SpatialHash {
+    tiles: HashMap<Point, Vec<Entity>>,
+    positions: HashMap<Entity, Point>,
+}
+
+impl SpatialHash {
+    /// Places the entity on a tile, moving it if it was already registered.
+    pub fn insert(&mut self, entity: Entity, position: Point) {
+        if self.positions.get(&entity) == Some(&position) {
+            return;
+        }
+        self.remove(entity);
+        self.positions.insert(entity, position);
+        self.tiles.entry(position).or_default().push(entity);
+    }
+
+    /// Removes the entity. Does nothing if it wasn't registered.
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(position) = self.positions.remove(&entity) {
+            if let Some(tile) = self.tiles.get_mut(&position) {
+                tile.retain(|e| *e != entity);
+                if tile.is_empty() {
+                    self.tiles.remove(&position);
+                }
+            }
+        }
+    }
+
+    /// The registered entities.
+    pub fn registered(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.positions.keys().copied()
+    }
+
+    /// The registered position of the entity.
+    pub fn position_of(&self, entity: Entity) -> Option<Point> {
+        self.positions.get(&entity).copied()
+    }
+
+    /// The entities on a tile, in the order they arrived on it.
+    pub fn entities_at(&self, p: Point) -> &[Entity] {
+        self.tiles.get(&p).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// The entities inside of the rectangle starting at `top_left`, tile by tile from
+    /// left to right, then top to bottom.
+    pub fn entities_in_rect(&self, top_left: Point, width: u32, height: u32) -> Vec<Entity> {
+        // Visits whichever is smaller: the tiles of the rectangle or the occupied tiles.
+        let mut occupied = if (width as usize) * (height as usize) <= self.tiles.len() {
+            (0..height as i32)
+                .flat_map(|y| (0..width as i32).map(move |x| (x, y)))
+                .map(|(x, y)| Point::new(top_left.x + x, top_left.y + y))
+                .filter(|p| self.tiles.contains_key(p))
+                .collect::<Vec<_>>()
+        } else {
+            self.tiles
+                .keys()
+                .filter(|p| position_inside_rect(p.x, p.y, top_left.x, top_left.y, width, height))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        occupied.sort_by_key(|p| (p.y, p.x));
+        occupied
+            .into_iter()
+            .flat_map(|p| self.entities_at(p).iter().copied())
+            .collect()
+    }
+
+    /// The `k` entities closest to `origin` accepted by `filter`, with their
+    /// Euclidean distance in tiles, closest first.
+    /// Ties are sorted by tile from left to right, then top to bottom, and by arrival
+    /// order on the same tile.
+    /// Searches rings of tiles around `origin` until the closest entities are found.
+    pub fn k_nearest(
+        &self,
+        origin: Point,
+        k: usize,
+        filter: impl Fn(Entity) -> bool,
+    ) -> Vec<(Entity, f32)> {
+        if k == 0 || self.tiles.is_empty() {
+            return vec![];
+        }
+        let distance = |p: &Point| {
+            (((p.x - origin.x) as f32).powi(2) + ((p.y - origin.y) as f32).powi(2)).sqrt()
+        };
+        let push = |found: &mut Vec<(Entity, f32, Point)>, p: Point| {
+            for e in self.entities_at(p) {
+                if filter(*e) {
+                    found.push((*e, distance(&p), p));
+                }
+            }
+        };
+        let sort = |found: &mut Vec<(Entity, f32, Point)>| {
+            found.sort_by(|a, b| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap()
+                    .then((a.2.y, a.2.x).cmp(&(b.2.y, b.2.x)))
+            });
+        };
+        let mut found = vec![];
+        let mut visited = 0;
+        let mut r = 0;
+        loop {
+            // Once the rings cover more tiles than are occupied, checking every
+            // occupied tile is faster.
+            if visited > self.tiles.len() {
+                found.clear();
+                for p in self.tiles.keys() {
+                    push(&mut found, *p);
+                }
+                sort(&mut found);
+                break;
+            }
+            if r == 0 {
+                push(&mut found, origin);
+                visited += 1;
+            } else {
+                for i in -r..r {
+                    push(&mut found, Point::new(origin.x + i, origin.y - r));
+                    push(&mut found, Point::new(origin.x + r, origin.y + i));
+                    push(&mut found, Point::new(origin.x - i, origin.y + r));
+                    push(&mut found, Point::new(origin.x - r, origin.y - i));
+                }
+                visited += 8 * r as usize;
+            }
+            sort(&mut found);
+            // Entities of the next rings are at least `r + 1` tiles away.
+            if found.len() >= k && found[k - 1].1 < (r + 1) as f32 {
+                break;
+            }
+            r += 1;
+        }
+        found.truncate(k);
+        found.into_iter().map(|(e, d, _)| (e, d)).collect()
+    }
+}
+
+/// The light level of each tile of the `CollisionResource`, from 0 (dark) to 1
+/// (fully lit). Rebuilt every frame by `light_map_system`, and used by
+/// `render_ascii_with` to darken sprites.
+/// Tiles outside of the map use the ambient level.
+pub struct LightMap {
+    /// The minimum level of every tile, used for the tiles not reached by any light.
+    pub ambient: f32,
+    position: Point,
+    width: u32,
+    height: u32,
+    levels: Vec<f32>,
+}
+
+impl LightMap {
+    /// Creates an empty light map with the given ambient level.
+    pub fn new(ambient: f32) -> Self {
+        Self {
+            ambient,
+            position: Point::new(0, 0),
+            width: 0,
+            height: 0,
+            levels: vec![],
+        }
+    }
+
+    /// Covers the `CollisionResource` and removes the light of every tile.
+    pub fn clear(&mut self, global_map: &CollisionResource) {
+        let (width, height) = global_map.map.size();
+        self.position = global_map.position;
+        self.width = width;
+        self.height = height;
+        self.levels.clear();
+        self.levels.resize((width * height) as usize, 0.0);
+    }
+
+    /// Lights the tiles seen from `center`, using the field of view of the
+    /// `CollisionResource`: colliding tiles are lit but cast a shadow behind them.
+    /// The level falls off linearly with the straight line distance, reaching 0 just
+    /// past the radius. Lights add up, up to 1.
+    pub fn add_light(&mut self, global_map: &CollisionResource, center: Point, light: &PointLight) {
+        if !global_map.is_inside(&center) || self.levels.is_empty() {
+            return;
+        }
+        let (x, y) = global_map.relative_point(&center);
+        let local = Point::new(x as i32, y as i32);
+        for (tx, ty) in global_map.map.field_of_view(local, light.radius) {
+            let distance = dist(&local, &Point::new(tx as i32, ty as i32));
+            if distance > light.radius as f32 {
+                continue;
+            }
+            let level = light.intensity * (1.0 - distance / (light.radius + 1) as f32);
+            let idx = (ty * self.width + tx) as usize;
+            self.levels[idx] = (self.levels[idx] + level.max(0.0)).min(1.0);
+        }
+    }
+
+    /// The light level at a world position.
+    pub fn level(&self, p: Point) -> f32 {
+        let (x, y) = (p.x - self.position.x, p.y - self.position.y);
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return self.ambient.clamp(0.0, 1.0);
+        }
+        self.levels[(y as u32 * self.width + x as u32) as usize].max(self.ambient.clamp(0.0, 1.0))
+    }
+}
+
+impl Default for LightMap {
+    /// Unlit tiles are black.
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+/// A grid of influence, like a scent or a threat level, deposited by sources and
+/// spreading over the walkable tiles of a `CollisionMap` over time. Positions are
+/// relative to the map. AI can move towards or away from the higher values.
+///
+/// Each call to `propagate` is one tick: every tile takes the highest of its own
+/// value and the values of the neighbors it can walk to, multiplied by `decay`.
+/// Influence spreads by one tile per tick and never crosses colliding tiles, which
+/// hold no influence.
+///
+/// With a `decay` below 1, a source added with the same strength after each tick
+/// converges once the influence reached every tile: a tile `k` steps away from it
+/// then holds `strength * decay^k`. Without sources, the highest value falls by
+/// `decay` each tick towards 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InfluenceMap {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl InfluenceMap {
+    /// Creates an influence map without influence, for a map of the given size.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            values: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    /// Raises the influence of the tile to at least `strength`.
+    /// Positions outside of the map are ignored.
+    pub fn add_source(&mut self, p: Point, strength: f32) {
+        if let Some(idx) = self.index(p) {
+            self.values[idx] = self.values[idx].max(strength);
+        }
+    }
+
+    /// Spreads and decays the influence by one tick, see `InfluenceMap`.
+    /// The neighbors follow the movement rules of the map, which must have the size
+    /// of the influence map.
+    pub fn propagate(&mut self, map: &CollisionMap, decay: f32) {
+        if map.size() != (self.width, self.height) {
+            return;
+        }
+        let previous = self.values.clone();
+        for (idx, value) in self.values.iter_mut().enumerate() {
+            let (x, y) = map.position_of(idx as u32);
+            if map.is_set(x, y) {
+                *value = 0.0;
+                continue;
+            }
+            let highest = map
+                .get_available_exits(idx)
+                .iter()
+                .map(|(n, _)| previous[*n])
+                .fold(previous[idx], f32::max);
+            *value = highest * decay;
+        }
+    }
+
+    /// The influence at the position, 0 outside of the map.
+    pub fn value_at(&self, p: Point) -> f32 {
+        self.index(p).map(|idx| self.values[idx]).unwrap_or(0.0)
+    }
+
+    /// Removes all the influence.
+    pub fn clear(&mut self) {
+        self.values.iter_mut().for_each(|v| *v = 0.0);
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        if p.x < 0 || p.y < 0 || p.x as u32 >= self.width || p.y as u32 >= self.height {
+            return None;
+        }
+        Some((p.y as u32 * self.width + p.x as u32) as usize)
+    }
+}
+
+/// The tiles currently seen by the entities having a `Viewer`, in world positions.
+/// Rebuilt every frame by `visible_set_system`. When given to `render_ascii_with`,
+/// the sprites on the other tiles aren't drawn.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct VisibleSet {
+    pub tiles: HashSet<Point>,
+}
+
+impl VisibleSet {
+    /// Adds the tiles seen from `origin`, using the field of view of the
+    /// `CollisionResource`: colliding tiles are seen but hide the tiles behind them.
+    pub fn add_view(&mut self, global_map: &CollisionResource, origin: Point, radius: u32) {
+        if !global_map.is_inside(&origin) {
+            return;
+        }
+        let (x, y) = global_map.relative_point(&origin);
+        let local = Point::new(x as i32, y as i32);
+        for (tx, ty) in global_map.map.field_of_view(local, radius) {
+            self.tiles.insert(Point::new(
+                tx as i32 + global_map.position.x,
+                ty as i32 + global_map.position.y,
+            ));
+        }
+    }
+
+    /// Checks if the world position is currently seen.
+    pub fn is_visible(&self, p: &Point) -> bool {
+        self.tiles.contains(p)
+    }
+}
+
+/// Colors drawn over tiles by `render_ascii_with`, independently of the entities on
+/// them, for example to show the movement range or the area of a skill.
+/// The highlights are drawn above the sprites of `layer` and the layers below it,
+/// and under the sprites of the higher layers.
+#[derive(new, Default, Clone, Debug, PartialEq)]
+pub struct HighlightOverlay {
+    /// The color of each highlighted tile. Its alpha sets how much of the
+    /// background below shows through.
+    #[new(default)]
+    pub tiles: HashMap<Point, RGBA>,
+    /// The `Layer` that the highlights are drawn above.
+    pub layer: i32,
+}
+
+impl HighlightOverlay {
+    /// Highlights the tiles with the color, replacing their previous highlight.
+    pub fn highlight(&mut self, tiles: impl IntoIterator<Item = Point>, color: RGBA) {
+        for tile in tiles {
+            self.tiles.insert(tile, color);
+        }
+    }
+
+    /// Removes every highlight.
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+}
+
+/// The cells drawn during the last frame by `render_ascii_cached`, by screen position.
+#[derive(Default)]
+pub struct RenderCache {
+    cells: HashMap<Point, Sprite>,
+}
+
+impl RenderCache {
+    /// Forgets the drawn cells, so that the next frame is fully redrawn.
+    pub fn invalidate(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Stores the cells of the new frame and gives the ones that need to be drawn:
+    /// changed cells and erased cells (`None`).
+    pub(crate) fn update(&mut self, cells: Vec<(Point, Sprite)>) -> Vec<(Point, Option<Sprite>)> {
+        let mut changed = vec![];
+        let mut next = HashMap::with_capacity(cells.len());
+        for (pos, cell) in cells {
+            if self.cells.get(&pos) != Some(&cell) {
+                changed.push((pos, Some(cell)));
+            }
+            next.insert(pos, cell);
+        }
+        for pos in self.cells.keys() {
+            if !next.contains_key(pos) {
+                changed.push((*pos, None));
+            }
+        }
+        self.cells = next;
+        changed
+    }
 }
 
 /// Sets the game speed multiplier.
@@ -39,3 +663,383 @@ impl Default for GameSpeed {
         GameSpeed(1.0)
     }
 }
+
+/// A seeded random number generator, so that the same seed gives the same maps and
+/// ai decisions, for tests and replays. Games should draw all their randomness from
+/// this resource instead of a global generator.
+/// Only `camera_shake_system` draws from it, the other systems of the crate give the
+/// same results every run.
+/// Uses SplitMix64, which isn't suited for cryptography.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    /// Creates a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Gives the state of the generator, to save it with a game.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Restores a state given by `state`. The next numbers will be the same as the
+    /// ones that followed when the state was saved.
+    pub fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+
+    /// Gives a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Gives a random number between 0 included and 1 excluded.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Gives a random number between `min` included and `max` excluded.
+    /// Returns `min` if the range is empty.
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64) as u64;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    /// Returns `true` with the given probability, between 0 and 1.
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    /// Shuffles the slice in place.
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            slice.swap(i, j);
+        }
+    }
+}
+
+/// Seeded with 0.
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Runs the simulation at a fixed rate while rendering as often as possible.
+/// Every frame, `advance` gives how many fixed updates to run, for example by
+/// running a second `Dispatcher` holding the movement systems followed by
+/// `snapshot_positions_system` that many times.
+/// The time left over is kept for the next frame: `alpha` gives how far the frame
+/// is between the last fixed update and the next one, which
+/// `interpolate_positions_system` reads to draw the entities between their
+/// positions.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct FixedTimestep {
+    /// The duration of a fixed update, in seconds.
+    pub step: f32,
+    /// The most fixed updates ran in one frame. The time left over after them is
+    /// dropped, so that a slow frame doesn't make the next ones slower.
+    #[new(value = "5")]
+    pub max_steps: u32,
+    /// The time not yet simulated, in seconds.
+    #[new(default)]
+    pub accumulator: f32,
+}
+
+impl FixedTimestep {
+    /// Adds the duration of a frame, in seconds, and gives how many fixed updates
+    /// to run.
+    pub fn advance(&mut self, delta: f32) -> u32 {
+        if self.step <= 0.0 {
+            self.accumulator = 0.0;
+            return 1;
+        }
+        self.accumulator += delta;
+        let steps = (self.accumulator / self.step) as u32;
+        if steps > self.max_steps {
+            self.accumulator = 0.0;
+            return self.max_steps;
+        }
+        self.accumulator -= steps as f32 * self.step;
+        steps
+    }
+
+    /// How far the current frame is between the last fixed update (0) and the
+    /// next one (1).
+    pub fn alpha(&self) -> f32 {
+        if self.step <= 0.0 {
+            return 1.0;
+        }
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+/// The largest `CameraShake::magnitude`, in world tiles.
+pub const MAX_CAMERA_SHAKE: f32 = 5.0;
+
+/// Shakes the `Camera` for a while, see `camera_shake_system`.
+#[derive(new, Debug, Clone, Copy, PartialEq)]
+pub struct CameraShake {
+    /// The largest offset at the start of the shake, in world tiles.
+    /// Clamped between 0 and `MAX_CAMERA_SHAKE`.
+    pub magnitude: f32,
+    /// How long the shake lasts, in seconds.
+    pub duration: f32,
+    /// The time elapsed since the start of the shake, in seconds.
+    #[new(default)]
+    pub elapsed: f32,
+}
+
+impl CameraShake {
+    /// The largest offset at this point of the shake, decaying linearly to 0.
+    pub fn strength(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        let left = (1.0 - self.elapsed / self.duration).clamp(0.0, 1.0);
+        self.magnitude.clamp(0.0, MAX_CAMERA_SHAKE) * left
+    }
+}
+
+/// The `Sprite`s that `SpriteIndex` components refer to, by index.
+/// Can be deserialized from a config file with the `serde` feature.
+#[derive(new, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpriteDefinitions {
+    /// The sprites.
+    pub sprites: Vec<Sprite>,
+}
+
+impl SpriteDefinitions {
+    /// Gets the `Sprite` at this index.
+    pub fn get(&self, index: usize) -> Option<&Sprite> {
+        self.sprites.get(index)
+    }
+}
+
+/// The `SpriteIndex` that `resolve_sprite_index_system` last resolved for each
+/// entity, valid or not.
+#[derive(Default)]
+pub struct SpriteIndexTracker {
+    /// The last resolved index of each entity.
+    pub indices: HashMap<Entity, usize>,
+}
+
+/// The `Palette` and `ThemedSprite`s that `resolve_themed_sprite_system` last
+/// resolved.
+#[derive(Default)]
+pub struct ThemedSpriteTracker {
+    /// The palette used during the last run.
+    pub palette: Palette,
+    /// The last resolved `ThemedSprite` of each entity.
+    pub sprites: HashMap<Entity, ThemedSprite>,
+}
+
+/// Named colors, so that sprites refer to a key instead of a hardcoded `RGBA` and
+/// the whole game can be recolored by changing the palette.
+/// Can be deserialized from a config file with the `serde` feature.
+/// Keys without a color give `Palette::FALLBACK`.
+#[derive(new, Default, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Palette {
+    /// The colors by key.
+    pub colors: HashMap<String, RGBA>,
+}
+
+impl Palette {
+    /// The color of missing keys: magenta, which stands out so that typos are
+    /// easy to spot.
+    pub const FALLBACK: RGBA = RGBA {
+        r: 1.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    };
+
+    /// Sets the color of a key.
+    pub fn set(&mut self, key: &str, color: RGBA) {
+        self.colors.insert(key.to_string(), color);
+    }
+
+    /// Gets the color of a key, or `Palette::FALLBACK` if it is missing.
+    pub fn color(&self, key: &str) -> RGBA {
+        self.colors.get(key).copied().unwrap_or(Self::FALLBACK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn render_cache_diff() {
+        let sprite = |glyph: char| Sprite {
+            glyph: to_cp437(glyph),
+            fg: RGBA::named(WHITE),
+            bg: None,
+        };
+        let mut cache = RenderCache::default();
+        let frame = vec![
+            (Point::new(0, 0), sprite('#')),
+            (Point::new(1, 0), sprite('@')),
+        ];
+        assert_eq!(cache.update(frame.clone()).len(), 2);
+        assert!(cache.update(frame).is_empty());
+        let moved = vec![
+            (Point::new(0, 0), sprite('#')),
+            (Point::new(2, 0), sprite('@')),
+        ];
+        assert_eq!(
+            cache.update(moved.clone()),
+            vec![
+                (Point::new(2, 0), Some(sprite('@'))),
+                (Point::new(1, 0), None)
+            ]
+        );
+        cache.invalidate();
+        assert_eq!(cache.update(moved).len(), 2);
+    }
+    #[test]
+    fn influence_spread() {
+        let map = CollisionMap::from_str_layout("...#..", '#').unwrap();
+        let mut influence = InfluenceMap::new(6, 1);
+        let values = |i: &InfluenceMap| {
+            (0..6)
+                .map(|x| i.value_at(Point::new(x, 0)))
+                .collect::<Vec<_>>()
+        };
+        influence.add_source(Point::new(0, 0), 1.0);
+        influence.propagate(&map, 0.5);
+        assert_eq!(values(&influence), vec![0.5, 0.5, 0.0, 0.0, 0.0, 0.0]);
+        for _ in 0..10 {
+            influence.add_source(Point::new(0, 0), 1.0);
+            influence.propagate(&map, 0.5);
+        }
+        influence.add_source(Point::new(0, 0), 1.0);
+        // Converged, and nothing leaks through the wall.
+        assert_eq!(values(&influence), vec![1.0, 0.5, 0.25, 0.0, 0.0, 0.0]);
+        influence.propagate(&map, 0.5);
+        assert_eq!(values(&influence), vec![0.5, 0.5, 0.25, 0.0, 0.0, 0.0]);
+        assert_eq!(influence.value_at(Point::new(-1, 0)), 0.0);
+        influence.clear();
+        assert_eq!(influence.value_at(Point::new(0, 0)), 0.0);
+    }
+    #[test]
+    fn light_shadows() {
+        let mut map = CollisionMap::new(7, 3);
+        map.set(3, 1);
+        let global_map = CollisionResource::new(map, Point::new(10, 0));
+        let mut light_map = LightMap::new(0.1);
+        light_map.clear(&global_map);
+        light_map.add_light(&global_map, Point::new(11, 1), &PointLight::new(5, 1.0));
+        assert_eq!(light_map.level(Point::new(11, 1)), 1.0);
+        assert!((light_map.level(Point::new(12, 1)) - 5.0 / 6.0).abs() < 1e-6);
+        // The wall is lit, the tile behind it isn't.
+        assert!(light_map.level(Point::new(13, 1)) > 0.1);
+        assert_eq!(light_map.level(Point::new(14, 1)), 0.1);
+        assert_eq!(light_map.level(Point::new(0, 0)), 0.1);
+
+        light_map.add_light(&global_map, Point::new(11, 1), &PointLight::new(5, 1.0));
+        assert_eq!(light_map.level(Point::new(12, 1)), 1.0);
+        light_map.clear(&global_map);
+        assert_eq!(light_map.level(Point::new(11, 1)), 0.1);
+    }
+    #[test]
+    fn k_nearest() {
+        let mut entities = Entities::default();
+        let mut spatial_hash = SpatialHash::default();
+        let mut add = |x, y| {
+            let e = entities.create();
+            spatial_hash.insert(e, Point::new(x, y));
+            e
+        };
+        let (a, b, c, d) = (add(5, 5), add(7, 5), add(3, 5), add(6, 6));
+        let stacked = add(6, 6);
+        let far = add(100, -40);
+
+        let origin = Point::new(5, 5);
+        let nearest = spatial_hash.k_nearest(origin, 3, |_| true);
+        assert_eq!(
+            nearest,
+            vec![
+                (a, 0.0),
+                (d, std::f32::consts::SQRT_2),
+                (stacked, std::f32::consts::SQRT_2)
+            ]
+        );
+        // Ties are sorted by tile, left to right.
+        let distances = spatial_hash.k_nearest(origin, 5, |e| e != a);
+        assert_eq!(
+            distances.iter().map(|(e, _)| *e).collect::<Vec<_>>(),
+            vec![d, stacked, c, b, far]
+        );
+        assert_eq!(
+            spatial_hash.k_nearest(origin, 2, |e| e == b),
+            vec![(b, 2.0)]
+        );
+        assert!(spatial_hash.k_nearest(origin, 0, |_| true).is_empty());
+        assert_eq!(
+            spatial_hash.k_nearest(Point::new(90, -40), 1, |_| true)[0].0,
+            far
+        );
+    }
+    #[test]
+    fn palette_colors() {
+        let mut palette = Palette::default();
+        palette.set("grass", RGBA::named(GREEN));
+        assert_eq!(palette.color("grass"), RGBA::named(GREEN));
+        assert_eq!(palette.color("missing"), Palette::FALLBACK);
+
+        let sprite = Sprite::themed(to_cp437('"'), "grass", Some("missing"), &palette);
+        assert_eq!(sprite.fg, RGBA::named(GREEN));
+        assert_eq!(sprite.bg, Some(Palette::FALLBACK));
+        assert_eq!(Sprite::themed(0, "grass", None, &palette).bg, None);
+    }
+    #[test]
+    fn seeded_rng() {
+        let mut a = GameRng::new(42);
+        let mut b = GameRng::new(42);
+        let first = (0..10).map(|_| a.next_u64()).collect::<Vec<_>>();
+        assert_eq!(first, (0..10).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(GameRng::new(43).next_u64(), first[0]);
+
+        let saved = a.state();
+        let next = (a.range(-3, 3), a.next_f32(), a.chance(0.5));
+        let mut restored = GameRng::default();
+        restored.set_state(saved);
+        assert_eq!(
+            (
+                restored.range(-3, 3),
+                restored.next_f32(),
+                restored.chance(0.5)
+            ),
+            next
+        );
+
+        for _ in 0..100 {
+            let v = a.range(-3, 3);
+            assert!((-3..3).contains(&v));
+            let f = a.next_f32();
+            assert!((0.0..1.0).contains(&f));
+        }
+        assert_eq!(a.range(5, 5), 5);
+        assert!(!a.chance(0.0));
+        assert!(a.chance(1.0));
+        let mut v = (0..10).collect::<Vec<_>>();
+        a.shuffle(&mut v);
+        v.sort();
+        assert_eq!(v, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The saved components of a single entity.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    /// The position of the entity.
+    pub position: Option<Point>,
+    /// The ascii sprite of the entity.
+    pub sprite: Option<Sprite>,
+    /// The 2d sprite of the entity.
+    pub sprite_index: Option<SpriteIndex>,
+    /// The direction the entity is facing.
+    pub direction: Option<Direction>,
+    /// The destination of the entity's ai.
+    pub destination: Option<AiDestination>,
+}
+
+/// A save game: the entities having any of the components of `EntitySnapshot`,
+/// the `CollisionResource` and the `GameRng`.
+/// Serialize it with any serde format to write it to a file.
+///
+/// `Entity` ids aren't stable across runs, so entities are saved in a list instead.
+/// `capture` and `restore` both give the entities in the order of that list, which
+/// can be used to remap entity references kept by the game.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    /// The saved entities.
+    pub entities: Vec<EntitySnapshot>,
+    /// The saved collision map.
+    pub collision: Option<CollisionResource>,
+    /// The saved random number generator.
+    pub rng: Option<GameRng>,
+}
+
+impl WorldSnapshot {
+    /// Saves the world. Missing resources are treated as empty.
+    /// Returns the saved entities in the same order as `WorldSnapshot::entities`.
+    pub fn capture(world: &World) -> (Self, Vec<Entity>) {
+        let mut snapshot = Self::default();
+        let mut captured = vec![];
+        if let Ok(collision) = world.get::<Option<CollisionResource>>() {
+            snapshot.collision = collision.clone();
+        }
+        if let Ok(rng) = world.get::<GameRng>() {
+            snapshot.rng = Some(rng.clone());
+        }
+        let entities = match world.get::<Entities>() {
+            Ok(entities) => entities,
+            Err(_) => return (snapshot, captured),
+        };
+        let positions = world.get::<Components<Point>>().ok();
+        let sprites = world.get::<Components<Sprite>>().ok();
+        let sprite_indices = world.get::<Components<SpriteIndex>>().ok();
+        let directions = world.get::<Components<Direction>>().ok();
+        let destinations = world.get::<Components<AiDestination>>().ok();
+        let mut candidates = vec![];
+        let mut seen = HashSet::new();
+        let mut add = |e: Option<Entity>| {
+            let e = e.unwrap();
+            if seen.insert(e) {
+                candidates.push(e);
+            }
+        };
+        if let Some(positions) = positions.as_deref() {
+            for (e, _) in join!(&entities && &positions) {
+                add(e);
+            }
+        }
+        if let Some(sprites) = sprites.as_deref() {
+            for (e, _) in join!(&entities && &sprites) {
+                add(e);
+            }
+        }
+        if let Some(sprite_indices) = sprite_indices.as_deref() {
+            for (e, _) in join!(&entities && &sprite_indices) {
+                add(e);
+            }
+        }
+        if let Some(directions) = directions.as_deref() {
+            for (e, _) in join!(&entities && &directions) {
+                add(e);
+            }
+        }
+        if let Some(destinations) = destinations.as_deref() {
+            for (e, _) in join!(&entities && &destinations) {
+                add(e);
+            }
+        }
+        for e in candidates {
+            let entity = EntitySnapshot {
+                position: positions.as_deref().and_then(|c| c.get(e).copied()),
+                sprite: sprites.as_deref().and_then(|c| c.get(e).copied()),
+                sprite_index: sprite_indices.as_deref().and_then(|c| c.get(e).copied()),
+                direction: directions.as_deref().and_then(|c| c.get(e).copied()),
+                destination: destinations.as_deref().and_then(|c| c.get(e).copied()),
+            };
+            snapshot.entities.push(entity);
+            captured.push(e);
+        }
+        (snapshot, captured)
+    }
+
+    /// Loads the save into the world, creating a new entity for each saved one and
+    /// initializing the resources it needs.
+    /// The `CollisionResource` is replaced by the saved one, and so is the `GameRng`
+    /// if one was saved.
+    /// Returns the created entities in the same order as `WorldSnapshot::entities`.
+    pub fn restore(&self, world: &mut World) -> Vec<Entity> {
+        world.initialize::<Entities>();
+        world.initialize::<Components<Point>>();
+        world.initialize::<Components<Sprite>>();
+        world.initialize::<Components<SpriteIndex>>();
+        world.initialize::<Components<Direction>>();
+        world.initialize::<Components<AiDestination>>();
+        world.initialize::<Option<CollisionResource>>();
+        *world.get_mut::<Option<CollisionResource>>().unwrap() = self.collision.clone();
+        if let Some(rng) = &self.rng {
+            world.initialize::<GameRng>();
+            *world.get_mut::<GameRng>().unwrap() = rng.clone();
+        }
+
+        let mut entities = world.get_mut::<Entities>().unwrap();
+        let mut positions = world.get_mut::<Components<Point>>().unwrap();
+        let mut sprites = world.get_mut::<Components<Sprite>>().unwrap();
+        let mut sprite_indices = world.get_mut::<Components<SpriteIndex>>().unwrap();
+        let mut directions = world.get_mut::<Components<Direction>>().unwrap();
+        let mut destinations = world.get_mut::<Components<AiDestination>>().unwrap();
+        let mut created = vec![];
+        for saved in &self.entities {
+            let e = entities.create();
+            if let Some(position) = saved.position {
+                positions.insert(e, position);
+            }
+            if let Some(sprite) = saved.sprite {
+                sprites.insert(e, sprite);
+            }
+            if let Some(sprite_index) = saved.sprite_index {
+                sprite_indices.insert(e, sprite_index);
+            }
+            if let Some(direction) = saved.direction {
+                directions.insert(e, direction);
+            }
+            if let Some(destination) = saved.destination {
+                destinations.insert(e, destination);
+            }
+            created.push(e);
+        }
+        created
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn capture_and_restore() {
+        let mut world = World::default();
+        world.initialize::<Entities>();
+        world.initialize::<Components<Point>>();
+        world.initialize::<Components<Direction>>();
+        world.initialize::<Components<AiDestination>>();
+        world.initialize::<GameRng>();
+        world.get_mut::<GameRng>().unwrap().next_u64();
+        let mut map = CollisionMap::new(5, 5);
+        map.set(1, 1);
+        world.initialize::<Option<CollisionResource>>();
+        *world.get_mut::<Option<CollisionResource>>().unwrap() =
+            Some(CollisionResource::new(map, Point::new(0, 0)));
+        let (unsaved, player) = {
+            let mut entities = world.get_mut::<Entities>().unwrap();
+            (entities.create(), entities.create())
+        };
+        world
+            .get_mut::<Components<Point>>()
+            .unwrap()
+            .insert(player, Point::new(2, 3));
+        world
+            .get_mut::<Components<Direction>>()
+            .unwrap()
+            .insert(player, Direction::West);
+        world
+            .get_mut::<Components<AiDestination>>()
+            .unwrap()
+            .insert(player, AiDestination::new(Point::new(4, 4)));
+
+        let (snapshot, captured) = WorldSnapshot::capture(&world);
+        assert_eq!(captured, vec![player]);
+        assert_ne!(captured[0], unsaved);
+
+        let mut loaded = World::default();
+        let created = WorldSnapshot::restore(&snapshot, &mut loaded);
+        assert_eq!(created.len(), 1);
+        let e = created[0];
+        assert_eq!(
+            loaded.get::<Components<Point>>().unwrap().get(e),
+            Some(&Point::new(2, 3))
+        );
+        assert_eq!(
+            loaded.get::<Components<Direction>>().unwrap().get(e),
+            Some(&Direction::West)
+        );
+        assert!(loaded.get::<Components<Sprite>>().unwrap().get(e).is_none());
+        assert!(loaded
+            .get::<Option<CollisionResource>>()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .map
+            .is_set(1, 1));
+        assert_eq!(
+            *loaded.get::<GameRng>().unwrap(),
+            *world.get::<GameRng>().unwrap()
+        );
+        assert!(WorldSnapshot::capture(&loaded) == (snapshot, created));
+    }
+    #[test]
+    fn json_round_trip() {
+        let mut map = CollisionMap::new(70, 3);
+        map.set(1, 1);
+        map.set(69, 2);
+        map.set_cost(3, 0, 2.5);
+        map.set_diagonal_movement(DiagonalMovement::IfBothOpen);
+        map.set_wrap(true);
+        let mut rng = GameRng::new(7);
+        rng.next_u64();
+        let snapshot = WorldSnapshot {
+            entities: vec![EntitySnapshot {
+                position: Some(Point::new(2, 1)),
+                direction: Some(Direction::North),
+                destination: Some(AiDestination::new(Point::new(5, 0))),
+                ..Default::default()
+            }],
+            collision: Some(CollisionResource::new(map.clone(), Point::new(-4, 8))),
+            rng: Some(rng.clone()),
+        };
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        // The collisions are saved as words of bits rather than tile by tile.
+        assert!(json.contains("\"words\""));
+        let loaded: WorldSnapshot = serde_json::from_str(&json).unwrap();
+        assert!(loaded == snapshot);
+        let collision = loaded.collision.as_ref().unwrap();
+        assert_eq!(collision.position, Point::new(-4, 8));
+        assert!(collision.map == map);
+        assert!(collision.map.is_set(69, 2) && !collision.map.is_set(68, 2));
+        assert_eq!(collision.map.cost_of(3, 0), 2.5);
+        // The generator continues with the same numbers.
+        let mut loaded_rng = loaded.rng.unwrap();
+        assert_eq!(loaded_rng.next_u64(), rng.next_u64());
+    }
+}
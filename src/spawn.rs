@@ -0,0 +1,102 @@
+use crate::*;
+
+/// Creates an entity and inserts its components one at a time.
+/// The storages of the components are initialized in the `World` when needed.
+pub struct EntityBuilder<'a> {
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl<'a> EntityBuilder<'a> {
+    /// Creates a new entity in the world, without any component.
+    pub fn new(world: &'a mut World) -> Self {
+        world.initialize::<Entities>();
+        let entity = world.get_mut::<Entities>().unwrap().create();
+        EntityBuilder { world, entity }
+    }
+
+    /// Inserts a component, replacing the one of the same type if any.
+    pub fn with<T: Send + Sync + 'static>(self, component: T) -> Self {
+        self.world.initialize::<Components<T>>();
+        self.world
+            .get_mut::<Components<T>>()
+            .unwrap()
+            .insert(self.entity, component);
+        self
+    }
+
+    /// The entity being built.
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Finishes building and gives the entity.
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}
+
+/// Starts building a static prop, such as a wall, a tree or a chest: it has a
+/// position, a `Sprite` and a `Collision`, on the default `Layer` 0.
+pub fn spawn_prop(world: &mut World, pos: Point, sprite: Sprite) -> EntityBuilder<'_> {
+    EntityBuilder::new(world)
+        .with(pos)
+        .with(sprite)
+        .with(Collision)
+}
+
+/// Starts building a creature that moves around: it has a position, a `Sprite`
+/// drawn on `Layer` 1 above the props, a `Collision`, and a `Direction` kept
+/// towards its movement by `FaceMovement`.
+/// Insert an `AiDestination`, a `GotoEntity` or a `GotoStraight` to move it.
+pub fn spawn_creature(world: &mut World, pos: Point, sprite: Sprite) -> EntityBuilder<'_> {
+    EntityBuilder::new(world)
+        .with(pos)
+        .with(sprite)
+        .with(Layer(1))
+        .with(Collision)
+        .with(Direction::South)
+        .with(FaceMovement::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn archetypes() {
+        let mut world = World::default();
+        let sprite = Sprite {
+            glyph: to_cp437('T'),
+            fg: RGBA::named(GREEN),
+            bg: None,
+        };
+        let tree = spawn_prop(&mut world, Point::new(1, 2), sprite).build();
+        let orc = spawn_creature(&mut world, Point::new(4, 4), sprite)
+            .with(AiDestination::new(Point::new(0, 0)))
+            .build();
+        assert_ne!(tree, orc);
+
+        let positions = world.get::<Components<Point>>().unwrap();
+        assert_eq!(positions.get(tree), Some(&Point::new(1, 2)));
+        assert_eq!(positions.get(orc), Some(&Point::new(4, 4)));
+        let collisions = world.get::<Components<Collision>>().unwrap();
+        assert!(collisions.get(tree).is_some() && collisions.get(orc).is_some());
+        let layers = world.get::<Components<Layer>>().unwrap();
+        assert_eq!(layers.get(tree), None);
+        assert_eq!(layers.get(orc), Some(&Layer(1)));
+        assert!(world
+            .get::<Components<FaceMovement>>()
+            .unwrap()
+            .get(orc)
+            .is_some());
+        assert!(world
+            .get::<Components<AiDestination>>()
+            .unwrap()
+            .get(orc)
+            .is_some());
+        assert_eq!(
+            world.get::<Components<Sprite>>().unwrap().get(tree),
+            Some(&sprite)
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::*;
+
+/// Sends an `Adjacent` event when an entity of the `SpatialHash` ends up
+/// orthogonally next to an entity having an `AdjacencyTrigger`, whichever of the
+/// two moved. Each pair sends a single event until they stop being adjacent.
+/// Should run after `spatial_hash_system`.
+pub fn adjacency_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    spatial_hash: &SpatialHash,
+    triggers: &mut Components<AdjacencyTrigger>,
+    events: &mut Vec<Adjacent>,
+) -> SystemResult {
+    for (e, pos, trigger) in join!(&entities && &positions && &mut triggers) {
+        let e = e.unwrap();
+        let pos = *pos.unwrap();
+        let trigger = trigger.unwrap();
+        let mut adjacent = vec![];
+        for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)].iter() {
+            for other in spatial_hash.entities_at(Point::new(pos.x + dx, pos.y + dy)) {
+                if *other != e && !adjacent.contains(other) {
+                    adjacent.push(*other);
+                }
+            }
+        }
+        for other in adjacent.iter() {
+            if !trigger.adjacent.contains(other) {
+                events.push(Adjacent::new(e, *other));
+            }
+        }
+        trigger.adjacent = adjacent;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn adjacent_once() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut triggers = Components::<AdjacencyTrigger>::default();
+        let mut spatial_hash = SpatialHash::default();
+        let mut events = vec![];
+
+        let npc = entities.create();
+        positions.insert(npc, Point::new(5, 5));
+        triggers.insert(npc, AdjacencyTrigger::new());
+        let player = entities.create();
+        positions.insert(player, Point::new(3, 5));
+
+        let mut run = |positions: &mut Components<Point>, events: &mut Vec<Adjacent>| {
+            spatial_hash_system(&entities, positions, &mut spatial_hash).unwrap();
+            adjacency_system(&entities, positions, &spatial_hash, &mut triggers, events).unwrap();
+        };
+        run(&mut positions, &mut events);
+        assert!(events.is_empty());
+
+        *positions.get_mut(player).unwrap() = Point::new(4, 5);
+        run(&mut positions, &mut events);
+        assert_eq!(events, vec![Adjacent::new(npc, player)]);
+
+        // Staying next to the npc doesn't send it again.
+        run(&mut positions, &mut events);
+        run(&mut positions, &mut events);
+        assert_eq!(events.len(), 1);
+
+        // Diagonals aren't adjacent, so coming back sends it again.
+        *positions.get_mut(player).unwrap() = Point::new(4, 4);
+        run(&mut positions, &mut events);
+        assert_eq!(events.len(), 1);
+        *positions.get_mut(player).unwrap() = Point::new(5, 4);
+        run(&mut positions, &mut events);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1], Adjacent::new(npc, player));
+    }
+}
@@ -2,23 +2,283 @@ use crate::*;
 
 /// Uses a `CollisionResource` and a `AiPath` component to move an entity
 /// one step towards the desired destination.
+/// Entities having a `MovementClass` use its layer in the `CollisionMapStack`.
+/// If the next step became blocked since the path was calculated, the entity
+/// doesn't move and a `BlockedMove` event is sent.
+/// Entities having `ActionPoints` only move when they have a point to spend.
+/// Entities being pushed by a `Knockback` don't move, nor do entities moving with a
+/// `GotoStraight`, unless it uses `pathfind_fallback`: the `AiPath` is then the
+/// detour it inserted. See `Knockback` for the precedence between movements.
+/// Entities having a `MoveCooldown` move at its pace using the frame duration given
+/// by `Time::delta_time`, taking as many steps as the frame lasted, even none.
+#[allow(clippy::too_many_arguments)]
 pub fn ai_movement_system(
+    entities: &Entities,
+    time: &Time,
     global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
     positions: &mut Components<Point>,
     paths: &mut Components<AiPath>,
+    action_points: &mut Components<ActionPoints>,
+    knockbacks: &Components<Knockback>,
+    straights: &Components<GotoStraight>,
+    cooldowns: &mut Components<MoveCooldown>,
+    blocked: &mut Vec<BlockedMove>,
 ) -> SystemResult {
     // doesn't handle two entities that want to go to the same tile.
-    for (pos, path) in join!(&mut positions && &mut paths) {
+    for (e, pos, path) in join!(&entities && &mut positions && &mut paths) {
+        let e = e.unwrap();
         let pos = pos.unwrap();
         let path = path.unwrap();
-        if path.path.success && path.path.steps.len() > 1 {
-            let dest = path.path.steps.remove(1);
-            let (x, y) = global_map.as_ref().unwrap().map.position_of(dest as u32);
-            *pos = Point::new(
-                x as i32 + global_map.as_ref().unwrap().position.x,
-                y as i32 + global_map.as_ref().unwrap().position.y,
+        if knockbacks.get(e).is_some()
+            || straights
+                .get(e)
+                .map(|s| !s.pathfind_fallback)
+                .unwrap_or(false)
+        {
+            continue;
+        }
+        let mut cooldown = cooldowns.get_mut(e);
+        let mut steps = if let Some(cooldown) = cooldown.as_mut() {
+            cooldown.advance(time.delta_time().as_secs_f32())
+        } else {
+            1
+        };
+        while steps > 0 && path.path.success && path.path.steps.len() > 1 {
+            if action_points
+                .get(e)
+                .map(|p| p.current == 0)
+                .unwrap_or(false)
+            {
+                break;
+            }
+            let global_map = match stack.layer_or(global_map.as_ref(), classes.get(e)) {
+                Some(map) => map,
+                None => break,
+            };
+            let (x, y) = global_map.map.position_of(path.path.steps[1] as u32);
+            let next = Point::new(
+                x as i32 + global_map.position.x,
+                y as i32 + global_map.position.y,
             );
+            if global_map.is_blocked(&next) {
+                if let Some(direction) = Direction::between(*pos, next) {
+                    blocked.push(BlockedMove::new(e, next, direction));
+                }
+                break;
+            }
+            if let Some(points) = action_points.get_mut(e) {
+                points.spend();
+            }
+            path.path.steps.remove(1);
+            *pos = next;
+            steps -= 1;
+        }
+        // Time left while standing still isn't kept for later, so that entities
+        // don't rush through several tiles once they can move again.
+        if steps > 0 {
+            if let Some(cooldown) = cooldown {
+                cooldown.elapsed = 0.0;
+            }
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+    #[test]
+    fn blocked_step() {
+        let mut entities = Entities::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut blocked = vec![];
+        let mut global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let e = entities.create();
+        dests.insert(e, AiDestination::new(Point::new(1, 3)));
+        positions.insert(e, Point::new(1, 1));
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut PathingBudget::default(),
+            &mut Components::default(),
+        )
+        .unwrap();
+
+        global_map.as_mut().unwrap().map.set(1, 2);
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut blocked,
+        )
+        .unwrap();
+        assert_eq!(*positions.get(e).unwrap(), Point::new(1, 1));
+        assert_eq!(
+            blocked,
+            vec![BlockedMove::new(e, Point::new(1, 2), Direction::South)]
+        );
+
+        global_map.as_mut().unwrap().map.unset(1, 2);
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut blocked,
+        )
+        .unwrap();
+        assert_eq!(*positions.get(e).unwrap(), Point::new(1, 2));
+        assert_eq!(blocked.len(), 1);
+    }
+    #[test]
+    fn no_global_map() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let e = entities.create();
+        positions.insert(e, Point::new(0, 0));
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &None,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut Components::default(),
+            &mut Components::default(),
+            &Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut vec![],
+        )
+        .unwrap();
+    }
+    #[test]
+    fn straight_takes_precedence() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut straights = Components::<GotoStraight>::default();
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let (straight, fallback) = (entities.create(), entities.create());
+        for e in &[straight, fallback] {
+            positions.insert(*e, Point::new(0, 0));
+            let path = global_map
+                .as_ref()
+                .unwrap()
+                .find_path(&Point::new(0, 0), &Point::new(0, 3))
+                .unwrap();
+            paths.insert(*e, AiPath::new(path));
+        }
+        straights.insert(straight, GotoStraight::new(Point::new(3, 0), 1.0));
+        let mut detour = GotoStraight::new(Point::new(3, 0), 1.0);
+        detour.pathfind_fallback = true;
+        straights.insert(fallback, detour);
+
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &Components::default(),
+            &straights,
+            &mut Components::default(),
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(*positions.get(straight).unwrap(), Point::new(0, 0));
+        assert_eq!(*positions.get(fallback).unwrap(), Point::new(0, 1));
+    }
+    #[test]
+    fn move_cooldown_pace() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut cooldowns = Components::<MoveCooldown>::default();
+        let mut blocked = vec![];
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let e = entities.create();
+        positions.insert(e, Point::new(0, 0));
+        let path = global_map
+            .as_ref()
+            .unwrap()
+            .find_path(&Point::new(0, 0), &Point::new(9, 0))
+            .unwrap();
+        paths.insert(e, AiPath::new(path));
+        cooldowns.insert(e, MoveCooldown::new(0.25));
+
+        let mut run = |millis: u64,
+                       positions: &mut Components<Point>,
+                       paths: &mut Components<AiPath>,
+                       cooldowns: &mut Components<MoveCooldown>| {
+            let mut time = Time::default();
+            time.advance_frame(Duration::from_millis(millis));
+            ai_movement_system(
+                &entities,
+                &time,
+                &global_map,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                positions,
+                paths,
+                &mut Components::default(),
+                &Components::default(),
+                &Components::default(),
+                cooldowns,
+                &mut blocked,
+            )
+            .unwrap();
+            positions.get(e).unwrap().x
+        };
+        // The leftover time of each frame carries to the next one.
+        assert_eq!(run(100, &mut positions, &mut paths, &mut cooldowns), 0);
+        assert_eq!(run(100, &mut positions, &mut paths, &mut cooldowns), 0);
+        assert_eq!(run(100, &mut positions, &mut paths, &mut cooldowns), 1);
+        // A long frame moves several tiles.
+        assert_eq!(run(1000, &mut positions, &mut paths, &mut cooldowns), 5);
+        assert!((cooldowns.get(e).unwrap().elapsed - 0.05).abs() < 0.0001);
+        // Reaching the end doesn't keep the unused time.
+        assert_eq!(run(2000, &mut positions, &mut paths, &mut cooldowns), 9);
+        assert_eq!(cooldowns.get(e).unwrap().elapsed, 0.0);
+    }
+}
@@ -1,35 +1,75 @@
 use crate::*;
 
 /// Calculates a path from the entity's current position towards the specified
-/// `AiDestination` and inserts it in a `AiPath` component.
-/// It uses a `CollisionResource` to take collisions into account.
+/// `AiDestination` and inserts it in a `AiPath` component, like a `PathRequest`
+/// renewed every frame.
+/// It uses a `CollisionResource` to take collisions into account, or the layer of
+/// the entity's `MovementClass` in the `CollisionMapStack`.
+/// Targets outside of the map are clamped to its edge. Targets colliding with the
+/// map are replaced by the nearest open tile when `AiDestination::snap_to_open` is
+/// set, otherwise the entity has no `AiPath`. Entities outside of the map are
+/// skipped, and logged once each time they leave it.
+/// At most `PathingBudget::max_per_frame` paths are calculated each frame, the
+/// other entities are marked with `PathPending` and wait for a later frame.
+#[allow(clippy::too_many_arguments)]
 pub fn ai_pathing_system(
     entities: &Entities,
     dests: &Components<AiDestination>,
     global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
     positions: &Components<Point>,
     paths: &mut Components<AiPath>,
+    budget: &mut PathingBudget,
+    pendings: &mut Components<PathPending>,
 ) -> SystemResult {
-    for (e, pos, dest) in join!(&entities && &positions && &dests) {
-        let pos = pos.unwrap();
-        let dest = dest.unwrap();
-        let mut path = AiPath::default();
-        let global_map = global_map.as_ref().unwrap();
+    for (e, _, _) in join!(&entities && &positions && &dests) {
+        let e = e.unwrap();
+        if pendings.get(e).is_none() {
+            budget.push(e);
+            pendings.insert(e, PathPending);
+        }
+    }
+    for _ in 0..budget.max_per_frame {
+        let e = if let Some(e) = budget.pop() {
+            e
+        } else {
+            break;
+        };
+        pendings.remove(e);
+        let (pos, dest) = match (positions.get(e), dests.get(e)) {
+            (Some(pos), Some(dest)) if entities.is_alive(e) => (pos, dest),
+            _ => {
+                budget.forget_outside(e);
+                continue;
+            }
+        };
+        let global_map = match stack.layer_or(global_map.as_ref(), classes.get(e)) {
+            Some(map) => map,
+            None => continue,
+        };
         if pos.x == dest.target.x && pos.y == dest.target.y {
             continue;
         }
-        // TODO Safety check for < 0 or out of map bounds
-        let d = global_map.map.index_of(
-            (pos.x - global_map.position.x) as u32,
-            (pos.y - global_map.position.y) as u32,
-        );
-        let t = global_map.map.index_of(
-            (dest.target.x - global_map.position.x) as u32,
-            (dest.target.y - global_map.position.y) as u32,
-        );
-        let p = a_star_search(d, t, &global_map.map);
-        path.path = p;
-        paths.insert(e.unwrap(), path);
+        if !global_map.is_inside(pos) {
+            if budget.mark_outside(e) {
+                eprintln!(
+                    "Entity at {:?} is outside of the collision map, it can't path to {:?}.",
+                    pos, dest.target
+                );
+            }
+            continue;
+        }
+        budget.forget_outside(e);
+        let request = PathRequest {
+            goal: dest.target,
+            snap_to_open: dest.snap_to_open,
+        };
+        if let Some(p) = request.path_from(global_map, pos) {
+            paths.insert(e, AiPath::new(p));
+        } else {
+            paths.remove(e);
+        }
     }
     Ok(())
 }
@@ -52,7 +92,18 @@ mod tests {
         dests.insert(e, AiDestination::new(Point::new(1, 3)));
         positions.insert(e, Point::new(1, 1));
 
-        ai_pathing_system(&entities, &dests, &global_map, &positions, &mut paths).unwrap();
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut PathingBudget::default(),
+            &mut Components::default(),
+        )
+        .unwrap();
         let steps = paths.get(e).unwrap().path.steps.clone();
         assert_eq!(steps.len(), 3);
         assert_eq!(
@@ -67,4 +118,201 @@ mod tests {
                 .collect::<Vec<_>>()
         );
     }
+    #[test]
+    fn movement_class_layer() {
+        let mut entities = Entities::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut positions = Components::<Point>::default();
+        let mut classes = Components::<MovementClass>::default();
+        let mut paths = Components::<AiPath>::default();
+        // A wall splits the ground, but flyers go over it.
+        let mut ground = CollisionMap::new(5, 5);
+        ground.set_rect(2, 0, 1, 5);
+        let global_map = Some(CollisionResource::new(ground, Point::new(0, 0)));
+        let mut stack = CollisionMapStack::default();
+        let air = MovementClass("air".to_string());
+        stack.insert(
+            air.clone(),
+            CollisionResource::new(CollisionMap::new(5, 5), Point::new(0, 0)),
+        );
+        assert_eq!(stack.for_class(&air).unwrap().count_set(), 0);
+        assert!(stack
+            .for_class(&MovementClass("water".to_string()))
+            .is_none());
+
+        let walker = entities.create();
+        let flyer = entities.create();
+        for e in &[walker, flyer] {
+            dests.insert(*e, AiDestination::new(Point::new(4, 0)));
+            positions.insert(*e, Point::new(0, 0));
+        }
+        classes.insert(flyer, air);
+
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &global_map,
+            &stack,
+            &classes,
+            &positions,
+            &mut paths,
+            &mut PathingBudget::default(),
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert!(!paths.get(walker).unwrap().path.success);
+        assert_eq!(paths.get(flyer).unwrap().remaining(), 4);
+
+        // The class layer is enough without a global map.
+        let mut paths = Components::<AiPath>::default();
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &None,
+            &stack,
+            &classes,
+            &positions,
+            &mut paths,
+            &mut PathingBudget::default(),
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert!(paths.get(walker).is_none());
+        assert_eq!(paths.get(flyer).unwrap().remaining(), 4);
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &None,
+            &stack,
+            &classes,
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(*positions.get(flyer).unwrap(), Point::new(1, 0));
+    }
+    #[test]
+    fn invalid_destinations() {
+        let mut entities = Entities::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut map = CollisionMap::new(5, 5);
+        map.set(4, 2);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let outside = entities.create();
+        dests.insert(outside, AiDestination::new(Point::new(9, -3)));
+        positions.insert(outside, Point::new(0, 0));
+        let wall = entities.create();
+        dests.insert(wall, AiDestination::new(Point::new(4, 2)));
+        positions.insert(wall, Point::new(0, 2));
+        let snap = entities.create();
+        let mut dest = AiDestination::new(Point::new(4, 2));
+        dest.snap_to_open = true;
+        dests.insert(snap, dest);
+        positions.insert(snap, Point::new(0, 2));
+        let lost = entities.create();
+        dests.insert(lost, AiDestination::new(Point::new(1, 1)));
+        positions.insert(lost, Point::new(-1, 0));
+        let mut budget = PathingBudget::default();
+
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut budget,
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert_eq!(paths.get(outside).unwrap().path.destination, 4);
+        assert!(paths.get(wall).is_none());
+        assert_eq!(paths.get(snap).unwrap().path.destination, 4 + 5);
+        assert!(paths.get(lost).is_none());
+        // Only logged when leaving the map, not on every frame.
+        assert!(!budget.mark_outside(lost));
+
+        *positions.get_mut(lost).unwrap() = Point::new(0, 0);
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut budget,
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert!(paths.get(lost).is_some());
+        assert!(budget.mark_outside(lost));
+    }
+    #[test]
+    fn budget_queue() {
+        let mut entities = Entities::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut pendings = Components::<PathPending>::default();
+        let mut budget = PathingBudget::new(2);
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let crowd = (0..5)
+            .map(|i| {
+                let e = entities.create();
+                dests.insert(e, AiDestination::new(Point::new(9, 9)));
+                positions.insert(e, Point::new(i, 0));
+                e
+            })
+            .collect::<Vec<_>>();
+
+        let run = |paths: &mut Components<AiPath>,
+                   budget: &mut PathingBudget,
+                   pendings: &mut Components<PathPending>| {
+            ai_pathing_system(
+                &entities,
+                &dests,
+                &global_map,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                &positions,
+                paths,
+                budget,
+                pendings,
+            )
+            .unwrap();
+        };
+        let has_path = |paths: &Components<AiPath>| {
+            crowd
+                .iter()
+                .map(|e| paths.get(*e).is_some())
+                .collect::<Vec<_>>()
+        };
+        run(&mut paths, &mut budget, &mut pendings);
+        assert_eq!(has_path(&paths), vec![true, true, false, false, false]);
+        assert!(pendings.get(crowd[0]).is_none());
+        assert!(pendings.get(crowd[2]).is_some());
+        assert_eq!(budget.queued(), 3);
+
+        run(&mut paths, &mut budget, &mut pendings);
+        assert_eq!(has_path(&paths), vec![true, true, true, true, false]);
+        // The first entities are queued again behind the ones still waiting.
+        run(&mut paths, &mut budget, &mut pendings);
+        assert_eq!(has_path(&paths), vec![true; 5]);
+        assert!(pendings.get(crowd[4]).is_none());
+        assert!(pendings.get(crowd[1]).is_some());
+    }
 }
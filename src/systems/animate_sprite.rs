@@ -0,0 +1,82 @@
+use crate::*;
+
+/// Advances `AnimatedSprite`s and writes the glyph of the current frame in the
+/// entity's `Sprite`.
+pub fn animate_sprite_system(
+    time: &Time,
+    animations: &mut Components<AnimatedSprite>,
+    sprites: &mut Components<Sprite>,
+) -> SystemResult {
+    for (anim, sprite) in join!(&mut animations && &mut sprites) {
+        let anim = anim.unwrap();
+        let sprite = sprite.unwrap();
+        if anim.frames.is_empty() {
+            continue;
+        }
+        anim.elapsed += time.delta_time().as_secs_f32();
+        let total = anim.frame_duration * anim.frames.len() as f32;
+        if anim.mode == AnimationMode::Loop && total > 0.0 {
+            anim.elapsed %= total;
+        }
+        sprite.glyph = anim.frames[anim.current_frame()];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+    #[test]
+    fn loop_wraps() {
+        let mut entities = Entities::default();
+        let mut animations = Components::<AnimatedSprite>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut time = Time::default();
+        time.advance_frame(Duration::from_millis(250));
+
+        let e = entities.create();
+        animations.insert(
+            e,
+            AnimatedSprite::new(vec![1, 2, 3], 0.5, AnimationMode::Loop),
+        );
+        sprites.insert(
+            e,
+            Sprite {
+                glyph: 0,
+                fg: RGBA::named(WHITE),
+                bg: Some(RGBA::named(BLACK)),
+            },
+        );
+        let mut glyphs = vec![];
+        for _ in 0..8 {
+            animate_sprite_system(&time, &mut animations, &mut sprites).unwrap();
+            glyphs.push(sprites.get(e).unwrap().glyph);
+        }
+        assert_eq!(glyphs, vec![1, 2, 2, 3, 3, 1, 1, 2]);
+        assert!(animations.get(e).unwrap().elapsed < 1.5);
+    }
+    #[test]
+    fn once_stays_on_last_frame() {
+        let mut entities = Entities::default();
+        let mut animations = Components::<AnimatedSprite>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut time = Time::default();
+        time.advance_frame(Duration::from_secs(1));
+
+        let e = entities.create();
+        animations.insert(e, AnimatedSprite::new(vec![1, 2], 0.5, AnimationMode::Once));
+        sprites.insert(
+            e,
+            Sprite {
+                glyph: 0,
+                fg: RGBA::named(WHITE),
+                bg: Some(RGBA::named(BLACK)),
+            },
+        );
+        for _ in 0..3 {
+            animate_sprite_system(&time, &mut animations, &mut sprites).unwrap();
+        }
+        assert_eq!(sprites.get(e).unwrap().glyph, 2);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::*;
+
+/// Moves the `Camera` so that the entity having the `CameraTarget` component stays
+/// centered on screen.
+/// The camera only moves once the target leaves the `Camera::dead_zone`, which is
+/// measured in world tiles.
+pub fn camera_follow_system(
+    targets: &Components<CameraTarget>,
+    positions: &Components<Point>,
+    camera: &mut Camera,
+) -> SystemResult {
+    for (_, pos) in join!(&targets && &positions) {
+        let pos = pos.unwrap();
+        let visible = camera.visible_size();
+        camera.position.x += follow_offset(camera.position.x, visible.x, camera.dead_zone.x, pos.x);
+        camera.position.y += follow_offset(camera.position.y, visible.y, camera.dead_zone.y, pos.y);
+    }
+    Ok(())
+}
+
+/// Gives how much the camera needs to move on one axis to bring the target back
+/// inside of the dead zone.
+fn follow_offset(position: i32, size: i32, dead_zone: i32, target: i32) -> i32 {
+    let dead_zone = dead_zone.max(1);
+    let min = position + size / 2 - dead_zone / 2;
+    let max = min + dead_zone - 1;
+    if target < min {
+        target - min
+    } else if target > max {
+        target - max
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn follow_target() {
+        let mut entities = Entities::default();
+        let mut targets = Components::<CameraTarget>::default();
+        let mut positions = Components::<Point>::default();
+        let mut camera = Camera::new(Point::new(0, 0), Point::new(20, 10));
+
+        let e = entities.create();
+        targets.insert(e, CameraTarget);
+        positions.insert(e, Point::new(50, 30));
+        camera_follow_system(&targets, &positions, &mut camera).unwrap();
+        assert_eq!(camera.position, Point::new(40, 25));
+
+        camera.dead_zone = Point::new(6, 4);
+        *positions.get_mut(e).unwrap() = Point::new(52, 31);
+        camera_follow_system(&targets, &positions, &mut camera).unwrap();
+        assert_eq!(camera.position, Point::new(40, 25));
+
+        *positions.get_mut(e).unwrap() = Point::new(55, 20);
+        camera_follow_system(&targets, &positions, &mut camera).unwrap();
+        assert_eq!(camera.position, Point::new(43, 17));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::*;
+
+/// Moves the `Camera::shake` offset randomly, within the decaying strength of the
+/// `CameraShake`, using the frame duration given by `Time::delta_time`.
+/// Once the shake is over, it is removed and the offset goes back to (0, 0).
+/// Only rendering is shaken: `Camera::position` and the conversions between world
+/// and screen positions, used for input, don't change.
+/// The offsets are drawn from the `GameRng`, so that replays shake the same way.
+pub fn camera_shake_system(
+    time: &Time,
+    shake: &mut Option<CameraShake>,
+    rng: &mut GameRng,
+    camera: &mut Camera,
+) -> SystemResult {
+    camera.shake = Point::new(0, 0);
+    if let Some(s) = shake.as_mut() {
+        s.elapsed += time.delta_time().as_secs_f32();
+        if s.elapsed >= s.duration {
+            *shake = None;
+            return Ok(());
+        }
+        let strength = s.strength();
+        let mut offset = || ((rng.next_f32() * 2.0 - 1.0) * strength).round() as i32;
+        camera.shake = Point::new(offset(), offset());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+    #[test]
+    fn shake_and_settle() {
+        let mut time = Time::default();
+        time.advance_frame(Duration::from_millis(100));
+        let run = |seed: u64| {
+            let mut shake = Some(CameraShake::new(100.0, 0.5));
+            let mut rng = GameRng::new(seed);
+            let mut camera = Camera::new(Point::new(10, 10), Point::new(20, 10));
+            let mut offsets = vec![];
+            while shake.is_some() {
+                camera_shake_system(&time, &mut shake, &mut rng, &mut camera).unwrap();
+                let strength = shake.map(|s| s.strength()).unwrap_or(0.0);
+                assert!(camera.shake.x.abs() as f32 <= strength.round());
+                assert!(camera.shake.y.abs() as f32 <= strength.round());
+                offsets.push(camera.shake);
+                // Input conversions ignore the shake.
+                assert_eq!(camera.position, Point::new(10, 10));
+                assert_eq!(
+                    camera.world_to_screen(Point::new(11, 12)),
+                    Some(Point::new(1, 2))
+                );
+            }
+            offsets
+        };
+        let offsets = run(7);
+        assert_eq!(offsets.len(), 5);
+        assert_eq!(*offsets.last().unwrap(), Point::new(0, 0));
+        assert!(offsets.iter().any(|o| *o != Point::new(0, 0)));
+        assert_eq!(run(7), offsets);
+    }
+}
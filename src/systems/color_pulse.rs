@@ -0,0 +1,52 @@
+use crate::*;
+
+/// Advances `ColorPulse`s and writes the current color in the `fg` of the entity's
+/// `Sprite`.
+pub fn color_pulse_system(
+    time: &Time,
+    pulses: &mut Components<ColorPulse>,
+    sprites: &mut Components<Sprite>,
+) -> SystemResult {
+    for (pulse, sprite) in join!(&mut pulses && &mut sprites) {
+        let pulse = pulse.unwrap();
+        let sprite = sprite.unwrap();
+        pulse.elapsed += time.delta_time().as_secs_f32();
+        if pulse.period > 0.0 {
+            pulse.elapsed %= pulse.period;
+        }
+        sprite.fg = pulse.color();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::time::Duration;
+    #[test]
+    fn pulse_back_and_forth() {
+        let mut entities = Entities::default();
+        let mut pulses = Components::<ColorPulse>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut time = Time::default();
+        time.advance_frame(Duration::from_millis(500));
+
+        let e = entities.create();
+        let (from, to) = (RGBA::named(BLACK), RGBA::named(WHITE));
+        pulses.insert(e, ColorPulse::new(from, to, 1.0));
+        sprites.insert(
+            e,
+            Sprite {
+                glyph: 0,
+                fg: from,
+                bg: None,
+            },
+        );
+
+        color_pulse_system(&time, &mut pulses, &mut sprites).unwrap();
+        assert!((sprites.get(e).unwrap().fg.r - 1.0).abs() < 0.0001);
+        color_pulse_system(&time, &mut pulses, &mut sprites).unwrap();
+        assert!(sprites.get(e).unwrap().fg.r.abs() < 0.0001);
+        assert!(pulses.get(e).unwrap().elapsed < 1.0);
+    }
+}
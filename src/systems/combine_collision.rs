@@ -2,29 +2,28 @@ use crate::*;
 
 /// Combine individual entity's `CollisionMap` components into one single
 /// `CollisionResource` resource.
+/// Only the dynamic layer of the map is rebuilt, so its static layer is kept.
+/// Entities having `Collision` block every tile of their `Footprint` or `MultiSprite`.
 pub fn combine_collision_system(
+    entities: &Entities,
     positions: &Components<Point>,
     collisions: &Components<Collision>,
+    footprints: &Components<Footprint>,
+    multi_sprites: &Components<MultiSprite>,
     maps: &Components<CollisionMap>,
     global_map: &mut Option<CollisionResource>,
 ) -> SystemResult {
     let global_map = global_map.as_mut().unwrap();
 
-    global_map.map.clear();
+    global_map.map.clear_dynamic();
 
-    for (pos, _) in join!(&positions && &collisions) {
-        let pos = pos.unwrap();
-        let (x, y) = (pos.x, pos.y);
-        if position_inside_rect(
-            x,
-            y,
-            global_map.position.x,
-            global_map.position.y,
-            global_map.map.size().0,
-            global_map.map.size().1,
-        ) {
-            let (t_x, t_y) = (global_map.position.x, global_map.position.y);
-            global_map.map.set((x - t_x) as u32, (y - t_y) as u32);
+    for (e, pos, _) in join!(&entities && &positions && &collisions) {
+        let e = e.unwrap();
+        for tile in Footprint::tiles_of(footprints.get(e), multi_sprites.get(e), *pos.unwrap()) {
+            if global_map.is_inside(&tile) {
+                let (x, y) = global_map.relative_point(&tile);
+                global_map.map.set_dynamic(x, y);
+            }
         }
     }
 
@@ -45,7 +44,9 @@ pub fn combine_collision_system(
                     )
                 {
                     let (t_x, t_y) = (global_map.position.x, global_map.position.y);
-                    global_map.map.set((x - t_x) as u32, (y - t_y) as u32);
+                    global_map
+                        .map
+                        .set_dynamic((x - t_x) as u32, (y - t_y) as u32);
                 }
             }
         }
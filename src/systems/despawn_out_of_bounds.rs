@@ -0,0 +1,54 @@
+use crate::*;
+
+/// Deletes the entities having `DespawnOutOfBounds` whose position is outside of
+/// the `CollisionResource`. Entities without a position are kept.
+pub fn despawn_out_of_bounds_system(
+    entities: &mut Entities,
+    markers: &Components<DespawnOutOfBounds>,
+    positions: &Components<Point>,
+    global_map: &Option<CollisionResource>,
+) -> SystemResult {
+    let global_map = global_map.as_ref().unwrap();
+    let mut v = vec![];
+    for (e, _, pos) in join!(&entities && &markers && &positions) {
+        if !global_map.is_inside(pos.unwrap()) {
+            v.push(e.unwrap());
+        }
+    }
+    for e in v {
+        entities.kill(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn despawn_outside() {
+        let mut entities = Entities::default();
+        let mut markers = Components::<DespawnOutOfBounds>::default();
+        let mut positions = Components::<Point>::default();
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(5, 5),
+            Point::new(-2, -2),
+        ));
+
+        let inside = entities.create();
+        positions.insert(inside, Point::new(2, -2));
+        markers.insert(inside, DespawnOutOfBounds);
+        let outside = entities.create();
+        positions.insert(outside, Point::new(3, 0));
+        markers.insert(outside, DespawnOutOfBounds);
+        let unmarked = entities.create();
+        positions.insert(unmarked, Point::new(10, 10));
+        let no_position = entities.create();
+        markers.insert(no_position, DespawnOutOfBounds);
+
+        despawn_out_of_bounds_system(&mut entities, &markers, &positions, &global_map).unwrap();
+        assert!(entities.is_alive(inside));
+        assert!(!entities.is_alive(outside));
+        assert!(entities.is_alive(unmarked));
+        assert!(entities.is_alive(no_position));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::*;
+
+/// Turns the `Direction` of entities having a `FaceMovement` towards their last
+/// step, whichever system moved them, using `Direction::between`.
+/// The direction only changes when the position changed on the 2d plane, so
+/// entities standing still keep facing the same way, including `Up` or `Down`.
+/// Should run after the movement systems.
+pub fn face_movement_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    faces: &mut Components<FaceMovement>,
+    directions: &mut Components<Direction>,
+) -> SystemResult {
+    for (e, pos, face) in join!(&entities && &positions && &mut faces) {
+        let e = e.unwrap();
+        let pos = *pos.unwrap();
+        let face = face.unwrap();
+        if let Some(last) = face.last {
+            if let (Some(direction), Some(heading)) =
+                (directions.get_mut(e), Direction::between(last, pos))
+            {
+                *direction = heading;
+            }
+        }
+        face.last = Some(pos);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn face_steps() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut faces = Components::<FaceMovement>::default();
+        let mut directions = Components::<Direction>::default();
+
+        let e = entities.create();
+        positions.insert(e, Point::new(5, 5));
+        faces.insert(e, FaceMovement::new());
+        directions.insert(e, Direction::Up);
+        let other = entities.create();
+        positions.insert(other, Point::new(0, 0));
+        directions.insert(other, Direction::North);
+
+        let mut run = |positions: &mut Components<Point>, to: Point| {
+            *positions.get_mut(e).unwrap() = to;
+            *positions.get_mut(other).unwrap() = to;
+            face_movement_system(&entities, positions, &mut faces, &mut directions).unwrap();
+            *directions.get(e).unwrap()
+        };
+        assert_eq!(run(&mut positions, Point::new(5, 5)), Direction::Up);
+        assert_eq!(run(&mut positions, Point::new(5, 5)), Direction::Up);
+        assert_eq!(run(&mut positions, Point::new(6, 5)), Direction::East);
+        assert_eq!(run(&mut positions, Point::new(6, 4)), Direction::North);
+        assert_eq!(run(&mut positions, Point::new(6, 4)), Direction::North);
+        assert_eq!(run(&mut positions, Point::new(5, 4)), Direction::West);
+        assert_eq!(*directions.get(other).unwrap(), Direction::North);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::*;
+
+/// Sets an `AiDestination` away from the entity targeted by `FleeEntity` while it
+/// is closer than `FleeEntity::min_distance`.
+///
+/// The tiles reachable within `2 * min_distance` steps are compared by how far
+/// the threat has to walk to reach them, so that dead ends close to the threat are
+/// avoided. The threat's walk is only searched up to `3 * min_distance`, further
+/// tiles being equally safe, and is shared by the entities fleeing the same threat
+/// on the same map. A cornered entity, which can't get further away, is left
+/// without a new destination.
+///
+/// Entities having a `MovementClass` use its layer in the `CollisionMapStack`
+/// instead of the `CollisionResource`.
+pub fn flee_entity_system(
+    entities: &Entities,
+    flees: &Components<FleeEntity>,
+    global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
+    positions: &Components<Point>,
+    dests: &mut Components<AiDestination>,
+) -> SystemResult {
+    // The walk costs from each threat, with the budget they were searched with.
+    let mut threat_maps = HashMap::<(Entity, Option<&MovementClass>), (f32, _)>::new();
+    for (e, pos, flee) in join!(&entities && &positions && &flees) {
+        let e = e.unwrap();
+        let pos = *pos.unwrap();
+        let flee = flee.unwrap();
+        let threat = match positions.get(flee.entity) {
+            Some(threat) => *threat,
+            None => continue,
+        };
+        let class = classes.get(e);
+        let map = match stack.layer_or(global_map.as_ref(), class) {
+            Some(map) => map,
+            None => continue,
+        };
+        if dist(&pos, &threat) >= flee.min_distance
+            || !map.is_inside(&pos)
+            || !map.is_inside(&threat)
+        {
+            continue;
+        }
+        let to_local = |p: Point| {
+            let (x, y) = map.relative_point(&p);
+            Point::new(x as i32, y as i32)
+        };
+        let radius = 2.0 * flee.min_distance.ceil();
+        let horizon = radius + flee.min_distance.ceil();
+        let from_threat = threat_maps
+            .entry((flee.entity, class))
+            .or_insert((-1.0, HashMap::new()));
+        if from_threat.0 < horizon {
+            *from_threat = (horizon, reachable(&map.map, to_local(threat), horizon));
+        }
+        let from_threat = &from_threat.1;
+        let threat_distance = |p: Point| {
+            from_threat
+                .get(&p)
+                .map(|d| d.min(horizon))
+                .unwrap_or(horizon)
+        };
+        let from_self = reachable(&map.map, to_local(pos), radius);
+        let mut best = (to_local(pos), threat_distance(to_local(pos)), 0.0);
+        for (p, steps) in from_self {
+            let away = threat_distance(p);
+            // Ties are broken by position so that the choice doesn't depend on
+            // the iteration order.
+            if away > best.1 || (away == best.1 && (steps, p.y, p.x) < (best.2, best.0.y, best.0.x))
+            {
+                best = (p, away, steps);
+            }
+        }
+        if best.0 != to_local(pos) {
+            let target = Point::new(best.0.x + map.position.x, best.0.y + map.position.y);
+            dests.insert(e, AiDestination::new(target));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn flee_and_cornered() {
+        let mut entities = Entities::default();
+        let mut flees = Components::<FleeEntity>::default();
+        let mut positions = Components::<Point>::default();
+        let mut dests = Components::<AiDestination>::default();
+        // A corridor with a dead end going up next to the threat.
+        let map = CollisionMap::from_str_layout(
+            "\
+##.#####
+........",
+            '#',
+        )
+        .unwrap();
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let threat = entities.create();
+        positions.insert(threat, Point::new(1, 1));
+        let prey = entities.create();
+        positions.insert(prey, Point::new(2, 1));
+        flees.insert(prey, FleeEntity::new(threat, 4.0));
+        let run = |flees: &_, positions: &_, dests: &mut _| {
+            flee_entity_system(
+                &entities,
+                flees,
+                &global_map,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                positions,
+                dests,
+            )
+            .unwrap()
+        };
+
+        run(&flees, &positions, &mut dests);
+        assert_eq!(dests.get(prey).unwrap().target, Point::new(7, 1));
+
+        // Far enough: no new destination.
+        dests.remove(prey);
+        *positions.get_mut(prey).unwrap() = Point::new(7, 1);
+        run(&flees, &positions, &mut dests);
+        assert!(dests.get(prey).is_none());
+
+        // Cornered at the end of the corridor.
+        *positions.get_mut(threat).unwrap() = Point::new(0, 1);
+        flees.get_mut(prey).unwrap().min_distance = 10.0;
+        run(&flees, &positions, &mut dests);
+        assert!(dests.get(prey).is_none());
+    }
+    #[test]
+    fn movement_class_layer() {
+        let mut entities = Entities::default();
+        let mut flees = Components::<FleeEntity>::default();
+        let mut classes = Components::<MovementClass>::default();
+        let mut positions = Components::<Point>::default();
+        let mut dests = Components::<AiDestination>::default();
+        let map = CollisionMap::from_str_layout(
+            "\
+##.#####
+........",
+            '#',
+        )
+        .unwrap();
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+        // Flyers go over the walls.
+        let mut stack = CollisionMapStack::default();
+        let air = MovementClass("air".to_string());
+        stack.insert(
+            air.clone(),
+            CollisionResource::new(CollisionMap::new(8, 2), Point::new(0, 0)),
+        );
+
+        let threat = entities.create();
+        positions.insert(threat, Point::new(1, 1));
+        let (walker, flyer) = (entities.create(), entities.create());
+        for e in &[walker, flyer] {
+            positions.insert(*e, Point::new(2, 1));
+            flees.insert(*e, FleeEntity::new(threat, 4.0));
+        }
+        classes.insert(flyer, air);
+
+        flee_entity_system(
+            &entities,
+            &flees,
+            &global_map,
+            &stack,
+            &classes,
+            &positions,
+            &mut dests,
+        )
+        .unwrap();
+        assert_eq!(dests.get(walker).unwrap().target, Point::new(7, 1));
+        assert_eq!(dests.get(flyer).unwrap().target, Point::new(7, 0));
+
+        // Without a global map, only the flyer flees.
+        dests.remove(walker);
+        dests.remove(flyer);
+        flee_entity_system(
+            &entities, &flees, &None, &stack, &classes, &positions, &mut dests,
+        )
+        .unwrap();
+        assert!(dests.get(walker).is_none());
+        assert_eq!(dests.get(flyer).unwrap().target, Point::new(7, 0));
+    }
+}
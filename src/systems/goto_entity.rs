@@ -0,0 +1,179 @@
+use crate::*;
+
+/// Calculates an `AiPath` towards the entity targeted by `GotoEntity`.
+/// The path is only recalculated when the target moved further than
+/// `GotoEntity::repath_threshold` since the last calculation.
+/// Entities having a `MovementClass` path on its layer in the `CollisionMapStack`.
+/// When the target is dead or has no position, the chaser stops and is marked
+/// with `TargetLost` for one frame.
+#[allow(clippy::too_many_arguments)]
+pub fn goto_entity_system(
+    entities: &Entities,
+    gotos: &mut Components<GotoEntity>,
+    global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
+    positions: &Components<Point>,
+    paths: &mut Components<AiPath>,
+    losts: &mut Components<TargetLost>,
+) -> SystemResult {
+    let mut v = vec![];
+    for (e, _) in join!(&entities && &losts) {
+        v.push(e.unwrap());
+    }
+    for e in v {
+        losts.remove(e);
+    }
+    let mut lost = vec![];
+    for (e, pos, goto) in join!(&entities && &positions && &mut gotos) {
+        let e = e.unwrap();
+        let pos = pos.unwrap();
+        let goto = goto.unwrap();
+        let target = match positions.get(goto.entity) {
+            Some(target) if entities.is_alive(goto.entity) => *target,
+            _ => {
+                lost.push(e);
+                continue;
+            }
+        };
+        if let Some(last) = goto.last_target {
+            let (dx, dy) = ((target.x - last.x) as f32, (target.y - last.y) as f32);
+            if (dx * dx + dy * dy).sqrt() <= goto.repath_threshold {
+                continue;
+            }
+        }
+        let map = match stack.layer_or(global_map.as_ref(), classes.get(e)) {
+            Some(map) => map,
+            None => continue,
+        };
+        if let Some(path) = map.find_path(pos, &target) {
+            paths.insert(e, AiPath::new(path));
+            goto.last_target = Some(target);
+        }
+    }
+    for e in lost {
+        gotos.remove(e);
+        paths.remove(e);
+        losts.insert(e, TargetLost);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn repath_on_target_move() {
+        let mut entities = Entities::default();
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let target = entities.create();
+        positions.insert(target, Point::new(5, 1));
+        let chaser = entities.create();
+        positions.insert(chaser, Point::new(1, 1));
+        let mut goto = GotoEntity::new(target, 1.0);
+        goto.repath_threshold = 2.0;
+        gotos.insert(chaser, goto);
+
+        goto_entity_system(
+            &entities,
+            &mut gotos,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert_eq!(paths.get(chaser).unwrap().path.destination, 15);
+
+        // Within the threshold, the old path is kept.
+        *positions.get_mut(target).unwrap() = Point::new(5, 3);
+        goto_entity_system(
+            &entities,
+            &mut gotos,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert_eq!(paths.get(chaser).unwrap().path.destination, 15);
+
+        *positions.get_mut(target).unwrap() = Point::new(5, 4);
+        goto_entity_system(
+            &entities,
+            &mut gotos,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert_eq!(paths.get(chaser).unwrap().path.destination, 45);
+        assert_eq!(
+            gotos.get(chaser).unwrap().last_target,
+            Some(Point::new(5, 4))
+        );
+    }
+
+    #[test]
+    fn target_killed() {
+        let mut entities = Entities::default();
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut losts = Components::<TargetLost>::default();
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let target = entities.create();
+        positions.insert(target, Point::new(5, 1));
+        let chaser = entities.create();
+        positions.insert(chaser, Point::new(1, 1));
+        gotos.insert(chaser, GotoEntity::new(target, 1.0));
+
+        let run = |entities: &Entities,
+                   gotos: &mut Components<GotoEntity>,
+                   paths: &mut Components<AiPath>,
+                   losts: &mut Components<TargetLost>| {
+            goto_entity_system(
+                entities,
+                gotos,
+                &global_map,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                &positions,
+                paths,
+                losts,
+            )
+            .unwrap();
+        };
+        run(&entities, &mut gotos, &mut paths, &mut losts);
+        assert!(paths.get(chaser).is_some());
+        assert!(losts.get(chaser).is_none());
+
+        // The storages still hold the dead target's position.
+        entities.kill(target);
+        run(&entities, &mut gotos, &mut paths, &mut losts);
+        assert!(gotos.get(chaser).is_none());
+        assert!(paths.get(chaser).is_none());
+        assert!(losts.get(chaser).is_some());
+
+        run(&entities, &mut gotos, &mut paths, &mut losts);
+        assert!(losts.get(chaser).is_none());
+    }
+}
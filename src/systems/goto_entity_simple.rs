@@ -1,24 +1,61 @@
 use crate::*;
 
-/// Moves an entity one tile towards the targeted entity, regardless of collisions.
+/// Moves an entity towards the targeted entity, regardless of collisions.
+/// It moves `GotoEntity::speed` tiles per second of `Time::delta_time`, keeping the
+/// fractions of tiles for the next frames.
+/// Entities being pushed by a `Knockback` or moving with a `GotoStraight` don't
+/// move, see `Knockback` for the precedence between movements.
+/// Entities having `ActionPoints` spend one point per step and stop once they have
+/// none left, dropping the remaining steps of the frame.
+/// When the target is dead or has no position, the `GotoEntity` is removed and
+/// the chaser is marked with `TargetLost` for one frame.
+#[allow(clippy::too_many_arguments)]
 pub fn goto_entity_simple_system(
     entities: &Entities,
-    gotos: &Components<GotoEntity>,
+    time: &Time,
+    gotos: &mut Components<GotoEntity>,
+    knockbacks: &Components<Knockback>,
+    straights: &Components<GotoStraight>,
     positions: &mut Components<Point>,
+    action_points: &mut Components<ActionPoints>,
+    losts: &mut Components<TargetLost>,
 ) -> SystemResult {
     let mut v = vec![];
-    for (e, _, goto) in join!(&entities && &positions && &gotos) {
-        v.push((
-            e.unwrap(),
-            goto.unwrap().entity.clone(),
-            goto.unwrap().speed,
-        ));
-    }
-    for (e, t, speed) in v {
+    for (e, _) in join!(&entities && &losts) {
+        v.push(e.unwrap());
+    }
+    for e in v {
+        losts.remove(e);
+    }
+    let mut v = vec![];
+    let mut lost = vec![];
+    for (e, _, goto) in join!(&entities && &positions && &mut gotos) {
+        let e = e.unwrap();
+        let goto = goto.unwrap();
+        if !entities.is_alive(goto.entity) || positions.get(goto.entity).is_none() {
+            lost.push(e);
+            continue;
+        }
+        if knockbacks.get(e).is_some() || straights.get(e).is_some() {
+            continue;
+        }
+        goto.progress += goto.speed * time.delta_time().as_secs_f32();
+        let steps = goto.progress as usize;
+        goto.progress -= steps as f32;
+        v.push((e, goto.entity, steps));
+    }
+    for (e, t, steps) in v {
         if let Some(target) = positions.get(t).map(|p| p.clone()) {
-            let mut p = positions.get_mut(e).unwrap();
-            // TODO improve when we have a Time struct
-            for _i in 0..(speed as usize) {
+            let p = positions.get_mut(e).unwrap();
+            for _i in 0..steps {
+                if *p == target {
+                    break;
+                }
+                if let Some(points) = action_points.get_mut(e) {
+                    if !points.spend() {
+                        break;
+                    }
+                }
                 let delta_x = target.x - p.x;
                 let delta_y = target.y - p.y;
                 if delta_x.abs() >= delta_y.abs() {
@@ -37,5 +74,124 @@ pub fn goto_entity_simple_system(
             }
         }
     }
+    for e in lost {
+        gotos.remove(e);
+        losts.insert(e, TargetLost);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn movement_precedence() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_secs(1));
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut knockbacks = Components::<Knockback>::default();
+        let mut straights = Components::<GotoStraight>::default();
+        let mut positions = Components::<Point>::default();
+
+        let target = entities.create();
+        positions.insert(target, Point::new(5, 0));
+        let (chaser, pushed, straight) = (entities.create(), entities.create(), entities.create());
+        for e in &[chaser, pushed, straight] {
+            positions.insert(*e, Point::new(0, 0));
+            gotos.insert(*e, GotoEntity::new(target, 2.0));
+        }
+        knockbacks.insert(pushed, Knockback::new(Direction::West, 1, 1.0));
+        straights.insert(straight, GotoStraight::new(Point::new(0, 5), 1.0));
+
+        goto_entity_simple_system(
+            &entities,
+            &time,
+            &mut gotos,
+            &knockbacks,
+            &straights,
+            &mut positions,
+            &mut Components::default(),
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert_eq!(*positions.get(chaser).unwrap(), Point::new(2, 0));
+        assert_eq!(*positions.get(pushed).unwrap(), Point::new(0, 0));
+        assert_eq!(*positions.get(straight).unwrap(), Point::new(0, 0));
+    }
+    #[test]
+    fn target_killed() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_secs(1));
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut positions = Components::<Point>::default();
+        let mut losts = Components::<TargetLost>::default();
+
+        let target = entities.create();
+        positions.insert(target, Point::new(5, 0));
+        let chaser = entities.create();
+        positions.insert(chaser, Point::new(0, 0));
+        gotos.insert(chaser, GotoEntity::new(target, 1.0));
+        entities.kill(target);
+
+        let run = |gotos: &mut _, positions: &mut _, losts: &mut _| {
+            goto_entity_simple_system(
+                &entities,
+                &time,
+                gotos,
+                &Components::default(),
+                &Components::default(),
+                positions,
+                &mut Components::default(),
+                losts,
+            )
+            .unwrap()
+        };
+        run(&mut gotos, &mut positions, &mut losts);
+        assert_eq!(*positions.get(chaser).unwrap(), Point::new(0, 0));
+        assert!(gotos.get(chaser).is_none());
+        assert!(losts.get(chaser).is_some());
+        run(&mut gotos, &mut positions, &mut losts);
+        assert!(losts.get(chaser).is_none());
+    }
+    #[test]
+    fn action_points() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_secs(1));
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut positions = Components::<Point>::default();
+        let mut action_points = Components::<ActionPoints>::default();
+
+        let target = entities.create();
+        positions.insert(target, Point::new(10, 0));
+        let chaser = entities.create();
+        positions.insert(chaser, Point::new(0, 0));
+        gotos.insert(chaser, GotoEntity::new(target, 3.0));
+        action_points.insert(chaser, ActionPoints::new(2, 2));
+
+        let run = |gotos: &mut _, positions: &mut _, action_points: &mut _| {
+            goto_entity_simple_system(
+                &entities,
+                &time,
+                gotos,
+                &Components::default(),
+                &Components::default(),
+                positions,
+                action_points,
+                &mut Components::default(),
+            )
+            .unwrap()
+        };
+        run(&mut gotos, &mut positions, &mut action_points);
+        assert_eq!(*positions.get(chaser).unwrap(), Point::new(2, 0));
+        assert_eq!(action_points.get(chaser).unwrap().current, 0);
+        // Waits for the next turn.
+        run(&mut gotos, &mut positions, &mut action_points);
+        assert_eq!(*positions.get(chaser).unwrap(), Point::new(2, 0));
+        action_points.get_mut(chaser).unwrap().refill();
+        run(&mut gotos, &mut positions, &mut action_points);
+        assert_eq!(*positions.get(chaser).unwrap(), Point::new(4, 0));
+    }
+}
@@ -1,31 +1,388 @@
 use crate::*;
 
-/// Moves an entity one tile towards the target position, regardless of collisions.
+/// The highest `GotoStraight::arrival_epsilon` used, so that an entity never snaps
+/// over more than one tile.
+const MAX_ARRIVAL_EPSILON: f32 = 0.99;
+
+/// Moves an entity towards the target position at `GotoStraight::speed` tiles per
+/// second of `Time::delta_time`. The fractions of tiles are kept in
+/// `GotoStraight::progress` for the next frames, so the pace doesn't depend on the
+/// frame rate.
+///
+/// Without `GotoStraight::pathfind_fallback`, collisions are ignored and blocked
+/// steps are taken. With it, when the next tile is blocked in the
+/// `CollisionResource`, a `BlockedMove` event is sent and an `AiPath` towards the
+/// target is inserted instead: the entity stops moving straight until that path is
+/// consumed.
+///
+/// Entities having a `MovementClass` use its layer in the `CollisionMapStack`
+/// instead of the `CollisionResource`.
+///
+/// Entities having `ActionPoints` spend one point per step and wait once they have
+/// none left. Entities being pushed by a `Knockback` wait until it is over.
+///
+/// The progress towards the next tile is written in the entity's `RenderOffset`,
+/// if it has one.
+///
+/// Once the entity is within `GotoStraight::arrival_epsilon` tiles of the target,
+/// it snaps to it, unless the fallback map blocks the target. The epsilon is
+/// clamped below one tile. The entity is marked with `PathComplete` on the frame
+/// it arrives and keeps its `GotoStraight`, which holds it at the target. Run it
+/// after `path_complete_system`, which clears the markers of the previous frame.
+#[allow(clippy::too_many_arguments)]
 pub fn goto_straight_system(
-    gotos: &Components<GotoStraight>,
+    entities: &Entities,
+    time: &Time,
+    gotos: &mut Components<GotoStraight>,
+    global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
     positions: &mut Components<Point>,
+    paths: &mut Components<AiPath>,
+    offsets: &mut Components<RenderOffset>,
+    action_points: &mut Components<ActionPoints>,
+    knockbacks: &Components<Knockback>,
+    completes: &mut Components<PathComplete>,
+    blocked: &mut Vec<BlockedMove>,
 ) -> SystemResult {
-    for (p, goto) in join!(&mut positions && &gotos) {
-        let mut p = p.unwrap();
+    for (e, p, goto) in join!(&entities && &mut positions && &mut gotos) {
+        let e = e.unwrap();
+        let p = p.unwrap();
         let goto = goto.unwrap();
-        // TODO improve when we have a Time struct
-        for _i in 0..(goto.speed as usize) {
-            let delta_x = goto.target.x - p.x;
-            let delta_y = goto.target.y - p.y;
-            if delta_x.abs() >= delta_y.abs() {
-                if delta_x > 0 {
-                    p.x += 1;
-                } else if delta_x < 0 {
-                    p.x -= 1;
-                }
-            } else {
-                if delta_y > 0 {
-                    p.y += 1;
-                } else if delta_y < 0 {
-                    p.y -= 1;
+        if knockbacks.get(e).is_some() {
+            continue;
+        }
+        let was_at_target = *p == goto.target;
+        let fallback_map = if goto.pathfind_fallback {
+            stack.layer_or(global_map.as_ref(), classes.get(e))
+        } else {
+            None
+        };
+        let following_path = paths
+            .get(e)
+            .map(|path| path.path.success && path.path.steps.len() > 1)
+            .unwrap_or(false);
+        if fallback_map.is_some() && following_path {
+            goto.progress = 0.0;
+        } else {
+            goto.progress += goto.speed * time.delta_time().as_secs_f32();
+        }
+        while goto.progress >= 1.0 && *p != goto.target {
+            if action_points
+                .get(e)
+                .map(|p| p.current == 0)
+                .unwrap_or(false)
+            {
+                // Waits for the next turn without saving up steps.
+                goto.progress = 1.0;
+                break;
+            }
+            let next = step_towards(*p, goto.target);
+            if let Some(map) = fallback_map {
+                if map.is_blocked(&next) {
+                    if let Some(direction) = Direction::between(*p, next) {
+                        blocked.push(BlockedMove::new(e, next, direction));
+                    }
+                    if let Some(path) = map.find_path(p, &goto.target) {
+                        paths.insert(e, AiPath::new(path));
+                    }
+                    goto.progress = 0.0;
+                    break;
                 }
             }
+            if let Some(points) = action_points.get_mut(e) {
+                points.spend();
+            }
+            *p = next;
+            goto.progress -= 1.0;
+        }
+        let remaining =
+            ((goto.target.x - p.x).abs() + (goto.target.y - p.y).abs()) as f32 - goto.progress;
+        let epsilon = goto.arrival_epsilon.clamp(0.0, MAX_ARRIVAL_EPSILON);
+        let target_blocked = fallback_map
+            .map(|map| map.is_blocked(&goto.target))
+            .unwrap_or(false);
+        if *p != goto.target && remaining <= epsilon && !target_blocked {
+            let can_step = action_points.get_mut(e).map(|p| p.spend()).unwrap_or(true);
+            if can_step {
+                *p = goto.target;
+            }
+        }
+        if *p == goto.target {
+            goto.progress = 0.0;
+            if !was_at_target {
+                completes.insert(e, PathComplete);
+            }
+        }
+        if let Some(offset) = offsets.get_mut(e) {
+            let next = step_towards(*p, goto.target);
+            *offset = RenderOffset::new(
+                (next.x - p.x) as f32 * goto.progress,
+                (next.y - p.y) as f32 * goto.progress,
+            );
         }
     }
     Ok(())
 }
+
+/// The next tile when moving straight from `from` towards `to`. Moves on the axis
+/// with the biggest distance first.
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn fallback_around_wall() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_secs(1));
+        let mut gotos = Components::<GotoStraight>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut blocked = vec![];
+        let mut map = CollisionMap::new(10, 10);
+        map.set(2, 1);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let straight = entities.create();
+        gotos.insert(straight, GotoStraight::new(Point::new(4, 1), 1.0));
+        positions.insert(straight, Point::new(1, 1));
+        let fallback = entities.create();
+        let mut goto = GotoStraight::new(Point::new(4, 1), 1.0);
+        goto.pathfind_fallback = true;
+        gotos.insert(fallback, goto);
+        positions.insert(fallback, Point::new(1, 1));
+
+        goto_straight_system(
+            &entities,
+            &time,
+            &mut gotos,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &mut Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut blocked,
+        )
+        .unwrap();
+        assert_eq!(*positions.get(straight).unwrap(), Point::new(2, 1));
+        assert_eq!(*positions.get(fallback).unwrap(), Point::new(1, 1));
+        assert!(paths.get(straight).is_none());
+        assert_eq!(
+            blocked,
+            vec![BlockedMove::new(
+                fallback,
+                Point::new(2, 1),
+                Direction::East
+            )]
+        );
+        let path = &paths.get(fallback).unwrap().path;
+        assert!(path.success);
+        assert!(!path
+            .steps
+            .contains(&(global_map.as_ref().unwrap().map.index_of(2, 1) as usize)));
+    }
+    #[test]
+    fn partial_progress() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_millis(500));
+        let mut gotos = Components::<GotoStraight>::default();
+        let mut positions = Components::<Point>::default();
+        let mut offsets = Components::<RenderOffset>::default();
+
+        let e = entities.create();
+        gotos.insert(e, GotoStraight::new(Point::new(0, 3), 3.0));
+        positions.insert(e, Point::new(0, 0));
+        offsets.insert(e, RenderOffset::default());
+
+        let run = |gotos: &mut _, positions: &mut _, offsets: &mut _| {
+            goto_straight_system(
+                &entities,
+                &time,
+                gotos,
+                &None,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                positions,
+                &mut Components::default(),
+                offsets,
+                &mut Components::default(),
+                &Components::default(),
+                &mut Components::default(),
+                &mut vec![],
+            )
+            .unwrap()
+        };
+        run(&mut gotos, &mut positions, &mut offsets);
+        assert_eq!(*positions.get(e).unwrap(), Point::new(0, 1));
+        assert_eq!(*offsets.get(e).unwrap(), RenderOffset::new(0.0, 0.5));
+        run(&mut gotos, &mut positions, &mut offsets);
+        assert_eq!(*positions.get(e).unwrap(), Point::new(0, 3));
+        assert_eq!(*offsets.get(e).unwrap(), RenderOffset::new(0.0, 0.0));
+    }
+    #[test]
+    fn frame_rate_independent() {
+        let run = |frame_ms: u64, frames: usize| {
+            let mut entities = Entities::default();
+            let mut time = Time::default();
+            let mut gotos = Components::<GotoStraight>::default();
+            let mut positions = Components::<Point>::default();
+            let e = entities.create();
+            gotos.insert(e, GotoStraight::new(Point::new(10, 0), 2.0));
+            positions.insert(e, Point::new(0, 0));
+            for _ in 0..frames {
+                time.advance_frame(std::time::Duration::from_millis(frame_ms));
+                goto_straight_system(
+                    &entities,
+                    &time,
+                    &mut gotos,
+                    &None,
+                    &CollisionMapStack::default(),
+                    &Components::default(),
+                    &mut positions,
+                    &mut Components::default(),
+                    &mut Components::default(),
+                    &mut Components::default(),
+                    &Components::default(),
+                    &mut Components::default(),
+                    &mut vec![],
+                )
+                .unwrap();
+            }
+            *positions.get(e).unwrap()
+        };
+        assert_eq!(run(1000, 2), Point::new(4, 0));
+        assert_eq!(run(250, 8), Point::new(4, 0));
+        assert_eq!(run(100, 20), Point::new(4, 0));
+    }
+    #[test]
+    fn arrival_snap() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_millis(500));
+        let mut gotos = Components::<GotoStraight>::default();
+        let mut positions = Components::<Point>::default();
+        let mut completes = Components::<PathComplete>::default();
+
+        let e = entities.create();
+        let mut goto = GotoStraight::new(Point::new(4, 0), 1.5);
+        goto.arrival_epsilon = 0.5;
+        gotos.insert(e, goto);
+        positions.insert(e, Point::new(0, 0));
+
+        for _ in 0..4 {
+            goto_straight_system(
+                &entities,
+                &time,
+                &mut gotos,
+                &None,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                &mut positions,
+                &mut Components::default(),
+                &mut Components::default(),
+                &mut Components::default(),
+                &Components::default(),
+                &mut completes,
+                &mut vec![],
+            )
+            .unwrap();
+        }
+        assert_eq!(*positions.get(e).unwrap(), Point::new(3, 0));
+        assert!(completes.get(e).is_none());
+
+        // 0.75 tiles of progress leave 0.25 tiles to go.
+        goto_straight_system(
+            &entities,
+            &time,
+            &mut gotos,
+            &None,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut Components::default(),
+            &mut Components::default(),
+            &mut Components::default(),
+            &Components::default(),
+            &mut completes,
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(*positions.get(e).unwrap(), Point::new(4, 0));
+        assert!(gotos.get(e).is_some());
+        assert!(completes.get(e).is_some());
+
+        // Staying at the target doesn't mark the entity again.
+        completes.remove(e);
+        goto_straight_system(
+            &entities,
+            &time,
+            &mut gotos,
+            &None,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut Components::default(),
+            &mut Components::default(),
+            &mut Components::default(),
+            &Components::default(),
+            &mut completes,
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(*positions.get(e).unwrap(), Point::new(4, 0));
+        assert!(gotos.get(e).is_some());
+        assert!(completes.get(e).is_none());
+    }
+    #[test]
+    fn arrival_snap_limits() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_millis(500));
+        let mut gotos = Components::<GotoStraight>::default();
+        let mut positions = Components::<Point>::default();
+        let mut completes = Components::<PathComplete>::default();
+        let mut map = CollisionMap::new(10, 10);
+        map.set(1, 1);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        // An epsilon above one tile doesn't teleport the entity.
+        let far = entities.create();
+        let mut goto = GotoStraight::new(Point::new(4, 0), 1.0);
+        goto.arrival_epsilon = 5.0;
+        gotos.insert(far, goto);
+        positions.insert(far, Point::new(0, 0));
+        // A blocked target isn't snapped to.
+        let walled = entities.create();
+        let mut goto = GotoStraight::new(Point::new(1, 1), 1.0);
+        goto.arrival_epsilon = 0.5;
+        goto.pathfind_fallback = true;
+        gotos.insert(walled, goto);
+        positions.insert(walled, Point::new(0, 1));
+
+        goto_straight_system(
+            &entities,
+            &time,
+            &mut gotos,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut Components::default(),
+            &mut Components::default(),
+            &mut Components::default(),
+            &Components::default(),
+            &mut completes,
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(*positions.get(far).unwrap(), Point::new(0, 0));
+        assert_eq!(*positions.get(walled).unwrap(), Point::new(0, 1));
+        assert!(completes.get(far).is_none());
+        assert!(completes.get(walled).is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::*;
+
+/// Sets the `RenderOffset` of the entities having an `InterpolatedPosition` so
+/// that they are drawn between their last two recorded positions, by the
+/// `FixedTimestep::alpha` of the frame. Run it every frame, before rendering.
+/// As consoles draw on whole tiles, the sprite moves once the frame is halfway
+/// between the fixed updates.
+/// Entities that haven't been recorded yet are drawn at their position. Don't
+/// combine it with other systems writing the `RenderOffset`, like
+/// `goto_straight_system`.
+pub fn interpolate_positions_system(
+    entities: &Entities,
+    timestep: &FixedTimestep,
+    positions: &Components<Point>,
+    snapshots: &Components<InterpolatedPosition>,
+    offsets: &mut Components<RenderOffset>,
+) -> SystemResult {
+    let remaining = 1.0 - timestep.alpha();
+    for (e, pos, snapshot) in join!(&entities && &positions && &snapshots) {
+        let (pos, snapshot) = (pos.unwrap(), snapshot.unwrap());
+        let offset = match snapshot.previous {
+            Some(previous) => RenderOffset::new(
+                (previous.x - pos.x) as f32 * remaining,
+                (previous.y - pos.y) as f32 * remaining,
+            ),
+            None => RenderOffset::default(),
+        };
+        offsets.insert(e.unwrap(), offset);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn draw_between_steps() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut snapshots = Components::<InterpolatedPosition>::default();
+        let mut offsets = Components::<RenderOffset>::default();
+        let mut timestep = FixedTimestep::new(0.1);
+
+        let e = entities.create();
+        positions.insert(e, Point::new(0, 0));
+        snapshots.insert(e, InterpolatedPosition::new());
+        snapshot_positions_system(&entities, &positions, &mut snapshots).unwrap();
+        *positions.get_mut(e).unwrap() = Point::new(2, 0);
+        snapshot_positions_system(&entities, &positions, &mut snapshots).unwrap();
+        assert_eq!(
+            *snapshots.get(e).unwrap(),
+            InterpolatedPosition {
+                previous: Some(Point::new(0, 0)),
+                current: Some(Point::new(2, 0)),
+            }
+        );
+
+        assert_eq!(timestep.advance(0.125), 1);
+        assert!((timestep.alpha() - 0.25).abs() < 0.001);
+        interpolate_positions_system(&entities, &timestep, &positions, &snapshots, &mut offsets)
+            .unwrap();
+        let offset = *offsets.get(e).unwrap();
+        assert!((offset.x + 1.5).abs() < 0.001 && offset.y == 0.0);
+
+        // A slow frame doesn't run more than `max_steps` updates.
+        assert_eq!(timestep.advance(10.0), 5);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+}
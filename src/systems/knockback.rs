@@ -0,0 +1,85 @@
+use crate::*;
+
+/// Pushes entities having a `Knockback` in its direction at `Knockback::speed` tiles
+/// per second.
+/// The `Knockback` is removed once its distance is covered, or earlier if the
+/// next tile is blocked in the `CollisionResource`.
+/// Run it before `goto_straight_system` and `ai_movement_system`: they leave
+/// pushed entities in place.
+pub fn knockback_system(
+    entities: &Entities,
+    time: &Time,
+    global_map: &Option<CollisionResource>,
+    knockbacks: &mut Components<Knockback>,
+    positions: &mut Components<Point>,
+) -> SystemResult {
+    let mut finished = vec![];
+    for (e, pos, knockback) in join!(&entities && &mut positions && &mut knockbacks) {
+        let e = e.unwrap();
+        let pos = pos.unwrap();
+        let knockback = knockback.unwrap();
+        knockback.progress += knockback.speed * time.delta_time().as_secs_f32();
+        while knockback.progress >= 1.0 && knockback.distance > 0 {
+            let next = knockback.direction.apply(*pos);
+            if global_map
+                .as_ref()
+                .map(|map| map.is_blocked(&next))
+                .unwrap_or(false)
+            {
+                knockback.distance = 0;
+                break;
+            }
+            *pos = next;
+            knockback.distance -= 1;
+            knockback.progress -= 1.0;
+        }
+        if knockback.distance == 0 {
+            finished.push(e);
+        }
+    }
+    for e in finished {
+        knockbacks.remove(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn stops_at_wall() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_secs(1));
+        let mut knockbacks = Components::<Knockback>::default();
+        let mut positions = Components::<Point>::default();
+        let mut map = CollisionMap::new(10, 10);
+        map.set(4, 1);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let e = entities.create();
+        positions.insert(e, Point::new(1, 1));
+        knockbacks.insert(e, Knockback::new(Direction::East, 5, 2.0));
+        knockback_system(
+            &entities,
+            &time,
+            &global_map,
+            &mut knockbacks,
+            &mut positions,
+        )
+        .unwrap();
+        assert_eq!(*positions.get(e).unwrap(), Point::new(3, 1));
+        assert_eq!(knockbacks.get(e).unwrap().distance, 3);
+
+        knockback_system(
+            &entities,
+            &time,
+            &global_map,
+            &mut knockbacks,
+            &mut positions,
+        )
+        .unwrap();
+        assert_eq!(*positions.get(e).unwrap(), Point::new(3, 1));
+        assert!(knockbacks.get(e).is_none());
+    }
+}
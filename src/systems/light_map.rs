@@ -0,0 +1,18 @@
+use crate::*;
+
+/// Rebuilds the `LightMap` from the entities having a `PointLight` and a position.
+/// Walls of the `CollisionResource` block the light.
+pub fn light_map_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    lights: &Components<PointLight>,
+    global_map: &Option<CollisionResource>,
+    light_map: &mut LightMap,
+) -> SystemResult {
+    let global_map = global_map.as_ref().unwrap();
+    light_map.clear(global_map);
+    for (_, pos, light) in join!(&entities && &positions && &lights) {
+        light_map.add_light(global_map, *pos.unwrap(), light.unwrap());
+    }
+    Ok(())
+}
@@ -1,23 +1,73 @@
+mod adjacency;
 mod ai_movement;
 mod ai_pathing;
+mod animate_sprite;
 mod apply_effector;
+mod camera_follow;
+mod camera_shake;
+mod color_pulse;
 mod combine_collision;
+mod despawn_out_of_bounds;
 mod exec_skill;
+mod face_movement;
+mod flee_entity;
+mod goto_entity;
 mod goto_entity_simple;
 mod goto_straight;
 mod input_driver;
+mod interpolate_positions;
+mod knockback;
+mod light_map;
+mod movement_events;
+mod path_complete;
+mod path_request;
+mod patrol;
+mod refill_action_points;
 mod remove_outdated_effector;
+mod resolve_sprite_index;
+mod resolve_themed_sprite;
+mod separation;
 mod skill_cooldown;
+mod snapshot_positions;
+mod spatial_hash;
+mod stuck_detection;
+mod track_collision;
 mod trigger_passive_skill;
+mod visible_set;
 
+pub use self::adjacency::*;
 pub use self::ai_movement::*;
 pub use self::ai_pathing::*;
+pub use self::animate_sprite::*;
 pub use self::apply_effector::*;
+pub use self::camera_follow::*;
+pub use self::camera_shake::*;
+pub use self::color_pulse::*;
 pub use self::combine_collision::*;
+pub use self::despawn_out_of_bounds::*;
 pub use self::exec_skill::*;
+pub use self::face_movement::*;
+pub use self::flee_entity::*;
+pub use self::goto_entity::*;
 pub use self::goto_entity_simple::*;
 pub use self::goto_straight::*;
 pub use self::input_driver::*;
+pub use self::interpolate_positions::*;
+pub use self::knockback::*;
+pub use self::light_map::*;
+pub use self::movement_events::*;
+pub use self::path_complete::*;
+pub use self::path_request::*;
+pub use self::patrol::*;
+pub use self::refill_action_points::*;
 pub use self::remove_outdated_effector::*;
+pub use self::resolve_sprite_index::*;
+pub use self::resolve_themed_sprite::*;
+pub use self::separation::*;
 pub use self::skill_cooldown::*;
+pub use self::snapshot_positions::*;
+pub use self::spatial_hash::*;
+pub use self::stuck_detection::*;
+pub use self::track_collision::*;
 pub use self::trigger_passive_skill::*;
+pub use self::visible_set::*;
@@ -0,0 +1,110 @@
+use crate::*;
+use std::collections::HashSet;
+
+/// Sends a `MovementChange` event when an `AiPath`, `AiDestination` or `GotoEntity`
+/// is added to or removed from an entity since the last run, so that games can
+/// react to an entity starting or stopping to move without comparing storages
+/// themselves.
+/// The storages don't need any special setup: this system compares them with the
+/// `MovementTracker` once per frame. Components added and removed between two runs
+/// send nothing. Killed entities send a removal.
+/// Should run after the systems that change these components.
+pub fn movement_events_system(
+    entities: &Entities,
+    paths: &Components<AiPath>,
+    dests: &Components<AiDestination>,
+    gotos: &Components<GotoEntity>,
+    tracker: &mut MovementTracker,
+    events: &mut Vec<MovementChange>,
+) -> SystemResult {
+    let current = [
+        (
+            MovementComponent::AiPath,
+            join!(&entities && &paths)
+                .map(|(e, _)| e.unwrap())
+                .collect::<Vec<_>>(),
+        ),
+        (
+            MovementComponent::AiDestination,
+            join!(&entities && &dests)
+                .map(|(e, _)| e.unwrap())
+                .collect(),
+        ),
+        (
+            MovementComponent::GotoEntity,
+            join!(&entities && &gotos)
+                .map(|(e, _)| e.unwrap())
+                .collect(),
+        ),
+    ];
+    for (component, now) in current.iter() {
+        let before = tracker.entities.entry(*component).or_default();
+        let (now_set, before_set) = (
+            now.iter().collect::<HashSet<_>>(),
+            before.iter().collect::<HashSet<_>>(),
+        );
+        for e in before.iter().filter(|e| !now_set.contains(e)) {
+            events.push(MovementChange::new(*e, *component, false));
+        }
+        for e in now.iter().filter(|e| !before_set.contains(e)) {
+            events.push(MovementChange::new(*e, *component, true));
+        }
+        *before = now.clone();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn start_and_stop() {
+        let mut entities = Entities::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut tracker = MovementTracker::default();
+
+        let walker = entities.create();
+        let target = entities.create();
+        dests.insert(walker, AiDestination::new(Point::new(3, 3)));
+        gotos.insert(target, GotoEntity::new(walker, 1.0));
+        let mut run = |paths: &Components<AiPath>,
+                       dests: &Components<AiDestination>,
+                       gotos: &Components<GotoEntity>,
+                       entities: &Entities| {
+            let mut events = vec![];
+            movement_events_system(entities, paths, dests, gotos, &mut tracker, &mut events)
+                .unwrap();
+            events
+        };
+        assert_eq!(
+            run(&paths, &dests, &gotos, &entities),
+            vec![
+                MovementChange::new(walker, MovementComponent::AiDestination, true),
+                MovementChange::new(target, MovementComponent::GotoEntity, true),
+            ]
+        );
+        assert!(run(&paths, &dests, &gotos, &entities).is_empty());
+
+        paths.insert(walker, AiPath::default());
+        dests.remove(walker);
+        assert_eq!(
+            run(&paths, &dests, &gotos, &entities),
+            vec![
+                MovementChange::new(walker, MovementComponent::AiPath, true),
+                MovementChange::new(walker, MovementComponent::AiDestination, false),
+            ]
+        );
+
+        entities.kill(target);
+        assert_eq!(
+            run(&paths, &dests, &gotos, &entities),
+            vec![MovementChange::new(
+                target,
+                MovementComponent::GotoEntity,
+                false
+            )]
+        );
+    }
+}
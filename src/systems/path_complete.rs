@@ -0,0 +1,95 @@
+use crate::*;
+
+/// Removes completed `AiPath`s and marks their entity with `PathComplete` for
+/// one frame. The `AiDestination` is removed too, unless `keep_on_complete` is set.
+/// Should run after `ai_movement_system`.
+pub fn path_complete_system(
+    entities: &Entities,
+    paths: &mut Components<AiPath>,
+    dests: &mut Components<AiDestination>,
+    completes: &mut Components<PathComplete>,
+) -> SystemResult {
+    let mut v = vec![];
+    for (e, _) in join!(&entities && &completes) {
+        v.push(e.unwrap());
+    }
+    for e in v {
+        completes.remove(e);
+    }
+    let mut v = vec![];
+    for (e, path) in join!(&entities && &paths) {
+        let path = path.unwrap();
+        if path.path.success && path.remaining() == 0 {
+            v.push(e.unwrap());
+        }
+    }
+    for e in v {
+        paths.remove(e);
+        if !dests.get(e).map(|d| d.keep_on_complete).unwrap_or(false) {
+            dests.remove(e);
+        }
+        completes.insert(e, PathComplete);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn marks_for_one_frame() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut completes = Components::<PathComplete>::default();
+        let global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let e = entities.create();
+        positions.insert(e, Point::new(1, 1));
+        dests.insert(e, AiDestination::new(Point::new(1, 2)));
+        ai_pathing_system(
+            &entities,
+            &dests,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut paths,
+            &mut PathingBudget::default(),
+            &mut Components::default(),
+        )
+        .unwrap();
+        assert_eq!(paths.get(e).unwrap().remaining(), 1);
+
+        path_complete_system(&entities, &mut paths, &mut dests, &mut completes).unwrap();
+        assert!(completes.get(e).is_none());
+
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(paths.get(e).unwrap().remaining(), 0);
+        path_complete_system(&entities, &mut paths, &mut dests, &mut completes).unwrap();
+        assert!(completes.get(e).is_some());
+        assert!(paths.get(e).is_none());
+        assert!(dests.get(e).is_none());
+
+        path_complete_system(&entities, &mut paths, &mut dests, &mut completes).unwrap();
+        assert!(completes.get(e).is_none());
+    }
+}
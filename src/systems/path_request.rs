@@ -0,0 +1,101 @@
+use crate::*;
+
+/// Calculates the `PathResult` of the entities having a `PathRequest`, then removes
+/// the request. Entities whose request fails, see `PathRequest::path_from`, have
+/// no `PathResult` afterwards. Their `AiPath`, if any, isn't changed.
+/// It uses a `CollisionResource` to take collisions into account, or the layer of
+/// the entity's `MovementClass` in the `CollisionMapStack`.
+/// Every request is handled in the frame it was made, regardless of the
+/// `PathingBudget`.
+pub fn path_request_system(
+    entities: &Entities,
+    requests: &mut Components<PathRequest>,
+    global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
+    positions: &Components<Point>,
+    results: &mut Components<PathResult>,
+) -> SystemResult {
+    let mut done = vec![];
+    for (e, pos, request) in join!(&entities && &positions && &requests) {
+        let e = e.unwrap();
+        let global_map = match stack.layer_or(global_map.as_ref(), classes.get(e)) {
+            Some(map) => map,
+            None => continue,
+        };
+        if let Some(p) = request.unwrap().path_from(global_map, pos.unwrap()) {
+            results.insert(e, PathResult::new(p));
+        } else {
+            results.remove(e);
+        }
+        done.push(e);
+    }
+    for e in done {
+        requests.remove(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn consume_requests() {
+        let mut entities = Entities::default();
+        let mut requests = Components::<PathRequest>::default();
+        let mut positions = Components::<Point>::default();
+        let mut results = Components::<PathResult>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut map = CollisionMap::new(5, 5);
+        map.set(4, 4);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let (preview, blocked, snapped) = (entities.create(), entities.create(), entities.create());
+        for e in &[preview, blocked, snapped] {
+            positions.insert(*e, Point::new(0, 0));
+        }
+        requests.insert(preview, PathRequest::new(Point::new(0, 9)));
+        requests.insert(blocked, PathRequest::new(Point::new(4, 4)));
+        results.insert(blocked, PathResult::new(NavigationPath::new()));
+        let mut snap = PathRequest::new(Point::new(4, 4));
+        snap.snap_to_open = true;
+        requests.insert(snapped, snap);
+
+        path_request_system(
+            &entities,
+            &mut requests,
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &positions,
+            &mut results,
+        )
+        .unwrap();
+        // Clamped to the edge of the map.
+        assert_eq!(results.get(preview).unwrap().path.steps.len(), 5);
+        assert!(results.get(blocked).is_none());
+        assert_eq!(results.get(snapped).unwrap().path.steps.len(), 8);
+        for e in &[preview, blocked, snapped] {
+            assert!(requests.get(*e).is_none());
+        }
+
+        // Nothing follows the path.
+        ai_movement_system(
+            &entities,
+            &Time::default(),
+            &global_map,
+            &CollisionMapStack::default(),
+            &Components::default(),
+            &mut positions,
+            &mut paths,
+            &mut Components::default(),
+            &Components::default(),
+            &Components::default(),
+            &mut Components::default(),
+            &mut vec![],
+        )
+        .unwrap();
+        assert_eq!(*positions.get(preview).unwrap(), Point::new(0, 0));
+        assert!(paths.get(preview).is_none());
+    }
+}
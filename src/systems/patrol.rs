@@ -0,0 +1,94 @@
+use crate::*;
+
+/// Sets the `AiDestination` of entities having a `Patrol` to their next waypoint
+/// once the current one is reached, which is detected using `PathComplete` or by
+/// standing on it.
+/// Patrols without waypoints are removed.
+/// Run it after `path_complete_system` and before `ai_pathing_system`.
+pub fn patrol_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    completes: &Components<PathComplete>,
+    patrols: &mut Components<Patrol>,
+    dests: &mut Components<AiDestination>,
+) -> SystemResult {
+    let mut empty = vec![];
+    for (e, pos, patrol) in join!(&entities && &positions && &mut patrols) {
+        let e = e.unwrap();
+        let pos = pos.unwrap();
+        let patrol = patrol.unwrap();
+        let target = match patrol.target() {
+            Some(target) => target,
+            None => {
+                empty.push(e);
+                continue;
+            }
+        };
+        let reached = completes.get(e).is_some() || *pos == target;
+        if reached {
+            patrol.advance();
+        }
+        if reached || dests.get(e).is_none() {
+            dests.insert(e, AiDestination::new(patrol.target().unwrap()));
+        }
+    }
+    for e in empty {
+        patrols.remove(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn patrol_modes() {
+        let waypoints = vec![Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)];
+        let mut looping = Patrol::new(waypoints.clone(), LoopMode::Loop);
+        let mut ping_pong = Patrol::new(waypoints, LoopMode::PingPong);
+        let mut looped = vec![];
+        let mut bounced = vec![];
+        for _ in 0..6 {
+            looping.advance();
+            ping_pong.advance();
+            looped.push(looping.current);
+            bounced.push(ping_pong.current);
+        }
+        assert_eq!(looped, vec![1, 2, 0, 1, 2, 0]);
+        assert_eq!(bounced, vec![1, 2, 1, 0, 1, 2]);
+    }
+    #[test]
+    fn next_waypoint() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut completes = Components::<PathComplete>::default();
+        let mut patrols = Components::<Patrol>::default();
+        let mut dests = Components::<AiDestination>::default();
+
+        let guard = entities.create();
+        positions.insert(guard, Point::new(0, 0));
+        patrols.insert(
+            guard,
+            Patrol::new(vec![Point::new(0, 0), Point::new(3, 0)], LoopMode::Loop),
+        );
+        let idle = entities.create();
+        positions.insert(idle, Point::new(0, 0));
+        patrols.insert(idle, Patrol::new(vec![], LoopMode::Loop));
+
+        let mut run = |completes: &Components<PathComplete>, dests: &mut _| {
+            patrol_system(&entities, &positions, completes, &mut patrols, dests).unwrap()
+        };
+        // Already standing on the first waypoint.
+        run(&completes, &mut dests);
+        assert_eq!(dests.get(guard).unwrap().target, Point::new(3, 0));
+        run(&completes, &mut dests);
+        assert_eq!(dests.get(guard).unwrap().target, Point::new(3, 0));
+
+        completes.insert(guard, PathComplete);
+        dests.remove(guard);
+        run(&completes, &mut dests);
+        assert_eq!(dests.get(guard).unwrap().target, Point::new(0, 0));
+        assert!(dests.get(idle).is_none());
+        assert!(patrols.get(idle).is_none());
+    }
+}
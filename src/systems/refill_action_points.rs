@@ -0,0 +1,57 @@
+use crate::*;
+
+/// Refills the `ActionPoints` of all entities.
+/// Run it when a new turn begins, before the movement systems.
+pub fn refill_action_points_system(action_points: &mut Components<ActionPoints>) -> SystemResult {
+    for points in action_points.iter_mut() {
+        points.refill();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn one_step_per_turn() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_secs(3));
+        let mut gotos = Components::<GotoStraight>::default();
+        let mut positions = Components::<Point>::default();
+        let mut action_points = Components::<ActionPoints>::default();
+
+        let e = entities.create();
+        gotos.insert(e, GotoStraight::new(Point::new(5, 0), 1.0));
+        positions.insert(e, Point::new(0, 0));
+        action_points.insert(e, ActionPoints::new(1, 1));
+        let mut run = |positions: &mut Components<Point>,
+                       action_points: &mut Components<ActionPoints>| {
+            goto_straight_system(
+                &entities,
+                &time,
+                &mut gotos,
+                &None,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                positions,
+                &mut Components::default(),
+                &mut Components::default(),
+                action_points,
+                &Components::default(),
+                &mut Components::default(),
+                &mut vec![],
+            )
+            .unwrap()
+        };
+        run(&mut positions, &mut action_points);
+        assert_eq!(*positions.get(e).unwrap(), Point::new(1, 0));
+        assert_eq!(action_points.get(e).unwrap().current, 0);
+        run(&mut positions, &mut action_points);
+        assert_eq!(*positions.get(e).unwrap(), Point::new(1, 0));
+
+        refill_action_points_system(&mut action_points).unwrap();
+        run(&mut positions, &mut action_points);
+        assert_eq!(*positions.get(e).unwrap(), Point::new(2, 0));
+    }
+}
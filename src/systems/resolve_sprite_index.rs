@@ -0,0 +1,100 @@
+use crate::*;
+
+/// Inserts the `Sprite` defined in `SpriteDefinitions` for each entity having a
+/// `SpriteIndex`. Indices without a definition are logged once and skipped.
+///
+/// The `Sprite` is only inserted when the entity has none or its index changed, so
+/// systems like `animate_sprite_system` can edit it afterwards. Clear the
+/// `SpriteIndexTracker` after changing the definitions to resolve every entity again.
+pub fn resolve_sprite_index_system(
+    entities: &Entities,
+    definitions: &SpriteDefinitions,
+    indices: &Components<SpriteIndex>,
+    tracker: &mut SpriteIndexTracker,
+    sprites: &mut Components<Sprite>,
+) -> SystemResult {
+    tracker.indices.retain(|e, _| indices.get(*e).is_some());
+    for (e, index) in join!(&entities && &indices) {
+        let (e, index) = (e.unwrap(), index.unwrap().0);
+        let changed = tracker.indices.insert(e, index) != Some(index);
+        if let Some(sprite) = definitions.get(index) {
+            if changed || sprites.get(e).is_none() {
+                sprites.insert(e, *sprite);
+            }
+        } else if changed {
+            eprintln!(
+                "SpriteIndex {} is out of range, only {} sprites are defined.",
+                index,
+                definitions.sprites.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn resolve_index() {
+        let mut entities = Entities::default();
+        let mut indices = Components::<SpriteIndex>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let sprite = Sprite {
+            glyph: to_cp437('@'),
+            fg: RGBA::named(YELLOW),
+            bg: Some(RGBA::named(BLACK)),
+        };
+        let definitions = SpriteDefinitions::new(vec![sprite]);
+
+        let valid = entities.create();
+        indices.insert(valid, SpriteIndex(0));
+        let invalid = entities.create();
+        indices.insert(invalid, SpriteIndex(3));
+
+        let mut tracker = SpriteIndexTracker::default();
+        resolve_sprite_index_system(
+            &entities,
+            &definitions,
+            &indices,
+            &mut tracker,
+            &mut sprites,
+        )
+        .unwrap();
+        assert_eq!(sprites.get(valid), Some(&sprite));
+        assert!(sprites.get(invalid).is_none());
+        assert_eq!(tracker.indices.get(&invalid), Some(&3));
+    }
+    #[test]
+    fn keep_edited_sprite() {
+        let mut entities = Entities::default();
+        let mut indices = Components::<SpriteIndex>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut tracker = SpriteIndexTracker::default();
+        let sprite = |c| Sprite {
+            glyph: to_cp437(c),
+            fg: RGBA::named(YELLOW),
+            bg: None,
+        };
+        let definitions = SpriteDefinitions::new(vec![sprite('@'), sprite('g')]);
+        let e = entities.create();
+        indices.insert(e, SpriteIndex(0));
+        let mut run = |indices: &Components<SpriteIndex>, sprites: &mut Components<Sprite>| {
+            resolve_sprite_index_system(&entities, &definitions, indices, &mut tracker, sprites)
+                .unwrap()
+        };
+        run(&indices, &mut sprites);
+
+        // An animation frame isn't overwritten while the index stays the same.
+        sprites.get_mut(e).unwrap().glyph = to_cp437('a');
+        run(&indices, &mut sprites);
+        assert_eq!(sprites.get(e).unwrap().glyph, to_cp437('a'));
+
+        indices.insert(e, SpriteIndex(1));
+        run(&indices, &mut sprites);
+        assert_eq!(sprites.get(e).unwrap().glyph, to_cp437('g'));
+        sprites.remove(e);
+        run(&indices, &mut sprites);
+        assert_eq!(sprites.get(e).unwrap().glyph, to_cp437('g'));
+    }
+}
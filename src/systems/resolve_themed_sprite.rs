@@ -0,0 +1,78 @@
+use crate::*;
+
+/// Inserts the `Sprite` matching the `Palette` colors for each entity having a
+/// `ThemedSprite`. Missing keys use `Palette::FALLBACK`.
+///
+/// The `Sprite` is only inserted when the entity has none, its `ThemedSprite`
+/// changed or the palette changed, so systems like `color_pulse_system` can edit
+/// it afterwards.
+pub fn resolve_themed_sprite_system(
+    entities: &Entities,
+    palette: &Palette,
+    themed: &Components<ThemedSprite>,
+    tracker: &mut ThemedSpriteTracker,
+    sprites: &mut Components<Sprite>,
+) -> SystemResult {
+    let recolor = tracker.palette != *palette;
+    if recolor {
+        tracker.palette = palette.clone();
+    }
+    tracker.sprites.retain(|e, _| themed.get(*e).is_some());
+    for (e, themed) in join!(&entities && &themed) {
+        let (e, themed) = (e.unwrap(), themed.unwrap());
+        if !recolor && tracker.sprites.get(&e) == Some(themed) && sprites.get(e).is_some() {
+            continue;
+        }
+        tracker.sprites.insert(e, themed.clone());
+        sprites.insert(
+            e,
+            Sprite::themed(themed.glyph, &themed.fg, themed.bg.as_deref(), palette),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn recolor() {
+        let mut entities = Entities::default();
+        let mut themed = Components::<ThemedSprite>::default();
+        let mut sprites = Components::<Sprite>::default();
+        let mut tracker = ThemedSpriteTracker::default();
+        let mut palette = Palette::default();
+        palette.set("player", RGBA::named(YELLOW));
+
+        let e = entities.create();
+        themed.insert(
+            e,
+            ThemedSprite::new(to_cp437('@'), "player".to_string(), None),
+        );
+        resolve_themed_sprite_system(&entities, &palette, &themed, &mut tracker, &mut sprites)
+            .unwrap();
+        assert_eq!(sprites.get(e).unwrap().fg, RGBA::named(YELLOW));
+
+        palette.set("player", RGBA::named(RED));
+        resolve_themed_sprite_system(&entities, &palette, &themed, &mut tracker, &mut sprites)
+            .unwrap();
+        assert_eq!(sprites.get(e).unwrap().fg, RGBA::named(RED));
+        assert_eq!(sprites.get(e).unwrap().bg, None);
+
+        // A pulsing color is kept until the palette or the ThemedSprite changes.
+        sprites.get_mut(e).unwrap().fg = RGBA::named(WHITE);
+        resolve_themed_sprite_system(&entities, &palette, &themed, &mut tracker, &mut sprites)
+            .unwrap();
+        assert_eq!(sprites.get(e).unwrap().fg, RGBA::named(WHITE));
+        themed.get_mut(e).unwrap().glyph = to_cp437('&');
+        resolve_themed_sprite_system(&entities, &palette, &themed, &mut tracker, &mut sprites)
+            .unwrap();
+        assert_eq!(sprites.get(e).unwrap().glyph, to_cp437('&'));
+        assert_eq!(sprites.get(e).unwrap().fg, RGBA::named(RED));
+        sprites.get_mut(e).unwrap().fg = RGBA::named(WHITE);
+        palette.set("player", RGBA::named(GREEN));
+        resolve_themed_sprite_system(&entities, &palette, &themed, &mut tracker, &mut sprites)
+            .unwrap();
+        assert_eq!(sprites.get(e).unwrap().fg, RGBA::named(GREEN));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// Spreads crowds of entities having a `Separation` component. When the next step
+/// of their `AiPath` is on a tile holding `max_per_tile` entities or more in the
+/// `SpatialHash`, that step is replaced by a walkable neighbor that still leads to
+/// the rest of the path. When the next step is the last one, the path ends on a
+/// free tile next to it instead. If no such tile exists, the path is kept.
+///
+/// This doesn't change collision: only tiles walkable on the entity's layer are
+/// picked, occupied tiles never block anyone and entities without `Separation`
+/// walk on crowded tiles as usual.
+/// Should run after `spatial_hash_system` and before `ai_movement_system`.
+#[allow(clippy::too_many_arguments)]
+pub fn separation_system(
+    entities: &Entities,
+    global_map: &Option<CollisionResource>,
+    stack: &CollisionMapStack,
+    classes: &Components<MovementClass>,
+    separations: &Components<Separation>,
+    positions: &Components<Point>,
+    paths: &mut Components<AiPath>,
+    spatial_hash: &SpatialHash,
+) -> SystemResult {
+    // Tiles entered during this frame, which the `SpatialHash` doesn't know about yet.
+    let mut claimed = HashMap::<Point, usize>::new();
+    for (e, separation, pos, path) in join!(&entities && &separations && &positions && &mut paths) {
+        let e = e.unwrap();
+        let max = separation.unwrap().max_per_tile;
+        let pos = *pos.unwrap();
+        let path = path.unwrap();
+        if !path.path.success || path.path.steps.len() < 2 {
+            continue;
+        }
+        let layer = match stack.layer_or(global_map.as_ref(), classes.get(e)) {
+            Some(layer) => layer,
+            None => continue,
+        };
+        let to_local = |p: Point| p - layer.position;
+        let to_world = |idx: usize| {
+            let (x, y) = layer.map.position_of(idx as u32);
+            Point::new(x, y) + layer.position
+        };
+        let crowded = |p: Point, claimed: &HashMap<Point, usize>| {
+            let count = spatial_hash
+                .entities_at(p)
+                .iter()
+                .filter(|o| **o != e)
+                .count();
+            count + claimed.get(&p).copied().unwrap_or(0) >= max
+        };
+        let next = to_world(path.path.steps[1]);
+        if !crowded(next, &claimed) {
+            *claimed.entry(next).or_insert(0) += 1;
+            continue;
+        }
+        // The tile the detour has to connect to.
+        let after = path
+            .path
+            .steps
+            .get(2)
+            .map(|idx| to_world(*idx))
+            .unwrap_or(next);
+        let detour = layer
+            .map
+            .walkable_neighbors(to_local(pos))
+            .into_iter()
+            .map(|p| p + layer.position)
+            .filter(|p| *p != next && !crowded(*p, &claimed))
+            .find(|p| {
+                layer
+                    .map
+                    .walkable_neighbors(to_local(*p))
+                    .contains(&to_local(after))
+            });
+        if let Some(detour) = detour {
+            let local = to_local(detour);
+            path.path.steps[1] = layer.map.index_of(local.x as u32, local.y as u32) as usize;
+            if path.path.steps.len() == 2 {
+                path.path.destination = path.path.steps[1];
+            }
+            *claimed.entry(detour).or_insert(0) += 1;
+        } else {
+            *claimed.entry(next).or_insert(0) += 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn step_around_crowd() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut separations = Components::<Separation>::default();
+        let mut spatial_hash = SpatialHash::default();
+        let mut map = CollisionMap::new(5, 5);
+        map.set_diagonal_movement(DiagonalMovement::IfEitherOpen);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let blocker = entities.create();
+        positions.insert(blocker, Point::new(2, 1));
+        let e = entities.create();
+        positions.insert(e, Point::new(1, 1));
+        let path = global_map
+            .as_ref()
+            .unwrap()
+            .find_path(&Point::new(1, 1), &Point::new(3, 1))
+            .unwrap();
+        assert_eq!(path.steps, vec![6, 7, 8]);
+        paths.insert(e, AiPath::new(path));
+        separations.insert(e, Separation::new(2));
+        spatial_hash_system(&entities, &positions, &mut spatial_hash).unwrap();
+
+        let run = |separations: &Components<Separation>,
+                   positions: &Components<Point>,
+                   paths: &mut Components<AiPath>,
+                   spatial_hash: &SpatialHash| {
+            separation_system(
+                &entities,
+                &global_map,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                separations,
+                positions,
+                paths,
+                spatial_hash,
+            )
+            .unwrap();
+        };
+        // Two entities can stack.
+        run(&separations, &positions, &mut paths, &spatial_hash);
+        assert_eq!(paths.get(e).unwrap().path.steps, vec![6, 7, 8]);
+
+        separations.insert(e, Separation::new(1));
+        run(&separations, &positions, &mut paths, &spatial_hash);
+        assert_eq!(paths.get(e).unwrap().path.steps, vec![6, 12, 8]);
+
+        // Ends next to a crowded destination.
+        *positions.get_mut(blocker).unwrap() = Point::new(3, 1);
+        spatial_hash_system(&entities, &positions, &mut spatial_hash).unwrap();
+        paths.get_mut(e).unwrap().path.steps = vec![7, 8];
+        *positions.get_mut(e).unwrap() = Point::new(2, 1);
+        run(&separations, &positions, &mut paths, &spatial_hash);
+        let path = &paths.get(e).unwrap().path;
+        assert_eq!(path.steps, vec![7, 12]);
+        assert_eq!(path.destination, 12);
+    }
+}
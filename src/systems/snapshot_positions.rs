@@ -0,0 +1,17 @@
+use crate::*;
+
+/// Records the position of the entities having an `InterpolatedPosition`.
+/// Run it after the movement systems, once per fixed update of the
+/// `FixedTimestep`.
+pub fn snapshot_positions_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    snapshots: &mut Components<InterpolatedPosition>,
+) -> SystemResult {
+    for (_, pos, snapshot) in join!(&entities && &positions && &mut snapshots) {
+        let (pos, snapshot) = (*pos.unwrap(), snapshot.unwrap());
+        snapshot.previous = Some(snapshot.current.unwrap_or(pos));
+        snapshot.current = Some(pos);
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use crate::*;
+use std::collections::HashSet;
+
+/// Updates the `SpatialHash` from the positions of the entities.
+/// Entities that moved are moved in it and entities that were deleted or lost
+/// their position are removed from it.
+pub fn spatial_hash_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    spatial_hash: &mut SpatialHash,
+) -> SystemResult {
+    let mut current = HashSet::new();
+    for (e, pos) in join!(&entities && &positions) {
+        let e = e.unwrap();
+        spatial_hash.insert(e, *pos.unwrap());
+        current.insert(e);
+    }
+    let gone = spatial_hash
+        .registered()
+        .filter(|e| !current.contains(e))
+        .collect::<Vec<_>>();
+    for e in gone {
+        spatial_hash.remove(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn tracks_entities() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut spatial_hash = SpatialHash::default();
+
+        let a = entities.create();
+        positions.insert(a, Point::new(1, 1));
+        let b = entities.create();
+        positions.insert(b, Point::new(1, 1));
+        let c = entities.create();
+        positions.insert(c, Point::new(3, 2));
+        spatial_hash_system(&entities, &positions, &mut spatial_hash).unwrap();
+        assert_eq!(spatial_hash.entities_at(Point::new(1, 1)), &[a, b]);
+        assert_eq!(
+            spatial_hash.entities_in_rect(Point::new(0, 0), 4, 4),
+            vec![a, b, c]
+        );
+        assert_eq!(
+            spatial_hash.entities_in_rect(Point::new(2, 0), 2, 2),
+            vec![]
+        );
+        assert_eq!(
+            spatial_hash.entities_in_rect(Point::new(1, 1), 1, 1),
+            vec![a, b]
+        );
+
+        *positions.get_mut(a).unwrap() = Point::new(2, 1);
+        positions.remove(c);
+        spatial_hash_system(&entities, &positions, &mut spatial_hash).unwrap();
+        assert_eq!(spatial_hash.entities_at(Point::new(1, 1)), &[b]);
+        assert_eq!(spatial_hash.entities_at(Point::new(2, 1)), &[a]);
+        assert!(spatial_hash.entities_at(Point::new(3, 2)).is_empty());
+        assert_eq!(spatial_hash.position_of(c), None);
+
+        entities.kill(b);
+        spatial_hash_system(&entities, &positions, &mut spatial_hash).unwrap();
+        assert!(spatial_hash.entities_at(Point::new(1, 1)).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::*;
+
+/// Clears the `AiPath` of entities that stopped making progress along it, for
+/// example when another mover keeps blocking their next tile, according to the
+/// `StuckDetection` thresholds.
+/// The path is requested again: `ai_pathing_system` calculates a new one for the
+/// `AiDestination`, and the `GotoEntity` is recalculated on the next run of
+/// `goto_entity_system`.
+/// Only entities having an unfinished `AiPath` are tracked, and only while they
+/// could step: entities waiting for their turn without `ActionPoints` aren't
+/// counted as stuck, and entities having a `MoveCooldown` are counted by the
+/// tiles it allows during `Time::delta_time`.
+/// Should run after `ai_movement_system`.
+#[allow(clippy::too_many_arguments)]
+pub fn stuck_detection_system(
+    entities: &Entities,
+    time: &Time,
+    positions: &Components<Point>,
+    paths: &mut Components<AiPath>,
+    gotos: &mut Components<GotoEntity>,
+    action_points: &Components<ActionPoints>,
+    cooldowns: &Components<MoveCooldown>,
+    detection: &mut StuckDetection,
+) -> SystemResult {
+    let mut stuck = vec![];
+    for (e, pos, path) in join!(&entities && &positions && &paths) {
+        let e = e.unwrap();
+        if path.unwrap().remaining() == 0 {
+            continue;
+        }
+        let waiting = action_points
+            .get(e)
+            .map(|p| p.current == 0)
+            .unwrap_or(false);
+        let chances = match cooldowns.get(e) {
+            _ if waiting => 0.0,
+            Some(cooldown) if cooldown.seconds_per_tile > 0.0 => {
+                time.delta_time().as_secs_f32() / cooldown.seconds_per_tile
+            }
+            _ => 1.0,
+        };
+        if detection.track(e, *pos.unwrap(), chances) {
+            stuck.push(e);
+        }
+    }
+    detection.retain(|e| {
+        entities.is_alive(e) && paths.get(e).map(|p| p.remaining() > 0).unwrap_or(false)
+    });
+    for e in stuck {
+        paths.remove(e);
+        if let Some(goto) = gotos.get_mut(e) {
+            goto.last_target = None;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn repath_when_blocked() {
+        let mut entities = Entities::default();
+        let mut dests = Components::<AiDestination>::default();
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut gotos = Components::<GotoEntity>::default();
+        let mut detection = StuckDetection::new(3, 0.0);
+        let mut global_map = Some(CollisionResource::new(
+            CollisionMap::new(10, 10),
+            Point::new(0, 0),
+        ));
+
+        let e = entities.create();
+        dests.insert(e, AiDestination::new(Point::new(1, 5)));
+        positions.insert(e, Point::new(1, 1));
+        let pathing = |paths: &mut Components<AiPath>, global_map: &Option<CollisionResource>| {
+            ai_pathing_system(
+                &entities,
+                &dests,
+                global_map,
+                &CollisionMapStack::default(),
+                &Components::default(),
+                &positions,
+                paths,
+                &mut PathingBudget::default(),
+                &mut Components::default(),
+            )
+            .unwrap();
+        };
+        pathing(&mut paths, &global_map);
+        assert_eq!(paths.get(e).unwrap().path.steps[1], 21);
+
+        // Another mover blocks the next tile, the old path keeps the entity in place.
+        global_map.as_mut().unwrap().map.set(1, 2);
+        for _ in 0..2 {
+            stuck_detection_system(
+                &entities,
+                &Time::default(),
+                &positions,
+                &mut paths,
+                &mut gotos,
+                &Components::default(),
+                &Components::default(),
+                &mut detection,
+            )
+            .unwrap();
+            assert!(paths.get(e).is_some());
+        }
+        stuck_detection_system(
+            &entities,
+            &Time::default(),
+            &positions,
+            &mut paths,
+            &mut gotos,
+            &Components::default(),
+            &Components::default(),
+            &mut detection,
+        )
+        .unwrap();
+        assert!(paths.get(e).is_none());
+
+        // The path requested again goes around the mover.
+        pathing(&mut paths, &global_map);
+        let path = &paths.get(e).unwrap().path;
+        assert!(path.success);
+        assert_ne!(path.steps[1], 21);
+    }
+    #[test]
+    fn waiting_for_turn() {
+        let mut entities = Entities::default();
+        let mut time = Time::default();
+        time.advance_frame(std::time::Duration::from_millis(500));
+        let mut positions = Components::<Point>::default();
+        let mut paths = Components::<AiPath>::default();
+        let mut action_points = Components::<ActionPoints>::default();
+        let mut cooldowns = Components::<MoveCooldown>::default();
+        let mut detection = StuckDetection::new(3, 0.0);
+        let path = |steps: Vec<usize>| {
+            let mut path = NavigationPath::new();
+            path.success = true;
+            path.steps = steps;
+            AiPath::new(path)
+        };
+
+        let (waiting, slow) = (entities.create(), entities.create());
+        for e in &[waiting, slow] {
+            positions.insert(*e, Point::new(1, 1));
+            paths.insert(*e, path(vec![11, 12]));
+        }
+        action_points.insert(waiting, ActionPoints::new(0, 1));
+        // One chance to step every 2 seconds, so every 4 frames.
+        cooldowns.insert(slow, MoveCooldown::new(2.0));
+
+        let mut run = |paths: &mut _, action_points: &_| {
+            stuck_detection_system(
+                &entities,
+                &time,
+                &positions,
+                paths,
+                &mut Components::default(),
+                action_points,
+                &cooldowns,
+                &mut detection,
+            )
+            .unwrap()
+        };
+        for _ in 0..11 {
+            run(&mut paths, &action_points);
+        }
+        assert!(paths.get(waiting).is_some());
+        assert!(paths.get(slow).is_some());
+        run(&mut paths, &action_points);
+        assert!(paths.get(slow).is_none());
+
+        // The turn starts, but the next tile stays blocked.
+        action_points.get_mut(waiting).unwrap().refill();
+        for _ in 0..2 {
+            run(&mut paths, &action_points);
+        }
+        assert!(paths.get(waiting).is_some());
+        run(&mut paths, &action_points);
+        assert!(paths.get(waiting).is_none());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::*;
+
+/// Incrementally updates the `CollisionResource` from the entities having a
+/// `Collision` and a position. Only tiles of entities that moved, appeared or
+/// disappeared since the last frame are changed, on the dynamic layer of the map.
+///
+/// Use this instead of `combine_collision_system`, which rebuilds the whole map.
+/// A tile is only unset if no other `Collision` entity is on it.
+/// Entities block every tile of their `Footprint` or `MultiSprite`.
+pub fn track_collision_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    collisions: &Components<Collision>,
+    footprints: &Components<Footprint>,
+    multi_sprites: &Components<MultiSprite>,
+    tracker: &mut CollisionTracker,
+    global_map: &mut Option<CollisionResource>,
+) -> SystemResult {
+    let global_map = global_map.as_mut().unwrap();
+    let mut current = HashMap::new();
+    let mut occupied = HashMap::new();
+    for (e, pos, _) in join!(&entities && &positions && &collisions) {
+        let e = e.unwrap();
+        let tiles = Footprint::tiles_of(footprints.get(e), multi_sprites.get(e), *pos.unwrap());
+        for tile in tiles.iter() {
+            *occupied.entry(*tile).or_insert(0) += 1;
+        }
+        current.insert(e, tiles);
+    }
+    for (e, old) in tracker.tiles.iter() {
+        if current.get(e) == Some(old) {
+            continue;
+        }
+        for tile in old {
+            if !occupied.contains_key(tile) && global_map.is_inside(tile) {
+                let (x, y) = global_map.relative_point(tile);
+                global_map.map.unset_dynamic(x, y);
+            }
+        }
+    }
+    for (e, tiles) in current.iter() {
+        if tracker.tiles.get(e) == Some(tiles) {
+            continue;
+        }
+        for tile in tiles {
+            if global_map.is_inside(tile) {
+                let (x, y) = global_map.relative_point(tile);
+                global_map.map.set_dynamic(x, y);
+            }
+        }
+    }
+    tracker.tiles = current;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn incremental_updates() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut collisions = Components::<Collision>::default();
+        let mut tracker = CollisionTracker::default();
+        let mut map = CollisionMap::new(5, 5);
+        map.set(4, 4);
+        let mut global_map = Some(CollisionResource::new(map, Point::new(-1, -1)));
+
+        let a = entities.create();
+        positions.insert(a, Point::new(0, 0));
+        collisions.insert(a, Collision);
+        let b = entities.create();
+        positions.insert(b, Point::new(2, 2));
+        collisions.insert(b, Collision);
+
+        track_collision_system(
+            &entities,
+            &positions,
+            &collisions,
+            &Components::default(),
+            &Components::default(),
+            &mut tracker,
+            &mut global_map,
+        )
+        .unwrap();
+        let set =
+            |m: &Option<CollisionResource>| m.as_ref().unwrap().map.iter_set().collect::<Vec<_>>();
+        assert_eq!(set(&global_map), vec![(1, 1), (3, 3), (4, 4)]);
+
+        *positions.get_mut(a).unwrap() = Point::new(2, 2);
+        track_collision_system(
+            &entities,
+            &positions,
+            &collisions,
+            &Components::default(),
+            &Components::default(),
+            &mut tracker,
+            &mut global_map,
+        )
+        .unwrap();
+        assert_eq!(set(&global_map), vec![(3, 3), (4, 4)]);
+
+        collisions.remove(b);
+        *positions.get_mut(a).unwrap() = Point::new(1, 0);
+        track_collision_system(
+            &entities,
+            &positions,
+            &collisions,
+            &Components::default(),
+            &Components::default(),
+            &mut tracker,
+            &mut global_map,
+        )
+        .unwrap();
+        assert_eq!(set(&global_map), vec![(2, 1), (4, 4)]);
+    }
+    #[test]
+    fn building_footprint() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut collisions = Components::<Collision>::default();
+        let mut footprints = Components::<Footprint>::default();
+        let mut multi_sprites = Components::<MultiSprite>::default();
+        let mut tracker = CollisionTracker::default();
+        let mut global_map = Some(CollisionResource::new(
+            CollisionMap::new(6, 6),
+            Point::new(0, 0),
+        ));
+
+        let building = entities.create();
+        positions.insert(building, Point::new(1, 1));
+        collisions.insert(building, Collision);
+        multi_sprites.insert(
+            building,
+            MultiSprite::from_string("####+#", 3, 2, RGBA::named(WHITE), None),
+        );
+
+        let mut run = |footprints: &Components<Footprint>, positions: &Components<Point>| {
+            track_collision_system(
+                &entities,
+                positions,
+                &collisions,
+                footprints,
+                &multi_sprites,
+                &mut tracker,
+                &mut global_map,
+            )
+            .unwrap();
+            global_map
+                .as_ref()
+                .unwrap()
+                .map
+                .iter_set()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            run(&footprints, &positions),
+            vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]
+        );
+
+        // An explicit footprint overrides the sprite's size.
+        footprints.insert(building, Footprint::new(1, 2));
+        assert_eq!(run(&footprints, &positions), vec![(1, 1), (1, 2)]);
+
+        footprints.remove(building);
+        *positions.get_mut(building).unwrap() = Point::new(3, 4);
+        assert_eq!(
+            run(&footprints, &positions),
+            vec![(3, 4), (4, 4), (5, 4), (3, 5), (4, 5), (5, 5)]
+        );
+    }
+}
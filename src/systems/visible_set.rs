@@ -0,0 +1,78 @@
+use crate::*;
+
+/// Rebuilds the `VisibleSet` from the entities having a `Viewer` and a position.
+/// Walls of the `CollisionResource` block the view.
+/// Then records the position of the visible entities having `Remembered`.
+pub fn visible_set_system(
+    entities: &Entities,
+    positions: &Components<Point>,
+    viewers: &Components<Viewer>,
+    remembered: &mut Components<Remembered>,
+    global_map: &Option<CollisionResource>,
+    visible: &mut VisibleSet,
+) -> SystemResult {
+    let global_map = global_map.as_ref().unwrap();
+    visible.tiles.clear();
+    for (_, pos, viewer) in join!(&entities && &positions && &viewers) {
+        visible.add_view(global_map, *pos.unwrap(), viewer.unwrap().radius);
+    }
+    for (_, pos, memory) in join!(&entities && &positions && &mut remembered) {
+        let pos = pos.unwrap();
+        if visible.is_visible(pos) {
+            memory.unwrap().last_seen = Some(*pos);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn remember_last_seen() {
+        let mut entities = Entities::default();
+        let mut positions = Components::<Point>::default();
+        let mut viewers = Components::<Viewer>::default();
+        let mut remembered = Components::<Remembered>::default();
+        let mut visible = VisibleSet::default();
+        let mut map = CollisionMap::new(10, 1);
+        map.set(5, 0);
+        let global_map = Some(CollisionResource::new(map, Point::new(0, 0)));
+
+        let player = entities.create();
+        positions.insert(player, Point::new(0, 0));
+        viewers.insert(player, Viewer::new(8));
+        let monster = entities.create();
+        positions.insert(monster, Point::new(3, 0));
+        remembered.insert(monster, Remembered::new(0.5));
+
+        let run = |positions: &Components<Point>,
+                   remembered: &mut Components<Remembered>,
+                   visible: &mut VisibleSet| {
+            visible_set_system(
+                &entities,
+                positions,
+                &viewers,
+                remembered,
+                &global_map,
+                visible,
+            )
+            .unwrap();
+        };
+        run(&positions, &mut remembered, &mut visible);
+        assert!(visible.is_visible(&Point::new(5, 0)));
+        assert!(!visible.is_visible(&Point::new(6, 0)));
+        assert_eq!(
+            remembered.get(monster).unwrap().last_seen,
+            Some(Point::new(3, 0))
+        );
+
+        // Behind the wall, the last seen position is kept.
+        *positions.get_mut(monster).unwrap() = Point::new(7, 0);
+        run(&positions, &mut remembered, &mut visible);
+        assert_eq!(
+            remembered.get(monster).unwrap().last_seen,
+            Some(Point::new(3, 0))
+        );
+    }
+}
@@ -35,6 +35,60 @@ pub fn move_position(old_position: &Point, dir: Direction) -> Point {
     }
 }
 
+/// Gives the tiles on the straight line between two points, using Bresenham's algorithm.
+/// Both ends are included and the tiles are ordered from `from` to `to`.
+pub fn bresenham_line(from: Point, to: Point) -> Vec<Point> {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut current = from;
+    let mut points = Vec::with_capacity((dx - dy + 1) as usize);
+    loop {
+        points.push(current);
+        if current == to {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            current.x += step_x;
+        }
+        if e2 <= dx {
+            err += dx;
+            current.y += step_y;
+        }
+    }
+    points
+}
+
+/// Moves one tile from `from` towards `to`, along the axis where they are the
+/// furthest apart. This is how `GotoStraight` walks, so it never moves diagonally.
+pub fn step_towards(from: Point, to: Point) -> Point {
+    let delta_x = to.x - from.x;
+    let delta_y = to.y - from.y;
+    let mut next = from;
+    if delta_x.abs() >= delta_y.abs() {
+        next.x += delta_x.signum();
+    } else {
+        next.y += delta_y.signum();
+    }
+    next
+}
+
+/// Interpolates linearly between two colors, alpha included.
+/// `t` is clamped between 0, giving `a`, and 1, giving `b`.
+pub fn lerp_rgba(a: RGBA, b: RGBA, t: f32) -> RGBA {
+    let t = t.clamp(0.0, 1.0);
+    RGBA::from_f32(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
+
 /// Initializes the default rayon threadpool for compability with the
 /// thread profiler.
 pub fn init_thread_pool() -> Result<(), ThreadPoolBuildError> {
@@ -85,4 +139,42 @@ mod tests {
         assert!(position_inside_rect(-1, -1, -2, -2, 2, 2));
         assert!(!position_inside_rect(-1, -1, -2, -2, 1, 1));
     }
+    #[test]
+    fn line() {
+        assert_eq!(
+            bresenham_line(Point::new(1, 1), Point::new(1, 1)),
+            vec![Point::new(1, 1)]
+        );
+        assert_eq!(
+            bresenham_line(Point::new(0, 0), Point::new(-3, 0)),
+            vec![
+                Point::new(0, 0),
+                Point::new(-1, 0),
+                Point::new(-2, 0),
+                Point::new(-3, 0)
+            ]
+        );
+        assert_eq!(
+            bresenham_line(Point::new(0, 0), Point::new(2, 2)),
+            vec![Point::new(0, 0), Point::new(1, 1), Point::new(2, 2)]
+        );
+        let l = bresenham_line(Point::new(0, 0), Point::new(5, 2));
+        assert_eq!(l.len(), 6);
+        assert_eq!(l[0], Point::new(0, 0));
+        assert_eq!(l[5], Point::new(5, 2));
+    }
+    #[test]
+    fn lerp_colors() {
+        let a = RGBA::from_f32(0.0, 0.2, 1.0, 1.0);
+        let b = RGBA::from_f32(1.0, 0.4, 0.0, 0.0);
+        assert_eq!(lerp_rgba(a, b, 0.0), a);
+        assert_eq!(lerp_rgba(a, b, 1.0), b);
+        assert_eq!(lerp_rgba(a, b, -1.0), a);
+        assert_eq!(lerp_rgba(a, b, 2.0), b);
+        let mid = lerp_rgba(a, b, 0.5);
+        assert!((mid.r - 0.5).abs() < 0.0001);
+        assert!((mid.g - 0.3).abs() < 0.0001);
+        assert!((mid.b - 0.5).abs() < 0.0001);
+        assert!((mid.a - 0.5).abs() < 0.0001);
+    }
 }